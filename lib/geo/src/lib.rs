@@ -1,9 +1,14 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+#[cfg(feature = "std")]
 use geographiclib_rs::{DirectGeodesic, Geodesic, InverseGeodesic};
 
-use crate::math::heading_to_point;
+#[cfg(feature = "std")]
+use crate::math::{heading_to_point, rotate_point, Vec2};
 
-mod math;
+pub mod math;
 
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Cardinal {
     North,
@@ -12,6 +17,20 @@ pub enum Cardinal {
     West,
 }
 
+#[cfg(feature = "std")]
+impl Cardinal {
+    /// The bearing in degrees pointing this way.
+    pub fn bearing(&self) -> f64 {
+        match self {
+            Cardinal::North => 0.0,
+            Cardinal::East => 90.0,
+            Cardinal::South => 180.0,
+            Cardinal::West => 270.0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl std::fmt::Display for Cardinal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -24,6 +43,7 @@ impl std::fmt::Display for Cardinal {
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DMS {
     pub degrees: u16,
@@ -32,6 +52,7 @@ pub struct DMS {
     pub cardinal: Option<Cardinal>,
 }
 
+#[cfg(feature = "std")]
 impl DMS {
     pub fn new(degrees: u16, minutes: u8, seconds: f64, cardinal: Cardinal) -> Self {
         Self {
@@ -94,6 +115,7 @@ impl DMS {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::fmt::Display for DMS {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(cardinal) = self.cardinal {
@@ -108,12 +130,58 @@ impl std::fmt::Display for DMS {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// The shape of the Earth used for the geodesic calculations.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ellipsoid {
+    /// Equatorial radius in meters.
+    pub semi_major_axis: f64,
+    pub flattening: f64,
+}
+
+#[cfg(feature = "std")]
+impl Ellipsoid {
+    pub const WGS84: Ellipsoid = Ellipsoid {
+        semi_major_axis: 6_378_137.0,
+        flattening: 1.0 / 298.257_223_563,
+    };
+
+    /// The mean radius of the Earth in meters, as used by spherical models.
+    pub const MEAN_RADIUS: f64 = 6_371_008.8;
+
+    pub fn new(semi_major_axis: f64, flattening: f64) -> Self {
+        Self {
+            semi_major_axis,
+            flattening,
+        }
+    }
+
+    /// A sphere with the given radius in meters.
+    pub fn sphere(radius: f64) -> Self {
+        Self::new(radius, 0.0)
+    }
+
+    fn geodesic(&self) -> Geodesic {
+        Geodesic::new(self.semi_major_axis, self.flattening)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Ellipsoid {
+    fn default() -> Self {
+        Self::WGS84
+    }
+}
+
+/// Defaults to 0,0.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct LatLon {
     pub lat: f64,
     pub lon: f64,
 }
 
+#[cfg(feature = "std")]
 impl LatLon {
     /// Create a new LatLon using the latitude & longitude
     /// in degrees.
@@ -121,6 +189,67 @@ impl LatLon {
         Self { lat, lon }
     }
 
+    /// Same as `new`, but rejecting a latitude or longitude outside of
+    /// -90..=90 and -180..=180.
+    pub fn try_new(lat: f64, lon: f64) -> Result<Self, String> {
+        if !(-90.0..=90.0).contains(&lat) {
+            return Err(format!("latitude out of range: {lat}"));
+        }
+        if !(-180.0..=180.0).contains(&lon) {
+            return Err(format!("longitude out of range: {lon}"));
+        }
+        Ok(Self { lat, lon })
+    }
+
+    /// Parse a latitude and longitude in decimal degrees, separated by a
+    /// comma or spaces, e.g. `34.7178, 32.4856`. Either can be followed by
+    /// its cardinal direction instead of a sign, e.g. `34.7178 N, 32.4856 E`.
+    pub fn from_decimal_str(s: &str) -> Result<Self, String> {
+        let mut parts: Vec<String> = vec![];
+        for token in s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+        {
+            // a cardinal direction apart from its degrees, e.g. `34.7178 N`
+            match parts.last_mut() {
+                Some(part) if token.len() == 1 && token.chars().all(char::is_alphabetic) => {
+                    part.push_str(token)
+                }
+                _ => parts.push(token.to_string()),
+            }
+        }
+        let [lat, lon] = parts.as_slice() else {
+            return Err(format!("invalid coordinates: {s}"));
+        };
+        let lat = parse_degrees(lat, Cardinal::North, Cardinal::South)
+            .ok_or_else(|| format!("invalid latitude: {lat}"))?;
+        let lon = parse_degrees(lon, Cardinal::East, Cardinal::West)
+            .ok_or_else(|| format!("invalid longitude: {lon}"))?;
+        Self::try_new(lat, lon)
+    }
+
+    /// Parse a latitude and longitude written by `to_dms_string`, e.g.
+    /// `N34°43'04.00" E032°29'08.00"`.
+    pub fn from_dms_str(s: &str) -> Result<Self, String> {
+        let (lat, lon) = s
+            .split_once(' ')
+            .ok_or_else(|| format!("invalid coordinates: {s}"))?;
+        let lat = parse_dms(lat, Cardinal::North, Cardinal::South)
+            .ok_or_else(|| format!("invalid latitude: {lat}"))?;
+        let lon = parse_dms(lon, Cardinal::East, Cardinal::West)
+            .ok_or_else(|| format!("invalid longitude: {lon}"))?;
+        Self::try_new(lat, lon)
+    }
+
+    /// Return a copy with the longitude wrapped into -180..180 and the latitude
+    /// clamped to the poles, for positions that come from noisy sources.
+    pub fn normalized(&self) -> Self {
+        Self {
+            lat: self.lat.clamp(-90.0, 90.0),
+            lon: (self.lon + 180.0).rem_euclid(360.0) - 180.0,
+        }
+    }
+
     pub fn from_radians(lat: f64, lon: f64) -> Self {
         Self {
             lat: lat.to_degrees(),
@@ -146,6 +275,39 @@ impl LatLon {
         )
     }
 
+    /// Both coordinates in degrees, minutes and seconds with the cardinal
+    /// first, as written in aviation, e.g. `N34°43'04.00" E032°29'08.00"`.
+    pub fn to_dms_string(&self) -> String {
+        // rounded to the hundredth of a second first, so 59.996" carries into
+        // the minutes
+        let hundredths = |degrees: f64| (degrees.abs() * 360_000.0).round() as u64;
+        let lat = hundredths(self.lat);
+        let lon = hundredths(self.lon);
+        let north_south = if self.lat < 0.0 {
+            Cardinal::South
+        } else {
+            Cardinal::North
+        };
+        let east_west = if self.lon < 0.0 {
+            Cardinal::West
+        } else {
+            Cardinal::East
+        };
+        format!(
+            "{}{:02}°{:02}'{:02}.{:02}\" {}{:03}°{:02}'{:02}.{:02}\"",
+            north_south,
+            lat / 360_000,
+            lat / 6000 % 60,
+            lat / 100 % 60,
+            lat % 100,
+            east_west,
+            lon / 360_000,
+            lon / 6000 % 60,
+            lon / 100 % 60,
+            lon % 100
+        )
+    }
+
     pub fn latitude(&self) -> f64 {
         self.lat
     }
@@ -164,19 +326,207 @@ impl LatLon {
         (p.x as f64 * distance, p.y as f64 * distance)
     }
 
+    /// Project onto a flat frame centered on `origin`, returning the meters
+    /// east (x) and north (y) of it. The distance and bearing from the origin
+    /// are kept, so it's only accurate close to the origin.
+    pub fn to_local_xy(&self, origin: &LatLon) -> Vec2 {
+        let (distance, bearing) = origin.distance_and_bearing(self);
+        rotate_point(Vec2::zero(), Vec2::new(0.0, distance), bearing.to_radians())
+    }
+
+    /// The inverse of `to_local_xy`, the position `xy` meters east and north
+    /// of `origin`.
+    pub fn from_local_xy(origin: &LatLon, xy: &Vec2) -> LatLon {
+        let distance = xy.x.hypot(xy.y);
+        let bearing = xy.x.atan2(xy.y).to_degrees();
+        origin.destination(bearing, distance)
+    }
+
+    /// Return a new latitude/longitude offset by a distance in meters towards a
+    /// cardinal direction.
+    pub fn offset(&self, cardinal: Cardinal, distance: f64) -> LatLon {
+        self.destination(cardinal.bearing(), distance)
+    }
+
     /// Return a new latitude/longitude offset by a distance in meters and a bearing
     /// in degrees.
     pub fn destination(&self, bearing: f64, distance: f64) -> LatLon {
-        let (lat, lon) = Geodesic::wgs84().direct(self.lat, self.lon, bearing, distance);
+        self.destination_on(bearing, distance, &Ellipsoid::WGS84)
+    }
+
+    /// Same as `destination`, on the given ellipsoid.
+    pub fn destination_on(&self, bearing: f64, distance: f64, ellipsoid: &Ellipsoid) -> LatLon {
+        let (lat, lon) = ellipsoid
+            .geodesic()
+            .direct(self.lat, self.lon, bearing, distance);
         Self { lat, lon }
     }
 
+    /// Return the point `fraction` (0-1) of the way along the geodesic from
+    /// this to another latitude and longitude.
+    pub fn intermediate(&self, other: &LatLon, fraction: f64) -> LatLon {
+        let (distance, bearing) = self.distance_and_bearing(other);
+        self.destination(bearing, distance * fraction)
+    }
+
+    /// Return `segments + 1` points evenly spaced along the geodesic from this
+    /// to another latitude and longitude, both ends included, e.g. for drawing
+    /// the route as a curve on a map. With no segments it's just the ends.
+    pub fn great_circle_points(&self, other: &LatLon, segments: usize) -> Vec<LatLon> {
+        if segments == 0 {
+            return vec![*self, *other];
+        }
+        (0..=segments)
+            .map(|i| match i {
+                0 => *self,
+                i if i == segments => *other,
+                i => self.intermediate(other, i as f64 / segments as f64),
+            })
+            .collect()
+    }
+
     /// Return the distance in meters between this and another latitude and longitude.
     pub fn distance(&self, other: &LatLon) -> f64 {
-        Geodesic::wgs84().inverse(self.lat, self.lon, other.lat, other.lon)
+        self.distance_and_bearing(other).0
+    }
+
+    /// Same as `distance`, on the given ellipsoid.
+    pub fn distance_on(&self, other: &LatLon, ellipsoid: &Ellipsoid) -> f64 {
+        self.distance_and_bearing_on(other, ellipsoid).0
+    }
+
+    /// Return the distance in meters between this and another latitude and
+    /// longitude along the rhumb line, the route flown holding a constant
+    /// course. It's on a sphere of `Ellipsoid::MEAN_RADIUS`, so compare it
+    /// with `distance_on` that sphere rather than with `distance`.
+    pub fn rhumb_distance(&self, other: &LatLon) -> f64 {
+        let (lat1, lon1) = self.to_radians();
+        let (lat2, lon2) = other.to_radians();
+        let d_lat = lat2 - lat1;
+        // the difference in latitude on a Mercator projection
+        let d_psi = ((std::f64::consts::FRAC_PI_4 + lat2 / 2.0).tan()
+            / (std::f64::consts::FRAC_PI_4 + lat1 / 2.0).tan())
+        .ln();
+        // east-west lines stay on the same parallel
+        let q = if d_psi.abs() > 1e-12 {
+            d_lat / d_psi
+        } else {
+            lat1.cos()
+        };
+        // the shorter way around, across the antimeridian if need be
+        let d_lon = (lon2 - lon1 + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU)
+            - std::f64::consts::PI;
+        d_lat.hypot(q * d_lon) * Ellipsoid::MEAN_RADIUS
+    }
+
+    /// Return the initial bearing in degrees (0-360) from this to another
+    /// latitude and longitude.
+    pub fn bearing(&self, other: &LatLon) -> f64 {
+        self.distance_and_bearing(other).1
+    }
+
+    /// Return both the distance in meters and the initial bearing in degrees (0-360)
+    /// to another latitude and longitude, using a single geodesic calculation.
+    pub fn distance_and_bearing(&self, other: &LatLon) -> (f64, f64) {
+        self.distance_and_bearing_on(other, &Ellipsoid::WGS84)
+    }
+
+    /// Same as `distance_and_bearing`, on the given ellipsoid.
+    pub fn distance_and_bearing_on(&self, other: &LatLon, ellipsoid: &Ellipsoid) -> (f64, f64) {
+        let (distance, azimuth, _, _) = ellipsoid
+            .geodesic()
+            .inverse(self.lat, self.lon, other.lat, other.lon);
+        (distance, (azimuth + 360.0) % 360.0)
+    }
+}
+
+/// Degrees and whole minutes, e.g. `34°43'N 032°29'E`, the way positions are
+/// written on charts.
+#[cfg(feature = "std")]
+impl std::fmt::Display for LatLon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // rounded to the minute first, so 59.6' carries into the degrees
+        let minutes = |degrees: f64| (degrees.abs() * 60.0).round() as u32;
+        let lat = minutes(self.lat);
+        let lon = minutes(self.lon);
+        let north_south = if self.lat < 0.0 {
+            Cardinal::South
+        } else {
+            Cardinal::North
+        };
+        let east_west = if self.lon < 0.0 {
+            Cardinal::West
+        } else {
+            Cardinal::East
+        };
+        write!(
+            f,
+            "{:02}°{:02}'{} {:03}°{:02}'{}",
+            lat / 60,
+            lat % 60,
+            north_south,
+            lon / 60,
+            lon % 60,
+            east_west
+        )
+    }
+}
+
+/// Parse decimal degrees, optionally followed by the `positive` or `negative`
+/// cardinal direction giving the sign, e.g. `32.4856E`.
+#[cfg(feature = "std")]
+fn parse_degrees(s: &str, positive: Cardinal, negative: Cardinal) -> Option<f64> {
+    let s = s.to_uppercase();
+    let (degrees, sign) = if let Some(degrees) = s.strip_suffix(&positive.to_string()) {
+        (degrees, Some(1.0))
+    } else if let Some(degrees) = s.strip_suffix(&negative.to_string()) {
+        (degrees, Some(-1.0))
+    } else {
+        (s.as_str(), None)
+    };
+    let degrees: f64 = degrees.parse().ok()?;
+    match sign {
+        // the direction already gives the sign, so `-34.7S` is ambiguous
+        Some(_) if degrees.is_sign_negative() => None,
+        Some(sign) => Some(sign * degrees),
+        None => Some(degrees),
     }
 }
 
+/// Parse degrees, minutes and seconds preceded by the `positive` or `negative`
+/// cardinal direction giving the sign, e.g. `E032°29'08.00"`.
+#[cfg(feature = "std")]
+fn parse_dms(s: &str, positive: Cardinal, negative: Cardinal) -> Option<f64> {
+    let (sign, dms) = if let Some(dms) = s.strip_prefix(&positive.to_string()) {
+        (1.0, dms)
+    } else if let Some(dms) = s.strip_prefix(&negative.to_string()) {
+        (-1.0, dms)
+    } else {
+        return None;
+    };
+    let (degrees, rest) = dms.split_once('°')?;
+    let (minutes, seconds) = rest.split_once('\'')?;
+    let degrees: u16 = degrees.parse().ok()?;
+    let minutes: u8 = minutes.parse().ok()?;
+    let seconds: f64 = seconds.strip_suffix('"')?.parse().ok()?;
+    if minutes >= 60 || !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    Some(sign * (degrees as f64 + minutes as f64 / 60.0 + seconds / 3600.0))
+}
+
+/// Return the item closest to `origin`, with `pos` giving each item's position,
+/// or none if there aren't any items.
+#[cfg(feature = "std")]
+pub fn nearest<T>(origin: LatLon, items: &[T], pos: impl Fn(&T) -> LatLon) -> Option<&T> {
+    items
+        .iter()
+        .map(|item| (origin.distance(&pos(item)), item))
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, item)| item)
+}
+
+#[cfg(feature = "std")]
 #[cfg(test)]
 mod test {
     use super::*;
@@ -195,6 +545,61 @@ mod test {
         lon: 33.624722,
     };
 
+    #[test]
+    fn test_latlon_display() {
+        assert_eq!("34°43'N 032°29'E", LCPH.to_string());
+        assert_eq!(
+            "33°57'S 151°11'E",
+            LatLon::new(-33.946111, 151.177222).to_string()
+        );
+        assert_eq!(
+            "40°38'N 073°47'W",
+            LatLon::new(40.639722, -73.778889).to_string()
+        );
+        // 59.7' rounds up into the next degree
+        assert_eq!("01°00'N 000°00'E", LatLon::new(0.995, 0.0).to_string());
+    }
+
+    #[test]
+    fn test_latlon_to_dms_string() {
+        assert_eq!("N34°52'30.00\" E033°37'29.00\"", LCLK.to_dms_string());
+        assert_eq!(
+            "S33°56'46.00\" W073°46'44.00\"",
+            LatLon::new(-33.946111, -73.778889).to_dms_string()
+        );
+        // 59.996" rounds up into the next minute, and on into the degree
+        assert_eq!(
+            "N34°44'00.00\" E033°00'00.00\"",
+            LatLon::new(
+                34.0 + 43.0 / 60.0 + 59.996 / 3600.0,
+                32.0 + 59.0 / 60.0 + 59.996 / 3600.0
+            )
+            .to_dms_string()
+        );
+    }
+
+    #[test]
+    fn test_latlon_normalized_wraps_longitude() {
+        let pos = LatLon::new(34.0, 190.0).normalized();
+        assert_eq!(34.0, pos.latitude());
+        assert_eq!(-170.0, pos.longitude());
+
+        let pos = LatLon::new(34.0, -190.0).normalized();
+        assert_eq!(170.0, pos.longitude());
+
+        assert_eq!(32.5, round_decimal(LCPH.normalized().longitude(), 1));
+    }
+
+    #[test]
+    fn test_latlon_normalized_clamps_latitude() {
+        let pos = LatLon::new(91.0, 32.0).normalized();
+        assert_eq!(90.0, pos.latitude());
+        assert_eq!(32.0, pos.longitude());
+
+        let pos = LatLon::new(-90.5, 32.0).normalized();
+        assert_eq!(-90.0, pos.latitude());
+    }
+
     #[test]
     fn test_latlon_destination() {
         let distance = (120.0 * NM_TO_KM) * 1000.0;
@@ -204,11 +609,195 @@ mod test {
         assert_eq!(distance.round(), LCPH.distance(&dest).round());
     }
 
+    #[test]
+    fn test_latlon_great_circle_points() {
+        let jfk = LatLon::new(40.639722, -73.778889);
+        let egll = LatLon::new(51.4775, -0.461389);
+        let points = jfk.great_circle_points(&egll, 10);
+        assert_eq!(11, points.len());
+        assert_eq!(jfk, points[0]);
+        assert_eq!(egll, points[10]);
+        let distances: Vec<f64> = points.iter().map(|p| jfk.distance(p)).collect();
+        assert!(distances.windows(2).all(|d| d[0] < d[1]));
+        // evenly spaced, along the great circle rather than the rhumb line
+        assert!((jfk.distance(&egll) / 2.0 - distances[5]).abs() < 1.0);
+        assert!(points[5].latitude() > 52.0);
+
+        assert_eq!(vec![jfk, egll], jfk.great_circle_points(&egll, 0));
+    }
+
+    #[test]
+    fn test_latlon_offset() {
+        let north = LCPH.offset(Cardinal::North, 1000.0);
+        assert!(north.latitude() > LCPH.latitude());
+        assert_eq!(
+            round_decimal(LCPH.longitude(), 9),
+            round_decimal(north.longitude(), 9)
+        );
+        let back = north.offset(Cardinal::South, 1000.0);
+        assert_eq!(
+            round_decimal(LCPH.latitude(), 9),
+            round_decimal(back.latitude(), 9)
+        );
+        assert_eq!(
+            round_decimal(LCPH.longitude(), 9),
+            round_decimal(back.longitude(), 9)
+        );
+
+        let west = LCPH.offset(Cardinal::West, 1000.0);
+        assert!(west.longitude() < LCPH.longitude());
+        let back = west.offset(Cardinal::East, 1000.0);
+        assert_eq!(1000.0, LCPH.distance(&west).round());
+        // east and west aren't along a geodesic, so it's only roughly back
+        assert!(LCPH.distance(&back) < 1.0);
+    }
+
     #[test]
     fn test_latlon_distance() {
         assert_eq!(105_698., LCPH.distance(&LCLK).round());
     }
 
+    #[test]
+    fn test_latlon_try_new() {
+        assert_eq!(Ok(LCPH), LatLon::try_new(LCPH.lat, LCPH.lon));
+        assert_eq!(Ok(LatLon::new(-90., 180.)), LatLon::try_new(-90., 180.));
+        assert!(LatLon::try_new(90.5, 32.4).is_err());
+        assert!(LatLon::try_new(34.7, -180.5).is_err());
+        assert!(LatLon::try_new(f64::NAN, 32.4).is_err());
+    }
+
+    #[test]
+    fn test_latlon_from_decimal_str() {
+        let expected = Ok(LatLon::new(34.7178, 32.4856));
+        assert_eq!(expected, LatLon::from_decimal_str("34.7178, 32.4856"));
+        assert_eq!(expected, LatLon::from_decimal_str("34.7178 32.4856"));
+        assert_eq!(expected, LatLon::from_decimal_str(" 34.7178,32.4856 "));
+    }
+
+    #[test]
+    fn test_latlon_from_decimal_str_cardinals() {
+        assert_eq!(
+            Ok(LatLon::new(34.7178, 32.4856)),
+            LatLon::from_decimal_str("34.7178 N, 32.4856 E")
+        );
+        assert_eq!(
+            Ok(LatLon::new(-33.9461, -118.4085)),
+            LatLon::from_decimal_str("33.9461S 118.4085W")
+        );
+        assert_eq!(
+            Ok(LatLon::new(-33.9461, 151.1772)),
+            LatLon::from_decimal_str("33.9461 s, 151.1772 e")
+        );
+    }
+
+    #[test]
+    fn test_latlon_from_dms_str() {
+        let position = LatLon::from_dms_str("S33°56'46.00\" W073°46'44.00\"").unwrap();
+        assert_eq!(-33.946111, round_decimal(position.latitude(), 6));
+        assert_eq!(-73.778889, round_decimal(position.longitude(), 6));
+        assert_eq!(
+            "N34°52'30.00\" E033°37'29.00\"",
+            LatLon::from_dms_str(&LCLK.to_dms_string())
+                .unwrap()
+                .to_dms_string()
+        );
+        assert!(LatLon::from_dms_str("34.7178 32.4856").is_err());
+        assert!(LatLon::from_dms_str("E34°43'04.00\" N032°29'08.00\"").is_err());
+        assert!(LatLon::from_dms_str("N34°60'04.00\" E032°29'08.00\"").is_err());
+        assert!(LatLon::from_dms_str("N94°43'04.00\" E032°29'08.00\"").is_err());
+    }
+
+    #[test]
+    fn test_latlon_from_decimal_str_invalid() {
+        assert!(LatLon::from_decimal_str("34.7178").is_err());
+        assert!(LatLon::from_decimal_str("34.7178, 32.4856, 10").is_err());
+        assert!(LatLon::from_decimal_str("34.7178 E, 32.4856 N").is_err());
+        assert!(LatLon::from_decimal_str("-34.7178 S, 32.4856 E").is_err());
+        assert!(LatLon::from_decimal_str("north, east").is_err());
+        assert!(LatLon::from_decimal_str("94.7178, 32.4856").is_err());
+    }
+
+    #[test]
+    fn test_latlon_rhumb_distance() {
+        let sphere = Ellipsoid::sphere(Ellipsoid::MEAN_RADIUS);
+        // almost due east, so hardly any longer than the great circle
+        assert_eq!(105_478., LCPH.rhumb_distance(&LCLK).round());
+        assert_eq!(105_477., LCPH.distance_on(&LCLK, &sphere).round());
+        // JFK to Heathrow
+        let jfk = LatLon::new(40.639722, -73.778889);
+        let egll = LatLon::new(51.4775, -0.461389);
+        assert_eq!(5_758_044., jfk.rhumb_distance(&egll).round());
+        assert_eq!(5_539_457., jfk.distance_on(&egll, &sphere).round());
+        assert_eq!(0., LCPH.rhumb_distance(&LCPH));
+        // along the equator, across the antimeridian
+        assert_eq!(
+            111_195.,
+            LatLon::new(0., 179.5)
+                .rhumb_distance(&LatLon::new(0., -179.5))
+                .round()
+        );
+    }
+
+    #[test]
+    fn test_latlon_bearing() {
+        assert_eq!(80., LCPH.bearing(&LCLK).round());
+        assert_eq!(261., LCLK.bearing(&LCPH).round());
+    }
+
+    #[test]
+    fn test_latlon_distance_and_bearing() {
+        let (distance, bearing) = LCPH.distance_and_bearing(&LCLK);
+        assert_eq!(105_698., distance.round());
+        assert_eq!(80., bearing.round());
+
+        let (distance, bearing) = LCLK.distance_and_bearing(&LCPH);
+        assert_eq!(105_698., distance.round());
+        assert_eq!(261., bearing.round());
+
+        // a degree due west along the equator, the bearing wrapped to 0-360
+        let (distance, bearing) =
+            LatLon::new(0.0, 0.0).distance_and_bearing(&LatLon::new(0.0, -1.0));
+        assert_eq!(111_319., distance.round());
+        assert_eq!(270., bearing.round());
+    }
+
+    #[test]
+    fn test_latlon_to_local_xy() {
+        let north = LCPH.offset(Cardinal::North, 10.0).to_local_xy(&LCPH);
+        assert_eq!(
+            (0.0, 10.0),
+            (round_decimal(north.x, 6), round_decimal(north.y, 6))
+        );
+
+        let east = LCPH.offset(Cardinal::East, 10.0).to_local_xy(&LCPH);
+        assert_eq!(
+            (10.0, 0.0),
+            (round_decimal(east.x, 6), round_decimal(east.y, 6))
+        );
+
+        let south_west = LCPH.destination(225.0, 10.0).to_local_xy(&LCPH);
+        assert_eq!(
+            (-7.071068, -7.071068),
+            (
+                round_decimal(south_west.x, 6),
+                round_decimal(south_west.y, 6)
+            )
+        );
+    }
+
+    #[test]
+    fn test_latlon_from_local_xy() {
+        let pos = LatLon::from_local_xy(&LCPH, &Vec2::new(1000.0, -500.0));
+        let xy = pos.to_local_xy(&LCPH);
+        assert_eq!(
+            (1000.0, -500.0),
+            (round_decimal(xy.x, 6), round_decimal(xy.y, 6))
+        );
+
+        let pos = LatLon::from_local_xy(&LCPH, &Vec2::zero());
+        assert_eq!(LCPH, pos);
+    }
+
     #[test]
     fn test_latlon_distance_xy() {
         let dest = LCPH.destination(0.0, 10.0);
@@ -231,4 +820,41 @@ mod test {
         assert_eq!(-10.0, LCPH.distance_xy(&dest).0.round());
         assert_eq!(0.0, LCPH.distance_xy(&dest).1.round());
     }
+
+    #[test]
+    fn test_latlon_distance_on_sphere() {
+        let sphere = Ellipsoid::sphere(Ellipsoid::MEAN_RADIUS);
+        assert_eq!(105_477., LCPH.distance_on(&LCLK, &sphere).round());
+        assert_eq!(
+            LCPH.distance(&LCLK),
+            LCPH.distance_on(&LCLK, &Ellipsoid::WGS84)
+        );
+        // the sphere is only off by ~0.2% here
+        let difference = LCPH.distance(&LCLK) - LCPH.distance_on(&LCLK, &sphere);
+        assert_eq!(220., difference.round());
+    }
+
+    #[test]
+    fn test_latlon_destination_on_sphere() {
+        let sphere = Ellipsoid::sphere(Ellipsoid::MEAN_RADIUS);
+        let dest = LCPH.destination_on(90.0, 100_000.0, &sphere);
+        assert_eq!(100_000., LCPH.distance_on(&dest, &sphere).round());
+        assert!(LCPH.distance(&dest) > 100_000.);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let airports = [
+            ("LCLK", LCLK),
+            ("LCPH", LCPH),
+            ("LCEN", LatLon::new(35.1525, 33.496389)),
+        ];
+        let nearest_ident = |origin| nearest(origin, &airports, |(_, pos)| *pos).map(|(i, _)| *i);
+
+        assert_eq!(Some("LCPH"), nearest_ident(LatLon::new(34.75, 32.5)));
+        // Limassol is closer to Paphos than Larnaca
+        assert_eq!(Some("LCPH"), nearest_ident(LatLon::new(34.68, 33.04)));
+        assert_eq!(Some("LCEN"), nearest_ident(LatLon::new(35.2, 33.4)));
+        assert_eq!(None, nearest(LCPH, &[] as &[LatLon], |pos| *pos));
+    }
 }
@@ -16,8 +16,9 @@ impl Vec2 {
 
 /// Rotate a point by an angle (in radians) around an origin (clockwise)
 pub fn rotate_point(origin: Vec2, point: Vec2, angle: f64) -> Vec2 {
-    let cos = angle.cos();
-    let sin = angle.sin();
+    // libm rather than f64's methods, which aren't available under no_std
+    let cos = libm::cos(angle);
+    let sin = libm::sin(angle);
 
     Vec2::new(
         (point.x - origin.x) * cos + (point.y - origin.y) * sin + origin.x,
@@ -33,6 +34,19 @@ pub fn heading_to_point(heading: i32) -> Vec2 {
     )
 }
 
+/// The angle in degrees to turn from bearing `from` to bearing `to` the short
+/// way round, positive clockwise, e.g. 20 from 350 to 10.
+pub fn relative_bearing(from: f64, to: f64) -> f64 {
+    let angle = libm::fmod(to - from, 360.0);
+    if angle > 180.0 {
+        angle - 360.0
+    } else if angle <= -180.0 {
+        angle + 360.0
+    } else {
+        angle
+    }
+}
+
 #[cfg(test)]
 pub fn round_decimal(val: f64, decimal_points: u32) -> f64 {
     let multiplier = 10f64.powi(decimal_points as i32);
@@ -51,6 +65,17 @@ mod test {
         assert_eq!(1.435, round_decimal(1.4351, 3));
     }
 
+    #[test]
+    fn test_relative_bearing() {
+        assert_eq!(20.0, relative_bearing(350.0, 10.0));
+        assert_eq!(-20.0, relative_bearing(10.0, 350.0));
+        assert_eq!(90.0, relative_bearing(0.0, 90.0));
+        assert_eq!(-90.0, relative_bearing(90.0, 0.0));
+        assert_eq!(180.0, relative_bearing(0.0, 180.0));
+        assert_eq!(180.0, relative_bearing(180.0, 0.0));
+        assert_eq!(0.0, relative_bearing(720.0, 0.0));
+    }
+
     #[test]
     fn test_heading_to_point() {
         assert_eq!((0.0, 1.0), (heading_to_point(0).x, heading_to_point(0).y));
@@ -76,4 +101,13 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_rotate_point() {
+        let p = rotate_point(Vec2::new(1.0, 1.0), Vec2::new(1.0, 2.0), 90f64.to_radians());
+        assert_eq!((2.0, 1.0), (round_decimal(p.x, 6), round_decimal(p.y, 6)));
+
+        let p = rotate_point(Vec2::zero(), Vec2::new(3.0, 4.0), 180f64.to_radians());
+        assert_eq!((-3.0, -4.0), (round_decimal(p.x, 6), round_decimal(p.y, 6)));
+    }
 }
@@ -1,18 +1,175 @@
-use serde::{Serialize, Deserialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The units `SimData`'s latitude and longitude are in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateUnits {
+    #[default]
+    Degrees,
+    Radians,
+}
+
+fn parse_bool<E: de::Error>(s: &str) -> Result<bool, E> {
+    match s.trim() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        other => Err(E::custom(format!("invalid bool: {other}"))),
+    }
+}
+
+/// Deserialize a bool written as `true`/`false` or `1`/`0`, e.g. straight from
+/// an int dataref.
+fn deserialize_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    parse_bool(&String::deserialize(deserializer)?)
+}
+
+/// Same as `deserialize_bool`, with an empty field for none.
+fn deserialize_optional_bool<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<bool>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    if s.trim().is_empty() {
+        return Ok(None);
+    }
+    parse_bool(&s).map(Some)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct SimData {
-    pub icao: String,
+    /// Empty (`None`) while the sim can't give it, e.g. an aircraft that
+    /// leaves it blank, same for the registration.
+    pub icao: Option<String>,
     pub name: String,
-    pub registration: String,
+    pub registration: Option<String>,
     pub latitude: f64,
     pub longitude: f64,
+    /// Whether any engine is running, see `any_engine_running`.
+    #[serde(deserialize_with = "deserialize_bool")]
     pub engine_on: bool,
+    #[serde(deserialize_with = "deserialize_bool")]
     pub on_ground: bool,
+    /// In knots.
+    pub ground_speed: f64,
+    /// Height above the ground (AGL) in feet. Unlike the altitude above sea
+    /// level (MSL), it's 0 when on the ground at any airport.
+    pub agl_ft: f64,
+    /// In knots.
+    pub true_airspeed_kt: f64,
+    /// Records sent before the units were added are in degrees.
+    #[serde(default)]
+    pub coordinate_units: CoordinateUnits,
+    #[serde(default, deserialize_with = "deserialize_bool")]
+    pub parking_brake: bool,
+    /// The `PROTOCOL_VERSION` of the plugin that sent the record.
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// Whether the sim is paused, the aircraft doesn't move while paused.
+    #[serde(default, deserialize_with = "deserialize_bool")]
+    pub paused: bool,
+    /// Altitude above sea level (MSL) in feet, from the aircraft's position.
+    #[serde(default)]
+    pub elevation_ft: f64,
+    /// Altitude in feet as indicated on the pilot's altimeter, so it depends
+    /// on its pressure setting, e.g. 29.92 inHg in the flight levels.
+    #[serde(default)]
+    pub baro_altitude_ft: f64,
+    /// True heading in degrees.
+    #[serde(default)]
+    pub heading: f64,
+    /// Whether any of the gear has weight on it, a second opinion on
+    /// `on_ground` that doesn't count other parts touching the ground.
+    #[serde(default, deserialize_with = "deserialize_optional_bool")]
+    pub gear_on_ground: Option<bool>,
+}
+
+/// The version of the records sent by the plugin, bumped when their fields
+/// change. Records sent before it was added have a version of 0.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// The names of `SimData`'s fields, in the order they're serialized.
+pub const FIELDS: [&str; 18] = [
+    "icao",
+    "name",
+    "registration",
+    "latitude",
+    "longitude",
+    "engine_on",
+    "on_ground",
+    "ground_speed",
+    "agl_ft",
+    "true_airspeed_kt",
+    "coordinate_units",
+    "parking_brake",
+    "protocol_version",
+    "paused",
+    "elevation_ft",
+    "baro_altitude_ft",
+    "heading",
+    "gear_on_ground",
+];
+
+/// Whether any of the engines is running, i.e. burning fuel and able to
+/// produce thrust, given each engine's state as the sim reports it: above 0
+/// when running, e.g. X-Plane's `ENGN_running` of 1 or MSFS's
+/// `ENG COMBUSTION` of 1.0. Every sim maps to this same notion, so that the
+/// taxi out starts at the same point whichever sim it's flown in. An engine
+/// that's only cranking on the starter isn't running yet.
+pub fn any_engine_running(engines: impl IntoIterator<Item = f64>) -> bool {
+    engines.into_iter().any(|engine| engine > 0.0)
+}
+
+/// Parse a comma separated list of field names, e.g. `latitude,longitude`.
+pub fn parse_fields(s: &str) -> Result<Vec<&'static str>, String> {
+    s.split(',')
+        .map(|name| {
+            let name = name.trim();
+            FIELDS
+                .into_iter()
+                .find(|field| *field == name)
+                .ok_or_else(|| format!("unknown field: {name}"))
+        })
+        .collect()
 }
 
 impl SimData {
+    pub fn builder() -> SimDataBuilder {
+        SimDataBuilder::default()
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "icao" => self.icao.clone().unwrap_or_default(),
+            "name" => self.name.clone(),
+            "registration" => self.registration.clone().unwrap_or_default(),
+            "latitude" => self.latitude.to_string(),
+            "longitude" => self.longitude.to_string(),
+            "engine_on" => self.engine_on.to_string(),
+            "on_ground" => self.on_ground.to_string(),
+            "ground_speed" => self.ground_speed.to_string(),
+            "agl_ft" => self.agl_ft.to_string(),
+            "true_airspeed_kt" => self.true_airspeed_kt.to_string(),
+            "coordinate_units" => match self.coordinate_units {
+                CoordinateUnits::Degrees => String::from("degrees"),
+                CoordinateUnits::Radians => String::from("radians"),
+            },
+            "parking_brake" => self.parking_brake.to_string(),
+            "protocol_version" => self.protocol_version.to_string(),
+            "paused" => self.paused.to_string(),
+            "elevation_ft" => self.elevation_ft.to_string(),
+            "baro_altitude_ft" => self.baro_altitude_ft.to_string(),
+            "heading" => self.heading.to_string(),
+            "gear_on_ground" => self
+                .gear_on_ground
+                .map(|on_ground| on_ground.to_string())
+                .unwrap_or_default(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Parse a single record, with or without its `\r\n` or `\n` terminator.
     pub fn from_csv(csv: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let csv = csv.trim_end_matches(['\r', '\n']);
         let mut wrt = csv::ReaderBuilder::new()
             .has_headers(false)
             .terminator(csv::Terminator::CRLF)
@@ -26,14 +183,131 @@ impl SimData {
     }
 
     pub fn to_csv(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let mut wrt = csv::WriterBuilder::new()
-            .has_headers(false)
-            .terminator(csv::Terminator::CRLF)
-            .from_writer(vec![]);
+        let mut wrt = csv_writer();
         wrt.serialize(self)?;
         wrt.flush()?;
         Ok(String::from_utf8(wrt.into_inner()?)?)
     }
+
+    /// Same as `to_csv`, with only the `fields`, in their order.
+    pub fn to_csv_fields(&self, fields: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+        let record = fields
+            .iter()
+            .map(|name| {
+                self.field(name)
+                    .ok_or_else(|| format!("unknown field: {name}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut wrt = csv_writer();
+        wrt.write_record(&record)?;
+        wrt.flush()?;
+        Ok(String::from_utf8(wrt.into_inner()?)?)
+    }
+}
+
+/// Writes records ending in `\r\n`, with all the fields or only some.
+fn csv_writer() -> csv::Writer<Vec<u8>> {
+    csv::WriterBuilder::new()
+        .has_headers(false)
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(vec![])
+}
+
+/// Builds a `SimData`, fields that aren't set are left empty, zero or false.
+#[derive(Default)]
+pub struct SimDataBuilder(SimData);
+
+impl SimDataBuilder {
+    /// Left empty with `None`, same for the registration.
+    pub fn icao(mut self, icao: Option<impl Into<String>>) -> Self {
+        self.0.icao = icao.map(Into::into);
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.0.name = name.into();
+        self
+    }
+
+    pub fn registration(mut self, registration: Option<impl Into<String>>) -> Self {
+        self.0.registration = registration.map(Into::into);
+        self
+    }
+
+    pub fn position(mut self, latitude: f64, longitude: f64) -> Self {
+        self.0.latitude = latitude;
+        self.0.longitude = longitude;
+        self
+    }
+
+    pub fn engine_on(mut self, engine_on: bool) -> Self {
+        self.0.engine_on = engine_on;
+        self
+    }
+
+    pub fn on_ground(mut self, on_ground: bool) -> Self {
+        self.0.on_ground = on_ground;
+        self
+    }
+
+    pub fn ground_speed(mut self, ground_speed: f64) -> Self {
+        self.0.ground_speed = ground_speed;
+        self
+    }
+
+    pub fn agl_ft(mut self, agl_ft: f64) -> Self {
+        self.0.agl_ft = agl_ft;
+        self
+    }
+
+    pub fn true_airspeed_kt(mut self, true_airspeed_kt: f64) -> Self {
+        self.0.true_airspeed_kt = true_airspeed_kt;
+        self
+    }
+
+    pub fn coordinate_units(mut self, coordinate_units: CoordinateUnits) -> Self {
+        self.0.coordinate_units = coordinate_units;
+        self
+    }
+
+    pub fn parking_brake(mut self, parking_brake: bool) -> Self {
+        self.0.parking_brake = parking_brake;
+        self
+    }
+
+    pub fn protocol_version(mut self, protocol_version: u32) -> Self {
+        self.0.protocol_version = protocol_version;
+        self
+    }
+
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.0.paused = paused;
+        self
+    }
+
+    pub fn elevation_ft(mut self, elevation_ft: f64) -> Self {
+        self.0.elevation_ft = elevation_ft;
+        self
+    }
+
+    pub fn baro_altitude_ft(mut self, baro_altitude_ft: f64) -> Self {
+        self.0.baro_altitude_ft = baro_altitude_ft;
+        self
+    }
+
+    pub fn heading(mut self, heading: f64) -> Self {
+        self.0.heading = heading;
+        self
+    }
+
+    pub fn gear_on_ground(mut self, gear_on_ground: bool) -> Self {
+        self.0.gear_on_ground = Some(gear_on_ground);
+        self
+    }
+
+    pub fn build(self) -> SimData {
+        self.0
+    }
 }
 
 #[cfg(test)]
@@ -42,29 +316,273 @@ mod tests {
 
     #[test]
     fn test_from_csv() {
-        let csv = "CL60,Challenger 650,C-FAAV,32.000123,42.000123,false,true";
+        let csv = "CL60,Challenger 650,C-FAAV,32.000123,42.000123,false,true,12.5,1500.5,250.5";
         let sim_data = SimData::from_csv(csv).unwrap();
-        assert_eq!(sim_data.icao, String::from("CL60"));
+        assert_eq!(sim_data.icao, Some(String::from("CL60")));
         assert_eq!(sim_data.name, String::from("Challenger 650"));
-        assert_eq!(sim_data.registration, String::from("C-FAAV"));
+        assert_eq!(sim_data.registration, Some(String::from("C-FAAV")));
         assert_eq!(sim_data.latitude, 32.000123);
         assert_eq!(sim_data.longitude, 42.000123);
         assert!(!sim_data.engine_on);
         assert!(sim_data.on_ground);
+        assert_eq!(sim_data.ground_speed, 12.5);
+        assert_eq!(sim_data.agl_ft, 1500.5);
+        assert_eq!(sim_data.true_airspeed_kt, 250.5);
+        assert_eq!(sim_data.coordinate_units, CoordinateUnits::Degrees);
+    }
+
+    #[test]
+    fn test_from_csv_empty_fields() {
+        let csv = ",Challenger 650,,32.000123,42.000123,false,true,12.5,1500.5,250.5";
+        let sim_data = SimData::from_csv(csv).unwrap();
+        assert_eq!(None, sim_data.icao);
+        assert_eq!(None, sim_data.registration);
+        assert_eq!(
+            csv,
+            sim_data.to_csv_fields(&FIELDS[..10]).unwrap().trim_end()
+        );
+    }
+
+    #[test]
+    fn test_from_csv_numeric_bools() {
+        let words = "CL60,Challenger 650,C-FAAV,32.5,42.5,true,false,0,0,0,degrees,true";
+        let numbers = "CL60,Challenger 650,C-FAAV,32.5,42.5,1,0,0,0,0,degrees,1";
+        let sim_data = SimData::from_csv(numbers).unwrap();
+        assert!(sim_data.engine_on);
+        assert!(!sim_data.on_ground);
+        assert!(sim_data.parking_brake);
+        assert_eq!(SimData::from_csv(words).unwrap(), sim_data);
+
+        let paused = SimData::from_csv(&format!("{numbers},2,1")).unwrap();
+        assert!(paused.paused);
+    }
+
+    #[test]
+    fn test_from_csv_gear_on_ground() {
+        let csv = "CL60,Challenger 650,C-FAAV,32.5,42.5,1,1,0,0,0,degrees,0,5,0,0,0,0";
+        assert_eq!(None, SimData::from_csv(csv).unwrap().gear_on_ground);
+        assert_eq!(
+            None,
+            SimData::from_csv(&format!("{csv},"))
+                .unwrap()
+                .gear_on_ground
+        );
+        assert_eq!(
+            Some(false),
+            SimData::from_csv(&format!("{csv},0"))
+                .unwrap()
+                .gear_on_ground
+        );
+        assert_eq!(
+            Some(true),
+            SimData::from_csv(&format!("{csv},true"))
+                .unwrap()
+                .gear_on_ground
+        );
+    }
+
+    #[test]
+    fn test_from_csv_invalid_bool() {
+        let csv = "CL60,Challenger 650,C-FAAV,32.5,42.5,yes,false,0,0,0";
+        assert!(SimData::from_csv(csv).is_err());
+    }
+
+    #[test]
+    fn test_from_csv_in_radians() {
+        let csv = "CL60,Challenger 650,C-FAAV,0.6059,0.5670,false,true,12.5,1500.5,250.5,radians";
+        let sim_data = SimData::from_csv(csv).unwrap();
+        assert_eq!(sim_data.coordinate_units, CoordinateUnits::Radians);
+    }
+
+    #[test]
+    fn test_from_csv_with_terminator() {
+        let csv = "CL60,Challenger 650,C-FAAV,32.000123,42.000123,false,true,12.5,1500.5,250.5";
+        let expected = SimData::from_csv(csv).unwrap();
+        assert_eq!(expected, SimData::from_csv(&format!("{csv}\r\n")).unwrap());
+        assert_eq!(expected, SimData::from_csv(&format!("{csv}\n")).unwrap());
     }
 
     #[test]
     fn test_to_csv() {
         let sim_data = SimData {
-            icao: String::from("CL60"),
+            icao: Some(String::from("CL60")),
             name: String::from("Challenger 650"),
-            registration: String::from("C-FAAV"),
+            registration: Some(String::from("C-FAAV")),
             latitude: 32.000123,
             longitude: 42.000123,
             engine_on: false,
             on_ground: true,
+            ground_speed: 12.5,
+            agl_ft: 1500.5,
+            true_airspeed_kt: 250.5,
+            coordinate_units: CoordinateUnits::Degrees,
+            parking_brake: true,
+            protocol_version: 1,
+            paused: true,
+            elevation_ft: 35012.5,
+            baro_altitude_ft: 34980.5,
+            heading: 271.5,
+            gear_on_ground: Some(false),
         };
         let csv = sim_data.to_csv().unwrap();
-        assert_eq!(csv, String::from("CL60,Challenger 650,C-FAAV,32.000123,42.000123,false,true\n"));
+        assert_eq!(csv, String::from("CL60,Challenger 650,C-FAAV,32.000123,42.000123,false,true,12.5,1500.5,250.5,degrees,true,1,true,35012.5,34980.5,271.5,false\r\n"));
+    }
+
+    #[test]
+    fn test_any_engine_running() {
+        assert!(any_engine_running([0.0, 1.0]));
+        assert!(any_engine_running([1.0]));
+        assert!(!any_engine_running([0.0, 0.0, 0.0, 0.0]));
+        assert!(!any_engine_running([]));
+        // nothing the sims send for a running engine
+        assert!(!any_engine_running([-1.0, f64::NAN]));
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!(
+            Ok(vec!["latitude", "longitude", "on_ground"]),
+            parse_fields("latitude, longitude,on_ground")
+        );
+        assert_eq!(
+            Err(String::from("unknown field: altitude")),
+            parse_fields("latitude,altitude")
+        );
+        assert!(parse_fields("").is_err());
+    }
+
+    #[test]
+    fn test_to_csv_fields() {
+        let sim_data = SimData::builder()
+            .name("Cessna 172, \"Skyhawk\"")
+            .position(34.717778, 32.485556)
+            .ground_speed(95.5)
+            .parking_brake(true)
+            .build();
+        assert_eq!(
+            "34.717778,\"Cessna 172, \"\"Skyhawk\"\"\",95.5,true\r\n",
+            sim_data
+                .to_csv_fields(&["latitude", "name", "ground_speed", "parking_brake"])
+                .unwrap()
+        );
+        assert!(sim_data.to_csv_fields(&["altitude"]).is_err());
+    }
+
+    #[test]
+    fn test_all_fields_match_to_csv() {
+        let sim_data = SimData::builder()
+            .icao(Some("CL60"))
+            .name("Challenger 650")
+            .position(32.5, 42.25)
+            .on_ground(true)
+            .ground_speed(12.5)
+            .build();
+        let csv = sim_data.to_csv_fields(&FIELDS).unwrap();
+        assert_eq!(sim_data, SimData::from_csv(&csv).unwrap());
+        assert!(csv.ends_with("\r\n"));
+    }
+
+    #[test]
+    fn test_fields_match_struct() {
+        // serde names every field of the struct in the header
+        let mut wrt = csv::WriterBuilder::new().from_writer(vec![]);
+        wrt.serialize(SimData::default()).unwrap();
+        let csv = String::from_utf8(wrt.into_inner().unwrap()).unwrap();
+        assert_eq!(Some(FIELDS.join(",").as_str()), csv.lines().next());
+    }
+
+    #[test]
+    fn test_builder() {
+        let sim_data = SimData::builder()
+            .icao(Some("CL60"))
+            .name("Challenger 650")
+            .registration(Some("C-FAAV"))
+            .position(32.000123, 42.000123)
+            .on_ground(true)
+            .ground_speed(12.5)
+            .agl_ft(1500.5)
+            .true_airspeed_kt(250.5)
+            .build();
+        assert_eq!(
+            sim_data,
+            SimData {
+                icao: Some(String::from("CL60")),
+                name: String::from("Challenger 650"),
+                registration: Some(String::from("C-FAAV")),
+                latitude: 32.000123,
+                longitude: 42.000123,
+                engine_on: false,
+                on_ground: true,
+                ground_speed: 12.5,
+                agl_ft: 1500.5,
+                true_airspeed_kt: 250.5,
+                coordinate_units: CoordinateUnits::Degrees,
+                parking_brake: false,
+                protocol_version: 0,
+                paused: false,
+                elevation_ft: 0.0,
+                baro_altitude_ft: 0.0,
+                heading: 0.0,
+                gear_on_ground: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let sim_data = SimData::builder()
+            .name("Cessna 172")
+            .position(34.717778, 32.485556)
+            .engine_on(true)
+            .ground_speed(95.0)
+            .agl_ft(2500.25)
+            .true_airspeed_kt(110.0)
+            .coordinate_units(CoordinateUnits::Radians)
+            .parking_brake(true)
+            .protocol_version(PROTOCOL_VERSION)
+            .paused(true)
+            .heading(359.5)
+            .gear_on_ground(true)
+            .build();
+        let csv = sim_data.to_csv().unwrap();
+        assert_eq!(sim_data, SimData::from_csv(&csv).unwrap());
+    }
+
+    #[test]
+    fn test_altitudes_csv_round_trip() {
+        let sim_data = SimData::builder()
+            .name("Challenger 650")
+            .agl_ft(34500.0)
+            .elevation_ft(35012.75)
+            .baro_altitude_ft(-120.5)
+            .build();
+        let csv = sim_data.to_csv().unwrap();
+        let parsed = SimData::from_csv(&csv).unwrap();
+        assert_eq!(35012.75, parsed.elevation_ft);
+        assert_eq!(-120.5, parsed.baro_altitude_ft);
+
+        let csv = sim_data
+            .to_csv_fields(&["elevation_ft", "baro_altitude_ft"])
+            .unwrap();
+        assert_eq!("35012.75,-120.5\r\n", csv);
+    }
+
+    #[test]
+    fn test_from_csv_without_altitudes() {
+        let csv = "CL60,Challenger 650,C-FAAV,32.5,42.5,true,false,0,0,0,degrees,true,2,false";
+        let sim_data = SimData::from_csv(csv).unwrap();
+        assert_eq!(0.0, sim_data.elevation_ft);
+        assert_eq!(0.0, sim_data.baro_altitude_ft);
+    }
+
+    #[test]
+    fn test_default_csv_round_trip() {
+        let csv = SimData::default().to_csv().unwrap();
+        assert_eq!(SimData::default(), SimData::from_csv(&csv).unwrap());
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        assert_eq!(SimData::builder().build(), SimData::default());
     }
 }
@@ -1,29 +1,178 @@
+use chrono::{DateTime, SecondsFormat, Utc};
 use std::{
-    io::Write,
+    collections::VecDeque,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
     net::{SocketAddr, TcpListener, TcpStream},
+    sync::Mutex,
 };
 use xplm::data::borrowed::{DataRef, FindError};
 use xplm::data::{ArrayRead, DataRead, ReadOnly, StringRead};
 use xplm::flight_loop::{FlightLoop, FlightLoopCallback, LoopState};
 use xplm::plugin::{Plugin, PluginInfo};
 use xplm::xplane_plugin;
-use xp_sim_data::SimData;
+use xp_sim_data::{CoordinateUnits, SimData};
 
 /// extension of xplm::debugln! that prints the plugin name before the
-/// log message.
+/// log message, also writing it to the plugin's own log if enabled.
 macro_rules! debugln {
     () => (xplm::debugln!());
     ($($arg:tt)*) => ({
-        xplm::debugln!("[Logbook]: {}", std::format_args!($($arg)*))
+        xplm::debugln!("[Logbook]: {}", std::format_args!($($arg)*));
+        log_to_file(std::format_args!($($arg)*));
     });
 }
 
+/// Set to log to `LOG_FILE` as well as X-Plane's Log.txt.
+const LOG_ENV_VAR: &str = "LOGBOOK_PLUGIN_LOG";
+
+/// The plugin's own log, in X-Plane's directory next to Log.txt.
+const LOG_FILE: &str = "logbook_plugin.log";
+
+static PLUGIN_LOG: Mutex<Option<File>> = Mutex::new(None);
+
+/// Start appending to `LOG_FILE` if `LOG_ENV_VAR` is set.
+fn open_log() {
+    if std::env::var_os(LOG_ENV_VAR).is_none() {
+        return;
+    }
+    match OpenOptions::new().create(true).append(true).open(LOG_FILE) {
+        Ok(file) => {
+            if let Ok(mut log) = PLUGIN_LOG.lock() {
+                *log = Some(file);
+            }
+        }
+        Err(e) => debugln!("failed to open {LOG_FILE}: {e}"),
+    }
+}
+
+/// Format a line of the plugin's log, e.g.
+/// `time=2024-01-01T10:00:00.000Z msg="127.0.0.1:5000 connected!"`.
+fn log_line(time: DateTime<Utc>, msg: fmt::Arguments) -> String {
+    let msg = msg.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        "time={} msg=\"{msg}\"\n",
+        time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    )
+}
+
+/// Append to the plugin's log, if enabled. The log is only diagnostics, so
+/// failing to write it is ignored rather than risking the sim.
+fn log_to_file(msg: fmt::Arguments) {
+    if let Ok(mut log) = PLUGIN_LOG.lock() {
+        if let Some(file) = log.as_mut() {
+            let _ = file.write_all(log_line(Utc::now(), msg).as_bytes());
+        }
+    }
+}
+
 pub const SERVER_ADDR: &str = "127.0.0.1:52000";
 
+/// Knots in a meter per second.
+const MS_TO_KT: f64 = 1.943844;
+
+/// Feet in a meter.
+const M_TO_FT: f64 = 3.28084;
+
+/// How many records can be waiting to be sent to a client before the oldest
+/// are dropped.
+const MAX_QUEUED_PACKETS: usize = 8;
+
+/// The longest subscription line a client can send.
+const MAX_SUBSCRIPTION_LEN: usize = 1024;
+
+/// How many flight loops between logging how many records were sent.
+const TRANSMIT_LOG_INTERVAL: u64 = 60;
+
+/// What's been sent to the clients, logged every `TRANSMIT_LOG_INTERVAL`
+/// flight loops for diagnosing the sim stuttering with many clients.
+#[derive(Debug, Default, PartialEq)]
+struct Metrics {
+    records_sent: u64,
+    bytes_written: u64,
+    connections: usize,
+    loops_since_log: u64,
+}
+
+impl Metrics {
+    /// Count a record sent to a client, `bytes` being how much of what was
+    /// queued for it could be written.
+    fn record_sent(&mut self, bytes: usize) {
+        self.records_sent = self.records_sent.saturating_add(1);
+        self.bytes_written = self.bytes_written.saturating_add(bytes as u64);
+    }
+
+    /// Count a flight loop that ended with `connections` clients connected,
+    /// returning the summary to log once it's time to.
+    fn end_loop(&mut self, connections: usize) -> Option<String> {
+        self.connections = connections;
+        self.loops_since_log += 1;
+        if self.loops_since_log < TRANSMIT_LOG_INTERVAL {
+            return None;
+        }
+        self.loops_since_log = 0;
+        Some(format!(
+            "sent {} records ({} bytes), {} clients connected",
+            self.records_sent, self.bytes_written, self.connections
+        ))
+    }
+}
+
+/// ICAO codes for aircraft whose `acf_ICAO` is left blank, by a part of
+/// their `acf_ui_name`. The first match wins, so more specific names go
+/// first.
+const ICAO_BY_NAME: &[(&str, &str)] = &[
+    ("cessna 172", "C172"),
+    ("skyhawk", "C172"),
+    ("citation x", "C750"),
+    ("baron 58", "BE58"),
+    ("king air c90", "BE9L"),
+    ("king air 350", "B350"),
+    ("737-800", "B738"),
+    ("a330-300", "A333"),
+    ("md-82", "MD82"),
+    ("super cub", "PA18"),
+    ("sr22", "SR22"),
+    ("vision sf50", "SF50"),
+    ("rv-10", "RV10"),
+    ("s-76", "S76"),
+    ("r22", "R22"),
+];
+
+/// The aircraft's ICAO code from `acf_ICAO`, or where that's blank or
+/// `UNKNOWN` a best guess from its `acf_ui_name` using `ICAO_BY_NAME`, if
+/// there's one.
+fn icao_or_guess(icao: &str, name: &str) -> Option<String> {
+    let icao = icao.trim();
+    if !icao.is_empty() && !icao.eq_ignore_ascii_case("UNKNOWN") {
+        return Some(icao.to_string());
+    }
+    let name = name.to_lowercase();
+    ICAO_BY_NAME
+        .iter()
+        .find(|(pattern, _)| name.contains(pattern))
+        .map(|(_, icao)| icao.to_string())
+}
+
+/// Whether any engine is running, from `ENGN_running`'s 0 or 1 for each
+/// engine.
+fn engine_on(running: &[i32]) -> bool {
+    xp_sim_data::any_engine_running(running.iter().map(|engine| *engine as f64))
+}
+
+/// A string dataref's value, or none while it's blank (e.g. the registration
+/// before the aircraft has finished loading) or can't be read.
+fn non_empty<E>(value: Result<String, E>) -> Option<String> {
+    value.ok().filter(|value| !value.trim().is_empty())
+}
+
 struct FlightLoopHandler {
     tcp_listener: std::net::TcpListener,
-    tcp_connections: Vec<(TcpStream, SocketAddr)>,
+    tcp_connections: Vec<(ClientConn<TcpStream>, SocketAddr)>,
+    metrics: Metrics,
     is_in_replay: DataRef<bool, ReadOnly>,
+    paused: DataRef<bool, ReadOnly>,
     // datarefs for transfer
     icao: DataRef<[u8], ReadOnly>,
     name: DataRef<[u8], ReadOnly>,
@@ -32,6 +181,14 @@ struct FlightLoopHandler {
     longitude: DataRef<f64, ReadOnly>,
     engine_on: DataRef<[i32], ReadOnly>,
     on_ground: DataRef<bool, ReadOnly>,
+    gear_on_ground: DataRef<[i32], ReadOnly>,
+    ground_speed: DataRef<f32, ReadOnly>,
+    agl: DataRef<f32, ReadOnly>,
+    elevation: DataRef<f64, ReadOnly>,
+    baro_altitude: DataRef<f32, ReadOnly>,
+    heading: DataRef<f32, ReadOnly>,
+    true_airspeed: DataRef<f32, ReadOnly>,
+    parking_brake: DataRef<f32, ReadOnly>,
 }
 
 impl FlightLoopHandler {
@@ -48,7 +205,9 @@ impl FlightLoopHandler {
         Ok(Self {
             tcp_listener,
             tcp_connections: vec![],
+            metrics: Metrics::default(),
             is_in_replay: DataRef::find("sim/time/is_in_replay")?,
+            paused: DataRef::find("sim/time/paused")?,
             icao: DataRef::find("sim/aircraft/view/acf_ICAO")?,
             name: DataRef::find("sim/aircraft/view/acf_ui_name")?,
             registration: DataRef::find("sim/aircraft/view/acf_tailnum")?,
@@ -57,41 +216,177 @@ impl FlightLoopHandler {
             engine_on: DataRef::find("sim/flightmodel/engine/ENGN_running")?,
             // according to the docs: "User Aircraft is on the ground when this is set to 1"
             on_ground: DataRef::find("sim/flightmodel/failures/onground_any")?,
+            // 1 for each gear with weight on its wheels
+            gear_on_ground: DataRef::find("sim/flightmodel2/gear/on_ground")?,
+            // in meters per second
+            ground_speed: DataRef::find("sim/flightmodel/position/groundspeed")?,
+            // in meters
+            agl: DataRef::find("sim/flightmodel/position/y_agl")?,
+            // in meters above sea level
+            elevation: DataRef::find("sim/flightmodel/position/elevation")?,
+            // in feet, as indicated on the pilot's altimeter
+            baro_altitude: DataRef::find("sim/cockpit2/gauges/indicators/altitude_ft_pilot")?,
+            // true heading in degrees
+            heading: DataRef::find("sim/flightmodel/position/true_psi")?,
+            // in meters per second
+            true_airspeed: DataRef::find("sim/flightmodel/position/true_airspeed")?,
+            // from 0 (released) to 1 (fully set)
+            parking_brake: DataRef::find("sim/cockpit2/controls/parking_brake_ratio")?,
         })
     }
 
     fn sim_data(&self) -> SimData {
-        let icao = self.icao.get_as_string().unwrap_or(String::from("UNKNOWN"));
-        let name = self.name.get_as_string().unwrap_or(String::from("UNKNOWN"));
-        let registration = self
-            .registration
+        let name = self
+            .name
             .get_as_string()
-            .unwrap_or(String::from("UNKNOWN"));
-        let latitude = self.latitude.get();
-        let longitude = self.longitude.get();
-        let engine_on = self.engine_on.as_vec().iter().any(|x| *x == 1);
-        let on_ground = self.on_ground.get();
-        SimData {
-            icao,
-            name,
-            registration,
-            latitude,
-            longitude,
-            engine_on,
-            on_ground
-        }
-    }
-}
-
-fn send_packet(
-    stream: &mut TcpStream,
-    msg: &str
-) -> Result<(), std::io::Error> {
-    let packet_size = msg.len() as u16;
-    stream.write_all(&packet_size.to_le_bytes())?;
-    stream.write_all(msg.as_bytes())?;
-    stream.flush()?;
-    Ok(())
+            .unwrap_or_else(|_| String::from("UNKNOWN"));
+        let icao = self.icao.get_as_string().unwrap_or_default();
+        // sent empty while unknown, rather than making up a value
+        SimData::builder()
+            .icao(icao_or_guess(&icao, &name))
+            .name(name)
+            .registration(non_empty(self.registration.get_as_string()))
+            .position(self.latitude.get(), self.longitude.get())
+            .coordinate_units(CoordinateUnits::Degrees)
+            .engine_on(engine_on(&self.engine_on.as_vec()))
+            .on_ground(self.on_ground.get())
+            .gear_on_ground(self.gear_on_ground.as_vec().contains(&1))
+            .ground_speed(self.ground_speed.get() as f64 * MS_TO_KT)
+            .agl_ft(self.agl.get() as f64 * M_TO_FT)
+            .true_airspeed_kt(self.true_airspeed.get() as f64 * MS_TO_KT)
+            .parking_brake(self.parking_brake.get() > 0.5)
+            .protocol_version(xp_sim_data::PROTOCOL_VERSION)
+            .paused(self.paused.get())
+            .elevation_ft(self.elevation.get() * M_TO_FT)
+            .baro_altitude_ft(self.baro_altitude.get() as f64)
+            .heading(self.heading.get() as f64)
+            .build()
+    }
+}
+
+/// A client connection that never blocks the flight loop.
+///
+/// Records are queued as packets, a little endian `u16` length followed by
+/// the record, and written as far as the (non-blocking) stream accepts them.
+/// A client that can't keep up loses its oldest records rather than
+/// stalling the sim.
+///
+/// Clients get every field, unless they subscribe to some of them by sending
+/// a line with the comma separated field names, e.g. `latitude,longitude\n`.
+/// The plugin answers with a header packet listing the fields, and every
+/// record after it only has those fields, in that order.
+struct ClientConn<W> {
+    stream: W,
+    queue: VecDeque<Vec<u8>>,
+    // how much of the packet at the front of the queue was already written
+    written: usize,
+    // what's been received of the subscription line so far
+    received: Vec<u8>,
+    // the fields subscribed to, or all of them
+    fields: Option<Vec<&'static str>>,
+}
+
+impl<W: Read + Write> ClientConn<W> {
+    /// Send the record, with only the fields the client subscribed to,
+    /// returning how many bytes were written.
+    fn send_record(&mut self, sim_data: &SimData, record: &str) -> Result<usize, io::Error> {
+        let mut written = 0;
+        if let Some(line) = self.read_subscription()? {
+            let fields = xp_sim_data::parse_fields(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            written += self.send(&format!("{}\r\n", fields.join(",")))?;
+            self.fields = Some(fields);
+        }
+        written += match &self.fields {
+            Some(fields) => {
+                let record = sim_data
+                    .to_csv_fields(fields)
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                self.send(&record)?
+            }
+            None => self.send(record)?,
+        };
+        Ok(written)
+    }
+
+    /// Return the subscription line once all of it has been received.
+    fn read_subscription(&mut self) -> Result<Option<String>, io::Error> {
+        if self.fields.is_some() {
+            return Ok(None);
+        }
+        let mut buf = [0; 256];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::ConnectionAborted.into()),
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let Some(end) = self.received.iter().position(|b| *b == b'\n') else {
+            if self.received.len() > MAX_SUBSCRIPTION_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "subscription too long",
+                ));
+            }
+            return Ok(None);
+        };
+        let line = String::from_utf8_lossy(&self.received[..end])
+            .trim()
+            .to_string();
+        self.received.clear();
+        Ok(Some(line))
+    }
+}
+
+impl<W: Write> ClientConn<W> {
+    fn new(stream: W) -> Self {
+        Self {
+            stream,
+            queue: VecDeque::new(),
+            written: 0,
+            received: vec![],
+            fields: None,
+        }
+    }
+
+    /// Queue `msg` and write as much as possible without blocking, returning
+    /// how many bytes were written.
+    fn send(&mut self, msg: &str) -> Result<usize, io::Error> {
+        let mut packet = (msg.len() as u16).to_le_bytes().to_vec();
+        packet.extend_from_slice(msg.as_bytes());
+        if self.queue.len() == MAX_QUEUED_PACKETS {
+            // a packet that's partly written has to be finished, or the
+            // client loses track of where packets start
+            let oldest = if self.written > 0 { 1 } else { 0 };
+            self.queue.remove(oldest);
+        }
+        self.queue.push_back(packet);
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<usize, io::Error> {
+        let mut total = 0;
+        while let Some(packet) = self.queue.front() {
+            match self.stream.write(&packet[self.written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    total += n;
+                    self.written += n;
+                    if self.written == packet.len() {
+                        self.queue.pop_front();
+                        self.written = 0;
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
 }
 
 // NOTE: be careful! we can't panic here, it will crash the sim.
@@ -109,7 +404,7 @@ impl FlightLoopCallback for FlightLoopHandler {
             Ok((socket, addr)) => {
                 debugln!("{addr} connected!");
                 if socket.set_nonblocking(true).is_ok() {
-                    self.tcp_connections.push((socket, addr));
+                    self.tcp_connections.push((ClientConn::new(socket), addr));
                 } else {
                     // should also basically never happen, but we want to be sure
                     // never to panic here
@@ -120,7 +415,8 @@ impl FlightLoopCallback for FlightLoopHandler {
             Err(e) => debugln!("could not open listener: {e}"),
         }
 
-        let record_line = match self.sim_data().to_csv() {
+        let sim_data = self.sim_data();
+        let record_line = match sim_data.to_csv() {
             Ok(record) => record,
             Err(e) => {
                 // should basically never happen, handle it to avoid panic
@@ -129,9 +425,13 @@ impl FlightLoopCallback for FlightLoopHandler {
             }
         };
 
-        self.tcp_connections.retain_mut(|(stream, addr)| {
-            match send_packet(stream, &record_line) {
-                Ok(_) => true,
+        let metrics = &mut self.metrics;
+        self.tcp_connections.retain_mut(|(conn, addr)| {
+            match conn.send_record(&sim_data, &record_line) {
+                Ok(bytes) => {
+                    metrics.record_sent(bytes);
+                    true
+                }
                 // client closed connection
                 Err(ref e) if e.kind() == std::io::ErrorKind::ConnectionAborted => {
                     debugln!("{addr} closed connection...");
@@ -143,6 +443,10 @@ impl FlightLoopCallback for FlightLoopHandler {
                 }
             }
         });
+
+        if let Some(summary) = self.metrics.end_loop(self.tcp_connections.len()) {
+            debugln!("{summary}");
+        }
     }
 }
 
@@ -154,6 +458,7 @@ impl Plugin for LogbookPlugin {
     type Error = FindError;
 
     fn start() -> Result<Self, Self::Error> {
+        open_log();
         debugln!("plugin started!");
         let flight_loop = FlightLoop::new(FlightLoopHandler::new()?);
         Ok(LogbookPlugin { flight_loop })
@@ -179,3 +484,263 @@ impl Plugin for LogbookPlugin {
 }
 
 xplane_plugin!(LogbookPlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A stream that accepts `capacity` bytes, then would block.
+    struct SlowStream {
+        written: Vec<u8>,
+        capacity: usize,
+        // what the client sent
+        incoming: Vec<u8>,
+    }
+
+    impl Read for SlowStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.incoming.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.incoming.len());
+            buf[..n].copy_from_slice(&self.incoming[..n]);
+            self.incoming.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for SlowStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.capacity - self.written.len());
+            if n == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packets(bytes: &[u8]) -> Vec<String> {
+        let mut packets = vec![];
+        let mut bytes = bytes;
+        while bytes.len() >= 2 {
+            let size = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+            packets.push(String::from_utf8(bytes[2..2 + size].to_vec()).unwrap());
+            bytes = &bytes[2 + size..];
+        }
+        packets
+    }
+
+    #[test]
+    fn test_client_conn_sends_packets() {
+        let mut conn = ClientConn::new(SlowStream {
+            written: vec![],
+            capacity: usize::MAX,
+            incoming: vec![],
+        });
+        assert_eq!(7, conn.send("first").unwrap());
+        assert_eq!(8, conn.send("second").unwrap());
+        assert!(conn.queue.is_empty());
+        assert_eq!(vec!["first", "second"], packets(&conn.stream.written));
+    }
+
+    #[test]
+    fn test_client_conn_drops_oldest() {
+        let mut conn = ClientConn::new(SlowStream {
+            written: vec![],
+            capacity: 0,
+            incoming: vec![],
+        });
+        for i in 0..MAX_QUEUED_PACKETS + 2 {
+            conn.send(&i.to_string()).unwrap();
+        }
+        assert_eq!(MAX_QUEUED_PACKETS, conn.queue.len());
+
+        // the client catches up
+        conn.stream.capacity = usize::MAX;
+        conn.send("latest").unwrap();
+        let expected: Vec<_> = (3..MAX_QUEUED_PACKETS + 2)
+            .map(|i| i.to_string())
+            .chain([String::from("latest")])
+            .collect();
+        assert_eq!(expected, packets(&conn.stream.written));
+    }
+
+    #[test]
+    fn test_client_conn_keeps_partly_sent_packet() {
+        let mut conn = ClientConn::new(SlowStream {
+            written: vec![],
+            capacity: 4,
+            incoming: vec![],
+        });
+        conn.send("first").unwrap();
+        for i in 0..MAX_QUEUED_PACKETS {
+            conn.send(&i.to_string()).unwrap();
+        }
+
+        conn.stream.capacity = usize::MAX;
+        conn.send("latest").unwrap();
+        let packets = packets(&conn.stream.written);
+        assert_eq!("first", packets[0]);
+        assert_eq!("latest", packets[packets.len() - 1]);
+        assert_eq!(MAX_QUEUED_PACKETS, packets.len());
+    }
+
+    fn subscriber(incoming: &str) -> ClientConn<SlowStream> {
+        ClientConn::new(SlowStream {
+            written: vec![],
+            capacity: usize::MAX,
+            incoming: incoming.as_bytes().to_vec(),
+        })
+    }
+
+    fn sim_data() -> SimData {
+        SimData::builder()
+            .icao(Some("C172"))
+            .name("Cessna 172")
+            .position(34.717778, 32.485556)
+            .ground_speed(95.5)
+            .build()
+    }
+
+    #[test]
+    fn test_client_conn_without_subscription() {
+        let sim_data = sim_data();
+        let record = sim_data.to_csv().unwrap();
+        let mut conn = subscriber("");
+        conn.send_record(&sim_data, &record).unwrap();
+        conn.send_record(&sim_data, &record).unwrap();
+        assert_eq!(vec![record.clone(), record], packets(&conn.stream.written));
+    }
+
+    #[test]
+    fn test_client_conn_subscription() {
+        let sim_data = sim_data();
+        let record = sim_data.to_csv().unwrap();
+        let mut conn = subscriber("latitude,longitude,ground_speed\n");
+        conn.send_record(&sim_data, &record).unwrap();
+        conn.send_record(&sim_data, &record).unwrap();
+        assert_eq!(
+            vec![
+                "latitude,longitude,ground_speed\r\n",
+                "34.717778,32.485556,95.5\r\n",
+                "34.717778,32.485556,95.5\r\n",
+            ],
+            packets(&conn.stream.written)
+        );
+    }
+
+    #[test]
+    fn test_client_conn_subscription_split_between_reads() {
+        let sim_data = sim_data();
+        let record = sim_data.to_csv().unwrap();
+        let mut conn = subscriber("icao,on_");
+        conn.send_record(&sim_data, &record).unwrap();
+        conn.stream.incoming.extend_from_slice(b"ground\r\n");
+        conn.send_record(&sim_data, &record).unwrap();
+        assert_eq!(
+            vec![
+                record,
+                String::from("icao,on_ground\r\n"),
+                String::from("C172,false\r\n")
+            ],
+            packets(&conn.stream.written)
+        );
+    }
+
+    #[test]
+    fn test_client_conn_invalid_subscription() {
+        let sim_data = sim_data();
+        let record = sim_data.to_csv().unwrap();
+        let mut conn = subscriber("latitude,altitude\n");
+        let error = conn.send_record(&sim_data, &record).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+    }
+
+    #[test]
+    fn test_engine_on() {
+        assert!(engine_on(&[0, 1]));
+        assert!(engine_on(&[1, 0, 0, 0, 0, 0, 0, 0]));
+        assert!(!engine_on(&[0; 8]));
+        // gliders have no engines
+        assert!(!engine_on(&[]));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut metrics = Metrics::default();
+        metrics.record_sent(100);
+        metrics.record_sent(0);
+        metrics.record_sent(50);
+        assert_eq!(3, metrics.records_sent);
+        assert_eq!(150, metrics.bytes_written);
+
+        for _ in 1..TRANSMIT_LOG_INTERVAL {
+            assert_eq!(None, metrics.end_loop(2));
+        }
+        assert_eq!(
+            Some(String::from(
+                "sent 3 records (150 bytes), 2 clients connected"
+            )),
+            metrics.end_loop(2)
+        );
+        // the totals carry on, only the loops start over
+        assert_eq!(None, metrics.end_loop(1));
+        assert_eq!(1, metrics.loops_since_log);
+        assert_eq!(3, metrics.records_sent);
+    }
+
+    #[test]
+    fn test_metrics_saturate() {
+        let mut metrics = Metrics {
+            records_sent: u64::MAX,
+            bytes_written: u64::MAX - 1,
+            ..Metrics::default()
+        };
+        metrics.record_sent(10);
+        assert_eq!(u64::MAX, metrics.records_sent);
+        assert_eq!(u64::MAX, metrics.bytes_written);
+    }
+
+    #[test]
+    fn test_icao_or_guess() {
+        let some = |icao: &str| Some(icao.to_string());
+        assert_eq!(some("CL60"), icao_or_guess("CL60", "Challenger 650"));
+        assert_eq!(some("C172"), icao_or_guess("", "Cessna 172 SP Skyhawk"));
+        assert_eq!(some("B738"), icao_or_guess(" UNKNOWN ", "Boeing 737-800"));
+        assert_eq!(
+            some("BE9L"),
+            icao_or_guess("unknown", "Beechcraft King Air C90B")
+        );
+        assert_eq!(None, icao_or_guess("", "Homebuilt Special"));
+        assert_eq!(None, icao_or_guess("", "UNKNOWN"));
+    }
+
+    #[test]
+    fn test_non_empty() {
+        assert_eq!(
+            Some(String::from("C-FAAV")),
+            non_empty::<()>(Ok(String::from("C-FAAV")))
+        );
+        assert_eq!(None, non_empty::<()>(Ok(String::from(" "))));
+        assert_eq!(None, non_empty(Err(())));
+    }
+
+    #[test]
+    fn test_log_line() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        assert_eq!(
+            "time=2024-01-01T10:00:00.000Z msg=\"127.0.0.1:5000 connected!\"\n",
+            log_line(time, format_args!("{} connected!", "127.0.0.1:5000"))
+        );
+        assert_eq!(
+            "time=2024-01-01T10:00:00.000Z msg=\"client error: \\\"C:\\\\X-Plane\\\"\"\n",
+            log_line(time, format_args!("client error: \"C:\\X-Plane\""))
+        );
+    }
+}
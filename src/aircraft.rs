@@ -1,6 +1,16 @@
 use geo::LatLon;
+use std::time::Duration;
 
-#[derive(Clone, Debug)]
+/// Meters in a nautical mile.
+pub const NM_TO_M: f64 = 1852.0;
+
+/// Meters in a foot.
+pub const FT_TO_M: f64 = 0.3048;
+
+/// Ground speed (in knots) below which the aircraft is considered stopped.
+pub const STOPPED_KT: f64 = 1.0;
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Aircraft {
     pub title: String,
     pub icao: String,
@@ -8,4 +18,165 @@ pub struct Aircraft {
     pub position: LatLon,
     pub engine_on: bool,
     pub on_ground: bool,
+    /// Whether the gear has weight on it, if the sim sends it separately
+    /// from `on_ground`.
+    pub gear_on_ground: Option<bool>,
+    /// In knots.
+    pub ground_speed: f64,
+    /// Height above the ground in feet, as opposed to the altitude above sea level.
+    pub agl_ft: f64,
+    /// In knots.
+    pub true_airspeed_kt: f64,
+    pub parking_brake: bool,
+    /// The version of the protocol the data was sent over, if the sim has one.
+    pub protocol_version: Option<u32>,
+    /// Whether the sim is paused, nothing should be timed while it is.
+    pub paused: bool,
+    /// Altitude above sea level (MSL) in feet.
+    pub elevation_ft: f64,
+    /// Altitude in feet as indicated on the altimeter, i.e. the flight level
+    /// times 100 when set to standard pressure.
+    pub baro_altitude_ft: f64,
+    /// True heading in degrees.
+    pub heading: f64,
+}
+
+impl Aircraft {
+    /// Whether the aircraft is parked, i.e. stopped on the ground with the
+    /// parking brake set, rather than just holding somewhere on a taxiway.
+    pub fn is_parked(&self) -> bool {
+        self.on_ground && self.ground_speed < STOPPED_KT && self.parking_brake
+    }
+
+    /// Estimated time to reach `destination` in a straight line at the
+    /// current ground speed, none while (nearly) stopped.
+    pub fn time_to(&self, destination: &LatLon) -> Option<Duration> {
+        if self.ground_speed < STOPPED_KT {
+            return None;
+        }
+        let speed_ms = self.ground_speed * NM_TO_M / 3600.0;
+        Some(Duration::from_secs_f64(
+            self.position.distance(destination) / speed_ms,
+        ))
+    }
+}
+
+/// Strip the spaces and nulls the sims can leave around strings.
+fn trim_padding(s: &str) -> &str {
+    s.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+}
+
+/// The aircraft's title as the sim sent it, without any padding.
+pub fn normalize_title(title: &str) -> String {
+    trim_padding(title).to_string()
+}
+
+/// The ICAO type designator in its canonical form, e.g. ` cl60 ` as `CL60`, so
+/// that the same type is always logged the same way.
+pub fn normalize_icao(icao: &str) -> String {
+    trim_padding(icao).to_uppercase()
+}
+
+/// A one line summary for the logs, e.g.
+/// `Challenger 650 (CL60) C-FAAV @ 34°43'N 032°29'E, engine on, on the ground`.
+impl std::fmt::Display for Aircraft {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) {} @ {}, engine {}, {}",
+            self.title,
+            self.icao,
+            self.registration,
+            self.position,
+            if self.engine_on { "on" } else { "off" },
+            if self.on_ground {
+                "on the ground"
+            } else {
+                "airborne"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_parked() {
+        let parked = Aircraft {
+            on_ground: true,
+            ground_speed: 0.0,
+            parking_brake: true,
+            ..Aircraft::default()
+        };
+        assert!(parked.is_parked());
+        // drifting a little doesn't count as moving
+        assert!(Aircraft {
+            ground_speed: 0.5,
+            ..parked.clone()
+        }
+        .is_parked());
+        // holding short on the brakes
+        assert!(!Aircraft {
+            parking_brake: false,
+            ..parked.clone()
+        }
+        .is_parked());
+        assert!(!Aircraft {
+            ground_speed: 10.0,
+            ..parked.clone()
+        }
+        .is_parked());
+        assert!(!Aircraft {
+            on_ground: false,
+            ..parked.clone()
+        }
+        .is_parked());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!("CL60", normalize_icao("  cl60 \0\0"));
+        assert_eq!("B738", normalize_icao("B738"));
+        assert_eq!(
+            "Challenger 650 Air Canada",
+            normalize_title("\tChallenger 650 Air Canada  \0")
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let mut aircraft = Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position: LatLon::new(34.717778, 32.485556),
+            engine_on: true,
+            on_ground: true,
+            ..Aircraft::default()
+        };
+        assert_eq!(
+            "Challenger 650 (CL60) C-FAAV @ 34°43'N 032°29'E, engine on, on the ground",
+            aircraft.to_string()
+        );
+        aircraft.engine_on = false;
+        aircraft.on_ground = false;
+        assert!(aircraft.to_string().ends_with(", engine off, airborne"));
+    }
+
+    #[test]
+    fn test_time_to() {
+        let mut aircraft = Aircraft {
+            position: LatLon::new(34.717778, 32.485556),
+            ground_speed: 120.0,
+            ..Aircraft::default()
+        };
+        let destination = aircraft.position.destination(90.0, 60.0 * NM_TO_M);
+        let eta = aircraft.time_to(&destination).unwrap();
+        assert!((eta.as_secs_f64() - 30.0 * 60.0).abs() < 1.0);
+
+        aircraft.ground_speed = 0.5;
+        assert_eq!(None, aircraft.time_to(&destination));
+    }
 }
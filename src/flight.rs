@@ -0,0 +1,2130 @@
+use crate::aircraft::{Aircraft, NM_TO_M, STOPPED_KT};
+use crate::clock::{Clock, SystemClock};
+use crate::hold::{HoldConfig, HoldDetector};
+use crate::logbook::{COLUMNS, DATE_FORMAT};
+use crate::navdata::Airport;
+use crate::track::{Track, TrackPoint, ALTITUDE_SMOOTHING_WINDOW};
+use chrono::{DateTime, Duration, Utc};
+use geo::{Ellipsoid, LatLon};
+use log::{debug, info, warn};
+
+/// Flights under this distance (in nautical miles) aren't cross-country.
+pub const DEFAULT_CROSS_COUNTRY_NM: f64 = 50.0;
+
+/// Ground speed (in knots) above which we're on a takeoff roll rather than taxiing.
+pub const TAKEOFF_ROLL_KT: f64 = 40.0;
+
+/// Ground speed (in knots) to slow back down to for a takeoff roll to count
+/// as aborted.
+pub const TAXI_SPEED_KT: f64 = 20.0;
+
+/// Height above the ground (in feet) to climb back to after landing for it to
+/// count as a touch and go, rather than bouncing on the runway.
+pub const TOUCH_AND_GO_AGL_FT: f64 = 50.0;
+
+/// Height above the ground (in feet) to descend below near an airport for
+/// climbing away again to count as a go around.
+pub const GO_AROUND_AGL_FT: f64 = 200.0;
+
+/// Height above the ground (in feet) after which we've climbed out, either
+/// after taking off or when going around.
+pub const CLIMB_OUT_AGL_FT: f64 = 500.0;
+
+/// How much further (in meters) the departure airport can be than the closest
+/// airport and still be taken as where we are.
+pub const DEFAULT_DEPARTURE_BIAS_M: f64 = 500.0;
+
+/// How long (in seconds) the sim can keep sending the exact same position
+/// en route before its feed looks frozen.
+pub const DEFAULT_FROZEN_FEED_SECS: i64 = 60;
+
+/// How far (in kilometers) the aircraft can move between two samples on the
+/// ground before it's taken to have been repositioned in the sim.
+pub const DEFAULT_REPOSITION_KM: f64 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlightCategory {
+    /// Departed and arrived at the same airport.
+    Pattern,
+    /// Arrived at a different airport, but close to the departure.
+    Local,
+    CrossCountry,
+}
+
+impl std::fmt::Display for FlightCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FlightCategory::Pattern => "pattern",
+            FlightCategory::Local => "local",
+            FlightCategory::CrossCountry => "cross-country",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlightState {
+    Preflight,
+    Taxi,
+    EnRoute,
+    Landed,
+    Complete,
+}
+
+impl FlightState {
+    pub const ALL: [FlightState; 5] = [
+        FlightState::Preflight,
+        FlightState::Taxi,
+        FlightState::EnRoute,
+        FlightState::Landed,
+        FlightState::Complete,
+    ];
+}
+
+impl std::fmt::Display for FlightState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FlightState::Preflight => "preflight",
+            FlightState::Taxi => "taxi",
+            FlightState::EnRoute => "en-route",
+            FlightState::Landed => "landed",
+            FlightState::Complete => "complete",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for FlightState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FlightState::ALL
+            .into_iter()
+            .find(|state| state.to_string() == s)
+            .ok_or_else(|| format!("invalid flight state: {s}"))
+    }
+}
+
+/// When the previous sample of the flight was received, and what it said.
+#[derive(Clone, Copy, Debug)]
+struct LastSample {
+    time: DateTime<Utc>,
+    engine_on: bool,
+    on_ground: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Flight {
+    pub aircraft: Aircraft,
+    pub state: FlightState,
+    /// Where the aircraft was last parked before starting up.
+    pub parked_at: Option<LatLon>,
+    /// The airport the aircraft was last parked at before starting up.
+    pub origin: Option<Airport>,
+    /// Where the aircraft was at the flight's first sample.
+    pub origin_position: LatLon,
+    /// Where the aircraft was when the flight was completed.
+    pub shutdown_position: Option<LatLon>,
+    pub taxi_out: Option<DateTime<Utc>>,
+    pub departure: Option<(Airport, DateTime<Utc>)>,
+    pub arrival: Option<(Airport, DateTime<Utc>)>,
+    pub shutdown: Option<DateTime<Utc>>,
+    /// When the aircraft first left the ground.
+    pub wheels_up: Option<DateTime<Utc>>,
+    /// When the aircraft touched down for the (final) landing.
+    pub wheels_down: Option<DateTime<Utc>>,
+    /// The course in degrees the aircraft left the ground on, roughly the
+    /// runway's direction.
+    pub departure_course: Option<f64>,
+    /// The course in degrees the aircraft touched down on for the (final)
+    /// landing.
+    pub arrival_course: Option<f64>,
+    /// The ground speed in knots when leaving the ground, roughly the
+    /// rotation speed.
+    pub rotation_speed_kt: Option<f64>,
+    /// The ground speed in knots when touching down for the (final) landing,
+    /// roughly the approach speed.
+    pub touchdown_speed_kt: Option<f64>,
+    pub track: Track,
+    pub category: Option<FlightCategory>,
+    /// Set when the sim switched to another aircraft while this flight was
+    /// in progress, but the flight was kept.
+    pub aircraft_changed: bool,
+    /// Set when the sim kept sending the exact same sample en route, e.g.
+    /// because it hung, see `FlightConfig::frozen_feed`.
+    pub frozen_feed: bool,
+    pub aborted_takeoffs: u32,
+    /// Total time with any engine running, in any phase of the flight.
+    pub hobbs_time: Duration,
+    pub go_arounds: u32,
+    /// Touch and goes at each airport, in the order they were first done
+    /// there, `unknown` outside of any known airport.
+    pub touch_and_goes: Vec<(String, u32)>,
+    /// The highest altitude indicated on the altimeter while airborne, in feet.
+    pub max_baro_altitude_ft: Option<f64>,
+    /// Where each hold flown en route was entered, see `HoldDetector`.
+    pub holds: Vec<LatLon>,
+    /// How many times the engines were shut down after running, including
+    /// the final shutdown.
+    pub engine_cycles: u32,
+    last_sample: Option<LastSample>,
+    // when the sim was paused and resumed during the flight
+    pauses: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    // the position, on_ground and engine_on sent unchanged en route, and
+    // since when
+    unchanged_since: Option<((LatLon, bool, bool), DateTime<Utc>)>,
+    // whether we're currently rolling for takeoff
+    takeoff_roll: bool,
+    // when the aircraft came to a stop after landing, if it's still stopped
+    stopped_since: Option<DateTime<Utc>>,
+    // whether we've climbed out since taking off, so the initial climb isn't
+    // taken for a go around
+    climbed_out: bool,
+    // whether we're low on an approach, without having touched down
+    low_approach: bool,
+    // the airport of the last touchdown
+    touchdown_airport: Option<String>,
+    // sum of the ground speed minus the true airspeed while en-route, and the
+    // number of samples summed
+    wind_sum: f64,
+    wind_samples: u32,
+    hold_detector: HoldDetector,
+}
+
+impl Flight {
+    pub fn new(aircraft: &Aircraft) -> Self {
+        Flight {
+            aircraft: aircraft.clone(),
+            state: FlightState::Preflight,
+            parked_at: None,
+            origin: None,
+            origin_position: aircraft.position,
+            shutdown_position: None,
+            taxi_out: None,
+            departure: None,
+            arrival: None,
+            shutdown: None,
+            wheels_up: None,
+            wheels_down: None,
+            departure_course: None,
+            arrival_course: None,
+            rotation_speed_kt: None,
+            touchdown_speed_kt: None,
+            track: Track::default(),
+            category: None,
+            aircraft_changed: false,
+            frozen_feed: false,
+            aborted_takeoffs: 0,
+            hobbs_time: Duration::zero(),
+            go_arounds: 0,
+            touch_and_goes: vec![],
+            max_baro_altitude_ft: None,
+            holds: vec![],
+            engine_cycles: 0,
+            last_sample: None,
+            pauses: vec![],
+            unchanged_since: None,
+            takeoff_roll: false,
+            stopped_since: None,
+            climbed_out: false,
+            low_approach: false,
+            touchdown_airport: None,
+            wind_sum: 0.0,
+            wind_samples: 0,
+            hold_detector: HoldDetector::default(),
+        }
+    }
+
+    pub fn arrive(&mut self, airport: &Airport, time: &DateTime<Utc>) {
+        self.arrival = Some((airport.clone(), *time));
+    }
+
+    pub fn depart(&mut self, airport: &Airport, time: &DateTime<Utc>) {
+        self.departure = Some((airport.clone(), *time));
+    }
+
+    /// Time from taxiing out to shutting down.
+    ///
+    /// Computed from the recorded timestamps rather than their formatted strings,
+    /// so flights crossing midnight come out right.
+    pub fn block_time(&self) -> Option<Duration> {
+        Some(self.unpaused_time(self.taxi_out?, self.shutdown?))
+    }
+
+    /// Time from takeoff to the (final) landing.
+    pub fn air_time(&self) -> Option<Duration> {
+        Some(self.unpaused_time(self.departure.as_ref()?.1, self.arrival.as_ref()?.1))
+    }
+
+    /// Time from `from` to `to`, without the time the sim was paused.
+    fn unpaused_time(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Duration {
+        let paused = self
+            .pauses
+            .iter()
+            .map(|(paused, resumed)| (*resumed).min(to) - (*paused).max(from))
+            .filter(|paused| *paused > Duration::zero())
+            .fold(Duration::zero(), |sum, paused| sum + paused);
+        to - from - paused
+    }
+
+    /// Distance in meters flown (or taxied) along the track between two times.
+    fn track_distance(&self, from: &DateTime<Utc>, to: &DateTime<Utc>) -> f64 {
+        let points = self
+            .track
+            .points()
+            .iter()
+            .filter(|p| &p.time >= from && &p.time <= to)
+            .collect::<Vec<_>>();
+        points
+            .windows(2)
+            .map(|w| w[0].position.distance(&w[1].position))
+            .sum()
+    }
+
+    /// Distance in meters along the whole track.
+    pub fn track_length(&self) -> f64 {
+        match self.track.points() {
+            [first, .., last] => self.track_distance(&first.time, &last.time),
+            _ => 0.0,
+        }
+    }
+
+    /// Distance in meters taxied from leaving the blocks to taking off, even
+    /// away from any known airport.
+    pub fn taxi_out_distance(&self) -> Option<f64> {
+        Some(self.track_distance(self.taxi_out.as_ref()?, self.wheels_up.as_ref()?))
+    }
+
+    /// Distance in meters taxied from the (final) landing to shutting down.
+    pub fn taxi_in_distance(&self) -> Option<f64> {
+        Some(self.track_distance(self.wheels_down.as_ref()?, self.shutdown.as_ref()?))
+    }
+
+    /// The distances in meters between the departure and arrival airports
+    /// along the great circle and along the rhumb line, the latter being how
+    /// far holding a constant course would've been. Both are on a sphere so
+    /// that they can be compared.
+    pub fn route_distances(&self) -> Option<(f64, f64)> {
+        let (departure, _) = self.departure.as_ref()?;
+        let (arrival, _) = self.arrival.as_ref()?;
+        let sphere = Ellipsoid::sphere(Ellipsoid::MEAN_RADIUS);
+        Some((
+            departure.position.distance_on(&arrival.position, &sphere),
+            departure.position.rhumb_distance(&arrival.position),
+        ))
+    }
+
+    /// Categorize the flight by the distance between the departure and arrival
+    /// airports, anything at least `cross_country_nm` apart is cross-country.
+    pub fn categorize(&self, cross_country_nm: f64) -> Option<FlightCategory> {
+        let (departure, _) = self.departure.as_ref()?;
+        let (arrival, _) = self.arrival.as_ref()?;
+        let category = if departure.ident == arrival.ident {
+            FlightCategory::Pattern
+        } else if departure.position.distance(&arrival.position) < cross_country_nm * NM_TO_M {
+            FlightCategory::Local
+        } else {
+            FlightCategory::CrossCountry
+        };
+        Some(category)
+    }
+
+    /// Rough wind component along the route in knots, positive for a tailwind
+    /// and negative for a headwind. It's the mean difference between the ground
+    /// speed and the true airspeed while airborne.
+    ///
+    /// This is only an indication of the wind: the samples aren't weighted by
+    /// time, and climbs, descents and crabbing into a crosswind all lower the
+    /// ground speed regardless of the wind along the route.
+    pub fn wind_component(&self) -> Option<f64> {
+        if self.wind_samples == 0 {
+            return None;
+        }
+        Some(self.wind_sum / self.wind_samples as f64)
+    }
+
+    /// Roughly when the climb to the cruise ended, see `Track::top_of_climb`.
+    pub fn top_of_climb(&self) -> Option<DateTime<Utc>> {
+        self.track.top_of_climb(ALTITUDE_SMOOTHING_WINDOW)
+    }
+
+    /// Roughly when the final descent started, see `Track::top_of_descent`.
+    pub fn top_of_descent(&self) -> Option<DateTime<Utc>> {
+        self.track.top_of_descent(ALTITUDE_SMOOTHING_WINDOW)
+    }
+
+    /// The touch and goes at each airport, e.g. `3 at LCPH, 1 at LCLK`.
+    pub fn touch_and_go_summary(&self) -> Option<String> {
+        if self.touch_and_goes.is_empty() {
+            return None;
+        }
+        let summary = self
+            .touch_and_goes
+            .iter()
+            .map(|(ident, count)| format!("{count} at {ident}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(summary)
+    }
+
+    /// The flight level cruised at, taken from the highest indicated altitude,
+    /// e.g. 350 for 35,000 ft.
+    ///
+    /// Below the transition altitude the altimeter is set to the local
+    /// pressure rather than standard, so for low flights it's only the
+    /// altitude in hundreds of feet.
+    pub fn cruise_flight_level(&self) -> Option<u32> {
+        let altitude_ft = self.max_baro_altitude_ft?;
+        Some((altitude_ft.max(0.0) / 100.0).round() as u32)
+    }
+
+    fn touch_and_go(&mut self) {
+        let ident = self.touchdown_airport.as_deref().unwrap_or("unknown");
+        match self.touch_and_goes.iter_mut().find(|(i, _)| i == ident) {
+            Some((_, count)) => *count += 1,
+            None => self.touch_and_goes.push((ident.to_string(), 1)),
+        }
+    }
+
+    fn record_altitude(&mut self, aircraft: &Aircraft) {
+        let max = self
+            .max_baro_altitude_ft
+            .get_or_insert(aircraft.baro_altitude_ft);
+        *max = max.max(aircraft.baro_altitude_ft);
+    }
+
+    fn accumulate_wind(&mut self, aircraft: &Aircraft) {
+        self.wind_sum += aircraft.ground_speed - aircraft.true_airspeed_kt;
+        self.wind_samples += 1;
+    }
+
+    /// Record a hold when a lap of one has just been flown, unless it's
+    /// another lap of a hold that's already been recorded.
+    fn record_hold(&mut self, config: &HoldConfig, aircraft: &Aircraft, now: DateTime<Utc>) {
+        let Some(fix) = self
+            .hold_detector
+            .update(config, now, aircraft.position, aircraft.heading)
+        else {
+            return;
+        };
+        if self
+            .holds
+            .iter()
+            .all(|hold| hold.distance(&fix) > config.radius_m)
+        {
+            info!("Hold.");
+            self.holds.push(fix);
+        }
+    }
+
+    /// Add the time since the previous sample to the hobbs time, if the engine
+    /// was running since then.
+    fn accumulate_hobbs(&mut self, aircraft: &Aircraft, now: DateTime<Utc>) {
+        if let Some(LastSample {
+            time,
+            engine_on: true,
+            ..
+        }) = self.last_sample
+        {
+            self.hobbs_time += self.unpaused_time(time, now);
+        }
+        self.last_sample = Some(LastSample {
+            time: now,
+            engine_on: aircraft.engine_on,
+            on_ground: aircraft.on_ground,
+        });
+    }
+
+    /// Warn, once for the flight, when the sim has sent the exact same
+    /// position, ground and engine state for longer than `frozen_after`.
+    /// Nothing else is changed, the sim may well just be slow to recover.
+    fn check_frozen(&mut self, aircraft: &Aircraft, now: DateTime<Utc>, frozen_after: Duration) {
+        let sample = (aircraft.position, aircraft.on_ground, aircraft.engine_on);
+        match self.unchanged_since {
+            Some((unchanged, since)) if unchanged == sample => {
+                let unchanged_for = self.unpaused_time(since, now);
+                if !self.frozen_feed && unchanged_for > frozen_after {
+                    warn!(
+                        "the sim has sent the same position for {}s, its feed may be frozen.",
+                        unchanged_for.num_seconds()
+                    );
+                    self.frozen_feed = true;
+                }
+            }
+            _ => self.unchanged_since = Some((sample, now)),
+        }
+    }
+
+    /// The flight's fields in the order of the logbook's columns.
+    pub fn to_record(&self) -> Vec<Option<String>> {
+        self.to_record_with_time_format(DATE_FORMAT)
+    }
+
+    /// Same as `to_record`, with the times in `time_format`.
+    pub fn to_record_with_time_format(&self, time_format: &str) -> Vec<Option<String>> {
+        COLUMNS
+            .iter()
+            .map(|(_, field)| field(self, time_format))
+            .collect()
+    }
+}
+
+/// The shortest flights worth logging, anything under every threshold that's
+/// set is skipped, e.g. repositioning to another gate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogThreshold {
+    pub min_distance_nm: Option<f64>,
+    pub min_duration: Option<Duration>,
+}
+
+impl LogThreshold {
+    /// Whether the flight is shorter than every threshold, in distance along
+    /// the track and block time. Always false when no threshold is set.
+    pub fn is_below(&self, flight: &Flight) -> bool {
+        if self.min_distance_nm.is_none() && self.min_duration.is_none() {
+            return false;
+        }
+        let block_time = flight.block_time().unwrap_or_else(Duration::zero);
+        self.min_distance_nm
+            .is_none_or(|nm| flight.track_length() < nm * NM_TO_M)
+            && self.min_duration.is_none_or(|min| block_time < min)
+    }
+}
+
+/// What to do when the sim switches to another aircraft mid-flight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AircraftChangePolicy {
+    /// Keep logging the current flight, warning that the aircraft changed.
+    Warn,
+    /// Discard the current flight and start a new one with the new aircraft.
+    Reset,
+}
+
+impl std::str::FromStr for AircraftChangePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(AircraftChangePolicy::Warn),
+            "reset" => Ok(AircraftChangePolicy::Reset),
+            _ => Err(format!("invalid aircraft change policy: {s}")),
+        }
+    }
+}
+
+/// How to tell whether the aircraft is on the ground when the sim's signals
+/// disagree, e.g. the gear touching down for a moment on a bounce.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroundPolicy {
+    /// On the ground only once both signals say so.
+    Both,
+    /// On the ground as soon as either signal says so.
+    Either,
+}
+
+impl GroundPolicy {
+    /// Whether `aircraft` is on the ground, going by `on_ground` alone when
+    /// the sim doesn't send the gear's contact.
+    pub fn on_ground(&self, aircraft: &Aircraft) -> bool {
+        match (self, aircraft.gear_on_ground) {
+            (_, None) => aircraft.on_ground,
+            (GroundPolicy::Both, Some(gear_on_ground)) => aircraft.on_ground && gear_on_ground,
+            (GroundPolicy::Either, Some(gear_on_ground)) => aircraft.on_ground || gear_on_ground,
+        }
+    }
+}
+
+impl std::str::FromStr for GroundPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "both" => Ok(GroundPolicy::Both),
+            "either" => Ok(GroundPolicy::Either),
+            _ => Err(format!(
+                "invalid ground policy: {s}, valid options: both, either"
+            )),
+        }
+    }
+}
+
+/// The thresholds the flight's phases are told apart by, and which flights are
+/// worth logging. Defaults to the constants above.
+#[derive(Clone, Copy, Debug)]
+pub struct FlightConfig {
+    /// Flights between airports at least this far apart, in nautical miles,
+    /// are cross-country.
+    pub cross_country_nm: f64,
+    pub aircraft_change: AircraftChangePolicy,
+    pub ground_policy: GroundPolicy,
+    /// Complete the flight once the aircraft has been stopped on the ground
+    /// for this long after landing, even if the engines are kept running.
+    /// The next leg starts once the aircraft is moving again.
+    pub turnaround: Option<Duration>,
+    /// Prefer the departure airport over a neighbouring one that's at most
+    /// this many meters closer, e.g. when landing back on a runway that's
+    /// nearly as close to the next field.
+    pub departure_bias_m: f64,
+    /// See `TAKEOFF_ROLL_KT`.
+    pub takeoff_roll_kt: f64,
+    /// See `TAXI_SPEED_KT`.
+    pub taxi_speed_kt: f64,
+    /// See `TOUCH_AND_GO_AGL_FT`.
+    pub touch_and_go_agl_ft: f64,
+    /// See `GO_AROUND_AGL_FT`.
+    pub go_around_agl_ft: f64,
+    /// See `CLIMB_OUT_AGL_FT`.
+    pub climb_out_agl_ft: f64,
+    /// Warn when the same position is sent for longer than this en route.
+    pub frozen_feed: Duration,
+    /// Start a new flight when the aircraft jumps further than this between
+    /// two samples on the ground, e.g. when the sim's flight is reset to
+    /// another airport without reconnecting.
+    pub reposition_km: f64,
+    /// Completed flights under the threshold aren't logged.
+    pub log_threshold: LogThreshold,
+    pub hold: HoldConfig,
+}
+
+impl Default for FlightConfig {
+    fn default() -> Self {
+        Self {
+            cross_country_nm: DEFAULT_CROSS_COUNTRY_NM,
+            aircraft_change: AircraftChangePolicy::Warn,
+            ground_policy: GroundPolicy::Both,
+            turnaround: None,
+            departure_bias_m: DEFAULT_DEPARTURE_BIAS_M,
+            takeoff_roll_kt: TAKEOFF_ROLL_KT,
+            taxi_speed_kt: TAXI_SPEED_KT,
+            touch_and_go_agl_ft: TOUCH_AND_GO_AGL_FT,
+            go_around_agl_ft: GO_AROUND_AGL_FT,
+            climb_out_agl_ft: CLIMB_OUT_AGL_FT,
+            frozen_feed: Duration::seconds(DEFAULT_FROZEN_FEED_SECS),
+            reposition_km: DEFAULT_REPOSITION_KM,
+            log_threshold: LogThreshold::default(),
+            hold: HoldConfig::default(),
+        }
+    }
+}
+
+/// Positions within this many degrees of 0,0 haven't been set by the sim yet.
+const UNPOSITIONED_DEG: f64 = 0.001;
+
+/// Right after connecting the sims can report a position of 0,0, before the
+/// aircraft has been placed.
+fn is_unpositioned(aircraft: &Aircraft) -> bool {
+    aircraft.position.latitude().abs() < UNPOSITIONED_DEG
+        && aircraft.position.longitude().abs() < UNPOSITIONED_DEG
+}
+
+fn track_point(aircraft: &Aircraft, now: DateTime<Utc>) -> TrackPoint {
+    TrackPoint {
+        time: now,
+        position: aircraft.position,
+        heading: aircraft.heading,
+        elevation_ft: aircraft.elevation_ft,
+    }
+}
+
+fn is_same_aircraft(a: &Aircraft, b: &Aircraft) -> bool {
+    a.title == b.title && a.registration == b.registration
+}
+
+/// What happened to the flight on an update, for reacting to each transition,
+/// e.g. in a GUI.
+#[derive(Clone, Debug)]
+pub enum FlightEvent {
+    /// The engine was started and the aircraft is on its way to the runway.
+    TaxiStarted,
+    /// The takeoff roll was abandoned.
+    AbortedTakeoff,
+    /// Left the ground, from the airport if there's one there.
+    Departed(Option<Airport>),
+    GoAround,
+    /// Touched down, at the airport if there's one there.
+    Arrived(Option<Airport>),
+    /// Took off again after landing.
+    TouchAndGo,
+    /// The flight is over and the next one starts with the next update.
+    Completed(Box<Flight>),
+}
+
+/// Follows the aircraft through the phases of a flight, from startup to shutdown.
+pub struct FlightTracker<C = SystemClock> {
+    pub current_flight: Option<Flight>,
+    clock: C,
+    config: FlightConfig,
+    // when the sim was paused, if it is
+    paused_since: Option<DateTime<Utc>>,
+    // whether the last leg ended in a turnaround, with the engines still
+    // running for the next one
+    after_turnaround: bool,
+}
+
+impl FlightTracker {
+    pub fn new(config: FlightConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock> FlightTracker<C> {
+    /// Same as `new`, timing the flights with `clock`.
+    pub fn with_clock(config: FlightConfig, clock: C) -> Self {
+        Self {
+            current_flight: None,
+            clock,
+            config,
+            paused_since: None,
+            after_turnaround: false,
+        }
+    }
+
+    /// Advance the current flight with the latest data from the sim, returning
+    /// the transition it caused, if any, and the flight once it's completed.
+    pub fn update(
+        &mut self,
+        aircraft: &Aircraft,
+        closest_airport: Option<Airport>,
+    ) -> Option<FlightEvent> {
+        self.update_at(aircraft, closest_airport, self.clock.now())
+    }
+
+    /// The times to give a batch of `len` samples received together now,
+    /// spread evenly from the flight's previous sample up to now (a second
+    /// apart without one), so that they're distinct and in order.
+    pub fn batch_times(&self, len: usize) -> Vec<DateTime<Utc>> {
+        let now = self.clock.now();
+        let previous = self
+            .current_flight
+            .as_ref()
+            .and_then(|flight| flight.last_sample)
+            .map(|last| last.time)
+            .filter(|time| *time < now)
+            .unwrap_or_else(|| now - Duration::seconds(len as i64));
+        let step = (now - previous) / len as i32;
+        (1..=len as i32).map(|i| previous + step * i).collect()
+    }
+
+    /// Only add the sample received at `now` to the current flight's track,
+    /// without moving the flight on, e.g. for the samples of a batch before
+    /// the latest one.
+    pub fn update_track(&mut self, aircraft: &Aircraft, now: DateTime<Utc>) {
+        if aircraft.paused || is_unpositioned(aircraft) {
+            return;
+        }
+        if let Some(flight) = self.current_flight.as_mut() {
+            flight.track.push(track_point(aircraft, now));
+        }
+    }
+
+    /// Same as `update`, with the data received at `now`.
+    ///
+    /// Samples at 0,0 are ignored, they're sent before the aircraft has been
+    /// positioned and would otherwise start the flight in the Atlantic.
+    ///
+    /// Samples while the sim is paused are ignored too, and the time spent
+    /// paused isn't counted in the flight's durations.
+    pub fn update_at(
+        &mut self,
+        aircraft: &Aircraft,
+        closest_airport: Option<Airport>,
+        now: DateTime<Utc>,
+    ) -> Option<FlightEvent> {
+        if aircraft.paused {
+            self.paused_since.get_or_insert(now);
+            return None;
+        }
+        if let Some(paused_since) = self.paused_since.take() {
+            if let Some(flight) = self.current_flight.as_mut() {
+                flight.pauses.push((paused_since, now));
+            }
+        }
+
+        if is_unpositioned(aircraft) {
+            return None;
+        }
+        let aircraft = &Aircraft {
+            on_ground: self.config.ground_policy.on_ground(aircraft),
+            ..aircraft.clone()
+        };
+
+        // initialize current flight if there isn't one
+        let flight = self
+            .current_flight
+            .get_or_insert_with(|| Flight::new(aircraft));
+
+        if !is_same_aircraft(&flight.aircraft, aircraft) {
+            // nothing has been logged yet before starting up, so it's always
+            // safe to switch to the new aircraft
+            if flight.state == FlightState::Preflight
+                || self.config.aircraft_change == AircraftChangePolicy::Reset
+            {
+                info!(
+                    "Aircraft changed to {}, starting a new flight.",
+                    aircraft.title
+                );
+                *flight = Flight::new(aircraft);
+            } else if !flight.aircraft_changed {
+                warn!(
+                    "aircraft changed from {} to {} during the flight, continuing with {}.",
+                    flight.aircraft.title, aircraft.title, flight.aircraft.title
+                );
+                flight.aircraft_changed = true;
+            }
+        }
+
+        // both on the ground, a jump from the air is a landing after a gap in
+        // the samples
+        let repositioned = aircraft.on_ground
+            && flight.last_sample.is_some_and(|last| last.on_ground)
+            && flight.track.points().last().is_some_and(|last| {
+                last.position.distance(&aircraft.position) > self.config.reposition_km * 1000.0
+            });
+        if repositioned {
+            info!("Aircraft repositioned, starting a new flight.");
+            *flight = Flight::new(aircraft);
+        }
+
+        let closest_airport = bias_to_departure(
+            closest_airport,
+            aircraft.position,
+            flight.departure.as_ref().map(|(airport, _)| airport),
+            self.config.departure_bias_m,
+        );
+
+        flight.track.push(track_point(aircraft, now));
+        if flight.last_sample.is_some_and(|last| last.engine_on) && !aircraft.engine_on {
+            flight.engine_cycles += 1;
+        }
+        flight.accumulate_hobbs(aircraft, now);
+        debug!("{aircraft}, {}", flight.state);
+        let mut event = None;
+        match flight.state {
+            FlightState::Preflight => {
+                if aircraft.is_parked() {
+                    flight.parked_at = Some(aircraft.position);
+                    flight.origin = closest_airport;
+                }
+                // after a turnaround the engines are already running, the
+                // next leg starts once we're moving
+                if self.after_turnaround
+                    && (!aircraft.engine_on || aircraft.ground_speed >= STOPPED_KT)
+                {
+                    self.after_turnaround = false;
+                }
+                if aircraft.engine_on && !self.after_turnaround {
+                    flight.taxi_out = Some(now);
+                    flight.state = FlightState::Taxi;
+                    event = Some(FlightEvent::TaxiStarted);
+                }
+            }
+            FlightState::Taxi => {
+                if !aircraft.on_ground {
+                    flight.wheels_up = Some(now);
+                    flight.departure_course = flight.track.course();
+                    flight.rotation_speed_kt = Some(aircraft.ground_speed);
+                    // the runway can be outside of the airport's bounds, but
+                    // it's still the airport we were parked at
+                    let departure = closest_airport.or_else(|| flight.origin.clone());
+                    match &departure {
+                        Some(airport) => flight.depart(airport, &now),
+                        None => warn!("took off outside of any known airport."),
+                    }
+                    flight.takeoff_roll = false;
+                    flight.climbed_out = false;
+                    flight.hold_detector = HoldDetector::default();
+                    flight.state = FlightState::EnRoute;
+                    event = Some(FlightEvent::Departed(departure));
+                } else if aircraft.ground_speed >= self.config.takeoff_roll_kt {
+                    flight.takeoff_roll = true;
+                } else if flight.takeoff_roll && aircraft.ground_speed < self.config.taxi_speed_kt {
+                    info!("Aborted takeoff.");
+                    flight.aborted_takeoffs += 1;
+                    flight.takeoff_roll = false;
+                    event = Some(FlightEvent::AbortedTakeoff);
+                }
+            }
+            FlightState::EnRoute => {
+                flight.check_frozen(aircraft, now, self.config.frozen_feed);
+                if !aircraft.on_ground {
+                    flight.accumulate_wind(aircraft);
+                    flight.record_altitude(aircraft);
+                    flight.record_hold(&self.config.hold, aircraft, now);
+                }
+                if aircraft.on_ground {
+                    flight.wheels_down = Some(now);
+                    flight.arrival_course = flight.track.course();
+                    flight.touchdown_speed_kt = Some(aircraft.ground_speed);
+                    flight.touchdown_airport = closest_airport.as_ref().map(|a| a.ident.clone());
+                    match &closest_airport {
+                        Some(airport) => flight.arrive(airport, &now),
+                        None => warn!("landed outside of any known airport."),
+                    }
+                    flight.low_approach = false;
+                    flight.state = FlightState::Landed;
+                    event = Some(FlightEvent::Arrived(closest_airport));
+                } else if aircraft.agl_ft > self.config.climb_out_agl_ft {
+                    if flight.low_approach {
+                        info!("Go around.");
+                        flight.go_arounds += 1;
+                        flight.low_approach = false;
+                        event = Some(FlightEvent::GoAround);
+                    }
+                    flight.climbed_out = true;
+                } else if flight.climbed_out
+                    && aircraft.agl_ft < self.config.go_around_agl_ft
+                    && closest_airport.is_some()
+                {
+                    flight.low_approach = true;
+                }
+            }
+            FlightState::Landed => {
+                if !aircraft.on_ground && aircraft.agl_ft > self.config.touch_and_go_agl_ft {
+                    // did a touch and go or a go around
+                    flight.touch_and_go();
+                    flight.climbed_out = false;
+                    flight.hold_detector = HoldDetector::default();
+                    flight.state = FlightState::EnRoute;
+                    event = Some(FlightEvent::TouchAndGo);
+                } else if !aircraft.engine_on {
+                    flight.shutdown = Some(now);
+                    flight.shutdown_position = Some(aircraft.position);
+                    flight.category = flight.categorize(self.config.cross_country_nm);
+                    flight.state = FlightState::Complete;
+                } else if aircraft.ground_speed < STOPPED_KT {
+                    let stopped_since = *flight.stopped_since.get_or_insert(now);
+                    if self
+                        .config
+                        .turnaround
+                        .is_some_and(|t| flight.unpaused_time(stopped_since, now) >= t)
+                    {
+                        info!("Turnaround with the engines running, completing the leg.");
+                        // the leg ended when we stopped, not when we noticed
+                        flight.shutdown = Some(stopped_since);
+                        flight.shutdown_position = Some(aircraft.position);
+                        flight.category = flight.categorize(self.config.cross_country_nm);
+                        flight.state = FlightState::Complete;
+                        self.after_turnaround = true;
+                    }
+                } else {
+                    flight.stopped_since = None;
+                }
+            }
+            FlightState::Complete => {
+                // reset flight
+                return self
+                    .current_flight
+                    .take()
+                    .map(|flight| FlightEvent::Completed(Box::new(flight)));
+            }
+        }
+        event
+    }
+}
+
+/// Return the departure airport instead of the closest airport, if it's at
+/// most `bias_m` meters further from `position`.
+fn bias_to_departure(
+    closest: Option<Airport>,
+    position: LatLon,
+    departure: Option<&Airport>,
+    bias_m: f64,
+) -> Option<Airport> {
+    let (Some(nearest), Some(departure)) = (&closest, departure) else {
+        return closest;
+    };
+    let further_m = position.distance(&departure.position) - position.distance(&nearest.position);
+    if nearest.ident != departure.ident && further_m <= bias_m {
+        Some(departure.clone())
+    } else {
+        closest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::logbook::header;
+    use chrono::TimeZone;
+
+    fn aircraft() -> Aircraft {
+        Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position: LatLon::new(34.717778, 32.485556),
+            engine_on: true,
+            on_ground: true,
+            ..Aircraft::default()
+        }
+    }
+
+    // Paphos Airport
+    fn lcph() -> Airport {
+        Airport {
+            id: 0,
+            ident: String::from("LCPH"),
+            position: LatLon::new(34.717778, 32.485556),
+        }
+    }
+
+    // Larnaca Airport, ~57nm from Paphos
+    fn lclk() -> Airport {
+        Airport {
+            id: 1,
+            ident: String::from("LCLK"),
+            position: LatLon::new(34.875, 33.624722),
+        }
+    }
+
+    #[test]
+    fn test_durations_across_midnight() {
+        let mut flight = Flight::new(&aircraft());
+        flight.taxi_out = Some(Utc.with_ymd_and_hms(2023, 12, 31, 23, 40, 0).unwrap());
+        flight.depart(
+            &lcph(),
+            &Utc.with_ymd_and_hms(2023, 12, 31, 23, 50, 0).unwrap(),
+        );
+        flight.arrive(
+            &lclk(),
+            &Utc.with_ymd_and_hms(2024, 1, 1, 0, 20, 0).unwrap(),
+        );
+        flight.shutdown = Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 25, 0).unwrap());
+
+        assert_eq!(Some(Duration::minutes(30)), flight.air_time());
+        assert_eq!(Some(Duration::minutes(45)), flight.block_time());
+        let block = header().position(|h| h == "Block Time").unwrap();
+        let air = header().position(|h| h == "Air Time").unwrap();
+        let departure = header().position(|h| h == "Departure Local Time").unwrap();
+        let arrival = header().position(|h| h == "Arrival Local Time").unwrap();
+        let record = flight.to_record();
+        assert_eq!(Some(String::from("0:45")), record[block]);
+        assert_eq!(Some(String::from("0:30")), record[air]);
+        assert_eq!(
+            Some(String::from("2024-01-01 01:50:00 +02:00")),
+            record[departure]
+        );
+        assert_eq!(
+            Some(String::from("2024-01-01 02:20:00 +02:00")),
+            record[arrival]
+        );
+    }
+
+    #[test]
+    fn test_durations_incomplete_flight() {
+        let flight = Flight::new(&aircraft());
+        assert_eq!(None, flight.block_time());
+        assert_eq!(None, flight.air_time());
+    }
+
+    #[test]
+    fn test_categorize_pattern() {
+        let mut flight = Flight::new(&aircraft());
+        flight.depart(&lcph(), &Utc::now());
+        flight.arrive(&lcph(), &Utc::now());
+        assert_eq!(
+            Some(FlightCategory::Pattern),
+            flight.categorize(DEFAULT_CROSS_COUNTRY_NM)
+        );
+    }
+
+    #[test]
+    fn test_categorize_local() {
+        let mut flight = Flight::new(&aircraft());
+        flight.depart(&lcph(), &Utc::now());
+        flight.arrive(&lclk(), &Utc::now());
+        assert_eq!(Some(FlightCategory::Local), flight.categorize(60.0));
+    }
+
+    #[test]
+    fn test_categorize_cross_country() {
+        let mut flight = Flight::new(&aircraft());
+        flight.depart(&lcph(), &Utc::now());
+        flight.arrive(&lclk(), &Utc::now());
+        assert_eq!(
+            Some(FlightCategory::CrossCountry),
+            flight.categorize(DEFAULT_CROSS_COUNTRY_NM)
+        );
+    }
+
+    #[test]
+    fn test_categorize_without_arrival() {
+        let mut flight = Flight::new(&aircraft());
+        flight.depart(&lcph(), &Utc::now());
+        assert_eq!(None, flight.categorize(DEFAULT_CROSS_COUNTRY_NM));
+    }
+
+    fn tracker() -> FlightTracker {
+        FlightTracker::new(FlightConfig::default())
+    }
+
+    fn completed(event: Option<FlightEvent>) -> Flight {
+        match event {
+            Some(FlightEvent::Completed(flight)) => *flight,
+            event => panic!("expected the flight to be completed, got {event:?}"),
+        }
+    }
+
+    fn other_aircraft() -> Aircraft {
+        Aircraft {
+            title: String::from("Cessna 172"),
+            icao: String::from("C172"),
+            registration: String::from("N172SP"),
+            ..aircraft()
+        }
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = FlightConfig::default();
+        assert_eq!(DEFAULT_CROSS_COUNTRY_NM, config.cross_country_nm);
+        assert_eq!(AircraftChangePolicy::Warn, config.aircraft_change);
+        assert_eq!(GroundPolicy::Both, config.ground_policy);
+        assert_eq!(None, config.turnaround);
+        assert_eq!(DEFAULT_DEPARTURE_BIAS_M, config.departure_bias_m);
+        assert_eq!(40.0, config.takeoff_roll_kt);
+        assert_eq!(20.0, config.taxi_speed_kt);
+        assert_eq!(50.0, config.touch_and_go_agl_ft);
+        assert_eq!(200.0, config.go_around_agl_ft);
+        assert_eq!(500.0, config.climb_out_agl_ft);
+        assert_eq!(Duration::seconds(60), config.frozen_feed);
+        assert_eq!(None, config.log_threshold.min_distance_nm);
+        assert_eq!(None, config.log_threshold.min_duration);
+        assert_eq!(Duration::minutes(8), config.hold.max_lap);
+        assert_eq!(2000.0, config.hold.radius_m);
+    }
+
+    #[test]
+    fn test_tracker_complete_flight() {
+        let mut tracker = tracker();
+        let parked = Aircraft {
+            engine_on: false,
+            ..aircraft()
+        };
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+
+        assert!(tracker.update(&parked, None).is_none());
+        assert!(matches!(
+            tracker.update(&aircraft(), None),
+            Some(FlightEvent::TaxiStarted)
+        ));
+        assert_eq!(
+            FlightState::Taxi,
+            tracker.current_flight.as_ref().unwrap().state
+        );
+        assert!(matches!(
+            tracker.update(&airborne, Some(lcph())),
+            Some(FlightEvent::Departed(Some(airport))) if airport.ident == "LCPH"
+        ));
+        assert!(matches!(
+            tracker.update(&aircraft(), Some(lclk())),
+            Some(FlightEvent::Arrived(Some(airport))) if airport.ident == "LCLK"
+        ));
+        assert!(tracker.update(&parked, Some(lclk())).is_none());
+
+        let flight = completed(tracker.update(&parked, Some(lclk())));
+        assert_eq!("LCPH", flight.departure.unwrap().0.ident);
+        assert_eq!("LCLK", flight.arrival.unwrap().0.ident);
+        assert!(flight.shutdown.is_some());
+        assert_eq!(Some(FlightCategory::CrossCountry), flight.category);
+        assert!(tracker.current_flight.is_none());
+    }
+
+    #[test]
+    fn test_tracker_aircraft_change_in_preflight() {
+        let mut tracker = tracker();
+        let parked = Aircraft {
+            engine_on: false,
+            ..aircraft()
+        };
+        let other = Aircraft {
+            engine_on: false,
+            ..other_aircraft()
+        };
+
+        tracker.update(&parked, None);
+        tracker.update(&other, None);
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!("Cessna 172", flight.aircraft.title);
+        assert_eq!(FlightState::Preflight, flight.state);
+        assert!(!flight.aircraft_changed);
+    }
+
+    #[test]
+    fn test_tracker_aircraft_change_en_route() {
+        let mut tracker = tracker();
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+        let other = Aircraft {
+            on_ground: false,
+            ..other_aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne, Some(lcph()));
+        tracker.update(&other, None);
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!("Challenger 650", flight.aircraft.title);
+        assert_eq!(FlightState::EnRoute, flight.state);
+        assert!(flight.aircraft_changed);
+    }
+
+    #[test]
+    fn test_tracker_aircraft_change_en_route_reset() {
+        let mut tracker = FlightTracker::new(FlightConfig {
+            aircraft_change: AircraftChangePolicy::Reset,
+            ..FlightConfig::default()
+        });
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+        let other = Aircraft {
+            on_ground: false,
+            ..other_aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne, Some(lcph()));
+        tracker.update(&other, None);
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!("Cessna 172", flight.aircraft.title);
+        assert_eq!(FlightState::Preflight, flight.state);
+    }
+
+    #[test]
+    fn test_tracker_reposition() {
+        let mut tracker = tracker();
+        // Larnaca, ~100km away
+        let teleported = Aircraft {
+            position: LatLon::new(34.875, 33.625),
+            engine_on: false,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), Some(lcph()));
+        assert_eq!(
+            FlightState::Taxi,
+            tracker.current_flight.as_ref().unwrap().state
+        );
+        assert!(tracker.update(&teleported, None).is_none());
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Preflight, flight.state);
+        assert_eq!(None, flight.taxi_out);
+        assert_eq!(1, flight.track.points().len());
+    }
+
+    #[test]
+    fn test_tracker_touchdown_after_gap_is_not_a_reposition() {
+        let mut tracker = tracker();
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+        // the next sample only arrives after landing at Larnaca
+        let landed = Aircraft {
+            position: LatLon::new(34.875, 33.625),
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne, Some(lcph()));
+        tracker.update(&landed, Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Landed, flight.state);
+        assert_eq!("LCPH", flight.departure.as_ref().unwrap().0.ident);
+        assert_eq!("LCLK", flight.arrival.as_ref().unwrap().0.ident);
+    }
+
+    #[test]
+    fn test_tracker_aborted_takeoff() {
+        let mut tracker = tracker();
+        let at_speed = |ground_speed| Aircraft {
+            ground_speed,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        for ground_speed in [15.0, 45.0, 80.0, 60.0, 30.0, 10.0] {
+            tracker.update(&at_speed(ground_speed), None);
+        }
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Taxi, flight.state);
+        assert_eq!(1, flight.aborted_takeoffs);
+
+        // fast taxiing never reaches the takeoff roll speed
+        for ground_speed in [35.0, 10.0] {
+            tracker.update(&at_speed(ground_speed), None);
+        }
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(1, flight.aborted_takeoffs);
+    }
+
+    #[test]
+    fn test_flight_state_round_trip() {
+        for state in FlightState::ALL {
+            assert_eq!(Ok(state), state.to_string().parse());
+        }
+        assert_eq!("en-route", FlightState::EnRoute.to_string());
+        assert!("EnRoute".parse::<FlightState>().is_err());
+        assert!("".parse::<FlightState>().is_err());
+    }
+
+    #[test]
+    fn test_tracker_turnaround_without_shutdown() {
+        let mut tracker = FlightTracker::new(FlightConfig {
+            turnaround: Some(Duration::minutes(10)),
+            ..FlightConfig::default()
+        });
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+        let taxiing = Aircraft {
+            ground_speed: 15.0,
+            ..aircraft()
+        };
+
+        tracker.update_at(&aircraft(), None, at(0));
+        tracker.update_at(&airborne, Some(lcph()), at(10));
+        tracker.update_at(&taxiing, Some(lclk()), at(40));
+        // stopped at the gate with the engines running
+        tracker.update_at(&aircraft(), Some(lclk()), at(45));
+        tracker.update_at(&aircraft(), Some(lclk()), at(50));
+        assert_eq!(
+            FlightState::Landed,
+            tracker.current_flight.as_ref().unwrap().state
+        );
+        tracker.update_at(&aircraft(), Some(lclk()), at(55));
+
+        let flight = completed(tracker.update_at(&aircraft(), Some(lclk()), at(56)));
+        assert_eq!(Some(at(45)), flight.shutdown);
+        assert_eq!("LCLK", flight.arrival.unwrap().0.ident);
+
+        // the next leg starts with the engines still running, once moving
+        tracker.update_at(&aircraft(), Some(lclk()), at(58));
+        let next = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Preflight, next.state);
+        assert_eq!(None, next.taxi_out);
+
+        tracker.update_at(&taxiing, Some(lclk()), at(60));
+        let next = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Taxi, next.state);
+        assert_eq!(Some(at(60)), next.taxi_out);
+    }
+
+    #[test]
+    fn test_tracker_short_stop_is_not_a_turnaround() {
+        let mut tracker = FlightTracker::new(FlightConfig {
+            turnaround: Some(Duration::minutes(10)),
+            ..FlightConfig::default()
+        });
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+        let taxiing = Aircraft {
+            ground_speed: 15.0,
+            ..aircraft()
+        };
+
+        tracker.update_at(&aircraft(), None, at(0));
+        tracker.update_at(&airborne, Some(lcph()), at(10));
+        tracker.update_at(&taxiing, Some(lclk()), at(40));
+        // holding short, then taxiing on to the gate
+        tracker.update_at(&aircraft(), Some(lclk()), at(41));
+        tracker.update_at(&taxiing, Some(lclk()), at(48));
+        tracker.update_at(&aircraft(), Some(lclk()), at(52));
+        tracker.update_at(&aircraft(), Some(lclk()), at(55));
+
+        assert_eq!(
+            FlightState::Landed,
+            tracker.current_flight.as_ref().unwrap().state
+        );
+    }
+
+    #[test]
+    fn test_ground_policy() {
+        let disagreeing = Aircraft {
+            on_ground: true,
+            gear_on_ground: Some(false),
+            ..aircraft()
+        };
+        assert!(!GroundPolicy::Both.on_ground(&disagreeing));
+        assert!(GroundPolicy::Either.on_ground(&disagreeing));
+        // without the gear's contact there's nothing to disagree with
+        assert!(GroundPolicy::Both.on_ground(&aircraft()));
+        assert_eq!(Ok(GroundPolicy::Either), "either".parse());
+        assert!("any".parse::<GroundPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_tracker_ground_signals_disagree() {
+        let airborne = Aircraft {
+            on_ground: false,
+            gear_on_ground: Some(false),
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+        // the sim counts something other than the gear touching down, e.g.
+        // a wingtip, while the gear is still in the air
+        let scraped = Aircraft {
+            on_ground: true,
+            gear_on_ground: Some(false),
+            agl_ft: 0.0,
+            ..aircraft()
+        };
+        let state_after_scrape = |ground_policy| {
+            let mut tracker = FlightTracker::new(FlightConfig {
+                ground_policy,
+                ..FlightConfig::default()
+            });
+            tracker.update(&aircraft(), None);
+            tracker.update(&airborne, Some(lcph()));
+            tracker.update(&scraped, None);
+            tracker.current_flight.as_ref().unwrap().state
+        };
+
+        assert_eq!(FlightState::EnRoute, state_after_scrape(GroundPolicy::Both));
+        assert_eq!(
+            FlightState::Landed,
+            state_after_scrape(GroundPolicy::Either)
+        );
+    }
+
+    #[test]
+    fn test_tracker_frozen_feed() {
+        let mut tracker = tracker();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |secs| start + Duration::seconds(secs);
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+        let frozen = |tracker: &FlightTracker| tracker.current_flight.as_ref().unwrap().frozen_feed;
+
+        tracker.update_at(&aircraft(), None, at(0));
+        tracker.update_at(&airborne, Some(lcph()), at(10));
+        tracker.update_at(&airborne, None, at(20));
+        tracker.update_at(&airborne, None, at(70));
+        assert!(!frozen(&tracker));
+        tracker.update_at(&airborne, None, at(90));
+        assert!(frozen(&tracker));
+
+        // the flight carries on as if nothing happened
+        let moved = Aircraft {
+            position: LatLon::new(34.8, 32.6),
+            ..airborne.clone()
+        };
+        tracker.update_at(&moved, None, at(100));
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::EnRoute, flight.state);
+        assert!(flight.frozen_feed);
+    }
+
+    #[test]
+    fn test_tracker_bounce_is_not_a_touch_and_go() {
+        let mut tracker = tracker();
+        let airborne_at = |agl_ft| Aircraft {
+            on_ground: false,
+            agl_ft,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne_at(1000.0), Some(lcph()));
+        tracker.update(&aircraft(), Some(lclk()));
+        // bounced on landing
+        tracker.update(&airborne_at(5.0), None);
+        assert_eq!(
+            FlightState::Landed,
+            tracker.current_flight.as_ref().unwrap().state
+        );
+
+        // went around
+        tracker.update(&airborne_at(200.0), None);
+        assert_eq!(
+            FlightState::EnRoute,
+            tracker.current_flight.as_ref().unwrap().state
+        );
+    }
+
+    #[test]
+    fn test_tracker_touch_and_goes_at_each_airport() {
+        let mut tracker = tracker();
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+        let touch_and_go = |tracker: &mut FlightTracker, airport: Airport| {
+            tracker.update(&aircraft(), Some(airport));
+            tracker.update(&airborne, None);
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne, Some(lcph()));
+        touch_and_go(&mut tracker, lcph());
+        touch_and_go(&mut tracker, lcph());
+        touch_and_go(&mut tracker, lclk());
+        touch_and_go(&mut tracker, lcph());
+        // the final landing isn't a touch and go
+        tracker.update(&aircraft(), Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Landed, flight.state);
+        assert_eq!(
+            vec![(String::from("LCPH"), 3), (String::from("LCLK"), 1)],
+            flight.touch_and_goes
+        );
+        assert_eq!(
+            Some(String::from("3 at LCPH, 1 at LCLK")),
+            flight.touch_and_go_summary()
+        );
+    }
+
+    #[test]
+    fn test_tracker_paused_time_isnt_counted() {
+        let mut tracker = tracker();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+        let paused = Aircraft {
+            paused: true,
+            ..airborne.clone()
+        };
+        let engine_off = Aircraft {
+            engine_on: false,
+            ..aircraft()
+        };
+
+        tracker.update_at(&aircraft(), None, at(0));
+        tracker.update_at(&airborne, Some(lcph()), at(10));
+        // paused for an hour mid-flight
+        for minutes in [20, 40, 60, 80] {
+            assert!(tracker.update_at(&paused, None, at(minutes)).is_none());
+        }
+        tracker.update_at(&airborne, None, at(80));
+        tracker.update_at(&aircraft(), Some(lclk()), at(90));
+        tracker.update_at(&engine_off, Some(lclk()), at(95));
+        let flight = completed(tracker.update_at(&engine_off, Some(lclk()), at(96)));
+
+        assert_eq!(Some(Duration::minutes(35)), flight.block_time());
+        assert_eq!(Some(Duration::minutes(20)), flight.air_time());
+        assert_eq!(Duration::minutes(35), flight.hobbs_time);
+        // the times themselves are still the wall clock's
+        assert_eq!(Some(at(95)), flight.shutdown);
+
+        // the next flight runs on the wall clock again
+        tracker.update_at(&aircraft(), None, at(100));
+        let next = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(at(100)), next.taxi_out);
+    }
+
+    #[test]
+    fn test_tracker_lands_back_at_departure_next_to_neighbour() {
+        // a fictional field 2km east of Paphos
+        let neighbour = Airport {
+            id: 2,
+            ident: String::from("LCXX"),
+            position: lcph().position.destination(90.0, 2000.0),
+        };
+        // slightly closer to the neighbour than to Paphos
+        let touchdown = Aircraft {
+            position: lcph().position.destination(90.0, 1100.0),
+            ..aircraft()
+        };
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+        let land_at = |tracker: &mut FlightTracker, touchdown: &Aircraft| {
+            tracker.update(&aircraft(), None);
+            tracker.update(&airborne, Some(lcph()));
+            tracker.update(touchdown, Some(neighbour.clone()));
+            let flight = tracker.current_flight.take().unwrap();
+            flight.arrival.unwrap().0.ident
+        };
+
+        assert_eq!("LCPH", land_at(&mut tracker(), &touchdown));
+        // clearly at the neighbour
+        let at_neighbour = Aircraft {
+            position: neighbour.position,
+            ..aircraft()
+        };
+        assert_eq!("LCXX", land_at(&mut tracker(), &at_neighbour));
+        // without the bias
+        let mut tracker = FlightTracker::new(FlightConfig {
+            departure_bias_m: 0.0,
+            ..FlightConfig::default()
+        });
+        assert_eq!("LCXX", land_at(&mut tracker, &touchdown));
+    }
+
+    #[test]
+    fn test_tracker_timestamps_from_clock() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+        let mut tracker = FlightTracker::with_clock(FlightConfig::default(), &clock);
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        clock.advance(Duration::minutes(7));
+        tracker.update(&airborne, Some(lcph()));
+        clock.advance(Duration::seconds(1830));
+        tracker.update(&aircraft(), Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(start), flight.taxi_out);
+        assert_eq!(Some(start + Duration::minutes(7)), flight.wheels_up);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 37, 30).unwrap()),
+            flight.wheels_down
+        );
+        assert_eq!(Some(Duration::seconds(1830)), flight.air_time());
+    }
+
+    #[test]
+    fn test_tracker_batch_times() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+        let mut tracker = FlightTracker::with_clock(FlightConfig::default(), &clock);
+        let at = |seconds| start + Duration::seconds(seconds);
+
+        // a second apart before the first sample
+        assert_eq!(vec![at(-2), at(-1), at(0)], tracker.batch_times(3));
+
+        tracker.update(&aircraft(), None);
+        clock.advance(Duration::seconds(6));
+        // spread since the previous sample
+        assert_eq!(vec![at(2), at(4), at(6)], tracker.batch_times(3));
+    }
+
+    #[test]
+    fn test_tracker_hobbs_time() {
+        let mut tracker = tracker();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let engine_off = Aircraft {
+            engine_on: false,
+            ..aircraft()
+        };
+
+        tracker.update_at(&engine_off, None, at(0));
+        tracker.update_at(&aircraft(), None, at(2));
+        tracker.update_at(&aircraft(), None, at(5));
+        // stalled the engine while taxiing, then restarted it
+        tracker.update_at(&engine_off, None, at(7));
+        tracker.update_at(&engine_off, None, at(15));
+        tracker.update_at(&aircraft(), None, at(20));
+        tracker.update_at(&aircraft(), None, at(24));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Taxi, flight.state);
+        assert_eq!(Duration::minutes(9), flight.hobbs_time);
+    }
+
+    #[test]
+    fn test_tracker_ignores_unpositioned_samples() {
+        let mut tracker = tracker();
+        let unpositioned = Aircraft {
+            position: LatLon::new(0.0, 0.0),
+            ..aircraft()
+        };
+
+        assert!(tracker.update(&unpositioned, None).is_none());
+        assert!(tracker.current_flight.is_none());
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&unpositioned, None);
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(1, flight.track.points().len());
+    }
+
+    #[test]
+    fn test_taxi_distance() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let mut flight = Flight::new(&aircraft());
+        let points = [
+            // taxiing out
+            (0, 34.7170, 32.4800),
+            (1, 34.7180, 32.4800),
+            (2, 34.7180, 32.4820),
+            (3, 34.7190, 32.4820),
+            // airborne
+            (10, 34.8000, 33.0000),
+            (20, 34.8500, 33.5000),
+            // taxiing in
+            (30, 34.8740, 33.6240),
+            (35, 34.8750, 33.6240),
+        ];
+        for (minutes, lat, lon) in points {
+            flight.track.push(TrackPoint {
+                time: at(minutes),
+                position: LatLon::new(lat, lon),
+                heading: 0.0,
+                elevation_ft: 0.0,
+            });
+        }
+        assert_eq!(None, flight.taxi_out_distance());
+
+        // off airport, the distances don't need the airports
+        flight.taxi_out = Some(at(0));
+        flight.wheels_up = Some(at(3));
+        flight.wheels_down = Some(at(30));
+        flight.shutdown = Some(at(35));
+
+        assert_eq!(405.0, flight.taxi_out_distance().unwrap().round());
+        assert_eq!(111.0, flight.taxi_in_distance().unwrap().round());
+        let taxi_out = header().position(|h| h == "Taxi Out Distance").unwrap();
+        let taxi_in = header().position(|h| h == "Taxi In Distance").unwrap();
+        let record = flight.to_record();
+        assert_eq!(Some(String::from("0.22")), record[taxi_out]);
+        assert_eq!(Some(String::from("0.06")), record[taxi_in]);
+    }
+
+    #[test]
+    fn test_log_threshold() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        // repositioning ~300m to another gate
+        let mut flight = Flight::new(&aircraft());
+        for (minutes, lat, lon) in [(0, 34.7170, 32.4800), (4, 34.7190, 32.4820)] {
+            flight.track.push(TrackPoint {
+                time: at(minutes),
+                position: LatLon::new(lat, lon),
+                heading: 0.0,
+                elevation_ft: 0.0,
+            });
+        }
+        flight.taxi_out = Some(at(0));
+        flight.shutdown = Some(at(5));
+
+        assert!(!LogThreshold::default().is_below(&flight));
+        let threshold = LogThreshold {
+            min_distance_nm: Some(1.0),
+            min_duration: Some(Duration::minutes(10)),
+        };
+        assert!(threshold.is_below(&flight));
+        let distance_only = LogThreshold {
+            min_distance_nm: Some(1.0),
+            min_duration: None,
+        };
+        assert!(distance_only.is_below(&flight));
+
+        // long enough in time, though not in distance
+        flight.shutdown = Some(at(15));
+        assert!(!threshold.is_below(&flight));
+        assert!(distance_only.is_below(&flight));
+
+        // and in distance
+        flight.shutdown = Some(at(5));
+        flight.track.push(TrackPoint {
+            time: at(5),
+            position: LatLon::new(34.875, 33.624722),
+            heading: 0.0,
+            elevation_ft: 0.0,
+        });
+        assert!(!threshold.is_below(&flight));
+    }
+
+    #[test]
+    fn test_tracker_engine_cycles() {
+        let mut tracker = tracker();
+        let engine_off = Aircraft {
+            engine_on: false,
+            ..aircraft()
+        };
+        let airborne = Aircraft {
+            on_ground: false,
+            ..aircraft()
+        };
+
+        tracker.update(&engine_off, None);
+        tracker.update(&aircraft(), None);
+        // shut down and restarted before taking off
+        tracker.update(&engine_off, None);
+        tracker.update(&engine_off, None);
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne, Some(lcph()));
+        tracker.update(&aircraft(), Some(lclk()));
+        tracker.update(&engine_off, Some(lclk()));
+
+        let flight = completed(tracker.update(&engine_off, Some(lclk())));
+        assert_eq!(2, flight.engine_cycles);
+        let cycles = header().position(|h| h == "Engine Cycles").unwrap();
+        assert_eq!(Some(String::from("2")), flight.to_record()[cycles]);
+    }
+
+    #[test]
+    fn test_tracker_events() {
+        let mut tracker = tracker();
+        let rolling = |ground_speed| Aircraft {
+            ground_speed,
+            ..aircraft()
+        };
+        let airborne_at = |agl_ft| Aircraft {
+            on_ground: false,
+            agl_ft,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&rolling(60.0), Some(lcph()));
+        assert!(matches!(
+            tracker.update(&rolling(10.0), Some(lcph())),
+            Some(FlightEvent::AbortedTakeoff)
+        ));
+        // taking off and landing outside of any airport
+        assert!(matches!(
+            tracker.update(&airborne_at(100.0), None),
+            Some(FlightEvent::Departed(None))
+        ));
+        tracker.update(&airborne_at(3000.0), None);
+        tracker.update(&airborne_at(150.0), Some(lclk()));
+        assert!(matches!(
+            tracker.update(&airborne_at(1000.0), None),
+            Some(FlightEvent::GoAround)
+        ));
+        assert!(matches!(
+            tracker.update(&aircraft(), None),
+            Some(FlightEvent::Arrived(None))
+        ));
+        assert!(matches!(
+            tracker.update(&airborne_at(100.0), None),
+            Some(FlightEvent::TouchAndGo)
+        ));
+        // nothing changes while cruising
+        assert!(tracker.update(&airborne_at(3000.0), None).is_none());
+    }
+
+    #[test]
+    fn test_tracker_go_around() {
+        let mut tracker = tracker();
+        let airborne_at = |agl_ft| Aircraft {
+            on_ground: false,
+            agl_ft,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        // the initial climb isn't a go around
+        tracker.update(&airborne_at(100.0), Some(lcph()));
+        tracker.update(&airborne_at(3000.0), None);
+        // low approach over an airport, then climbing away
+        tracker.update(&airborne_at(1000.0), None);
+        tracker.update(&airborne_at(150.0), Some(lclk()));
+        tracker.update(&airborne_at(1000.0), None);
+        // landing from the second approach
+        tracker.update(&airborne_at(150.0), Some(lclk()));
+        tracker.update(&aircraft(), Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Landed, flight.state);
+        assert_eq!(1, flight.go_arounds);
+    }
+
+    #[test]
+    fn test_tracker_low_flying_away_from_airports() {
+        let mut tracker = tracker();
+        let airborne_at = |agl_ft| Aircraft {
+            on_ground: false,
+            agl_ft,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&airborne_at(100.0), Some(lcph()));
+        tracker.update(&airborne_at(3000.0), None);
+        tracker.update(&airborne_at(150.0), None);
+        tracker.update(&airborne_at(1000.0), None);
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(0, flight.go_arounds);
+    }
+
+    #[test]
+    fn test_tracker_departure_and_arrival_course() {
+        let mut tracker = tracker();
+        let at = |position, on_ground| Aircraft {
+            position,
+            on_ground,
+            ground_speed: 140.0,
+            ..aircraft()
+        };
+        // rolling down runway 29 at Paphos
+        let rolling = lcph().position;
+        let rotating = rolling.destination(287.0, 70.0);
+        // and touching down on runway 22 at Larnaca
+        let short_final = lclk().position;
+        let touchdown = short_final.destination(222.0, 70.0);
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&at(rolling, true), Some(lcph()));
+        tracker.update(&at(rotating, false), Some(lcph()));
+        tracker.update(&at(short_final, false), Some(lclk()));
+        tracker.update(&at(touchdown, true), Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert!((flight.departure_course.unwrap() - 287.0).abs() < 0.1);
+        assert!((flight.arrival_course.unwrap() - 222.0).abs() < 0.1);
+        let departure = header().position(|h| h == "Departure Course").unwrap();
+        let arrival = header().position(|h| h == "Arrival Course").unwrap();
+        let record = flight.to_record();
+        assert_eq!(Some(String::from("287")), record[departure]);
+        assert_eq!(Some(String::from("222")), record[arrival]);
+    }
+
+    #[test]
+    fn test_tracker_rotation_and_touchdown_speed() {
+        let mut tracker = tracker();
+        let at = |ground_speed, agl_ft: f64| Aircraft {
+            ground_speed,
+            on_ground: agl_ft == 0.0,
+            agl_ft,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&at(60.0, 0.0), Some(lcph()));
+        tracker.update(&at(125.0, 0.0), Some(lcph()));
+        tracker.update(&at(138.0, 10.0), Some(lcph()));
+        tracker.update(&at(250.0, 5000.0), None);
+        tracker.update(&at(130.0, 50.0), Some(lclk()));
+        tracker.update(&at(121.5, 0.0), Some(lclk()));
+        tracker.update(&at(60.0, 0.0), Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(138.0), flight.rotation_speed_kt);
+        assert_eq!(Some(121.5), flight.touchdown_speed_kt);
+        let rotation = header().position(|h| h == "Rotation Speed").unwrap();
+        let touchdown = header().position(|h| h == "Touchdown Speed").unwrap();
+        let record = flight.to_record();
+        assert_eq!(Some(String::from("138")), record[rotation]);
+        assert_eq!(Some(String::from("122")), record[touchdown]);
+    }
+
+    #[test]
+    fn test_tracker_origin_and_shutdown_position() {
+        let mut tracker = tracker();
+        let gate = LatLon::new(34.7195, 32.4838);
+        let stand = LatLon::new(34.8769, 33.6297);
+        let at = |position, on_ground, engine_on| Aircraft {
+            position,
+            on_ground,
+            engine_on,
+            agl_ft: if on_ground { 0.0 } else { 1000.0 },
+            ..aircraft()
+        };
+
+        tracker.update(&at(gate, true, false), Some(lcph()));
+        tracker.update(&at(gate, true, true), Some(lcph()));
+        tracker.update(&at(lcph().position, false, true), Some(lcph()));
+        tracker.update(&at(lclk().position, true, true), Some(lclk()));
+        tracker.update(&at(stand, true, true), Some(lclk()));
+        assert_eq!(
+            None,
+            tracker.current_flight.as_ref().unwrap().shutdown_position
+        );
+        tracker.update(&at(stand, true, false), Some(lclk()));
+        let flight = completed(tracker.update(&at(stand, true, false), Some(lclk())));
+
+        assert_eq!(gate, flight.origin_position);
+        assert_eq!(Some(stand), flight.shutdown_position);
+        let origin = header().position(|h| h == "Origin Position").unwrap();
+        let shutdown = header().position(|h| h == "Shutdown Position").unwrap();
+        let record = flight.to_record();
+        assert_eq!(
+            Some(String::from("N34°43'10.20\" E032°29'01.68\"")),
+            record[origin]
+        );
+        assert_eq!(
+            Some(String::from("N34°52'36.84\" E033°37'46.92\"")),
+            record[shutdown]
+        );
+    }
+
+    #[test]
+    fn test_tracker_wheels_up_and_down() {
+        let mut tracker = tracker();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |minutes| start + Duration::minutes(minutes);
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 1000.0,
+            ..aircraft()
+        };
+
+        tracker.update_at(&aircraft(), None, at(0));
+        tracker.update_at(&aircraft(), None, at(5));
+        // no airport for the takeoff, the time is still recorded
+        tracker.update_at(&airborne, None, at(8));
+        tracker.update_at(&airborne, None, at(9));
+        tracker.update_at(&aircraft(), Some(lclk()), at(40));
+        tracker.update_at(&aircraft(), Some(lclk()), at(41));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(at(8)), flight.wheels_up);
+        assert_eq!(Some(at(40)), flight.wheels_down);
+        assert!(flight.departure.is_none());
+        assert_eq!(Some(at(40)), flight.arrival.as_ref().map(|a| a.1));
+    }
+
+    #[test]
+    fn test_tracker_wind_component() {
+        let mut tracker = tracker();
+        let airborne = |ground_speed, true_airspeed_kt| Aircraft {
+            on_ground: false,
+            agl_ft: 3000.0,
+            ground_speed,
+            true_airspeed_kt,
+            ..aircraft()
+        };
+        let taxiing = Aircraft {
+            ground_speed: 15.0,
+            true_airspeed_kt: 15.0,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), None);
+        tracker.update(&taxiing, None);
+        assert_eq!(
+            None,
+            tracker.current_flight.as_ref().unwrap().wind_component()
+        );
+        tracker.update(&airborne(140.0, 140.0), Some(lcph()));
+        // into a headwind, then turning out of it
+        tracker.update(&airborne(230.0, 250.0), None);
+        tracker.update(&airborne(240.0, 250.0), None);
+        tracker.update(&airborne(250.0, 250.0), None);
+        tracker.update(&taxiing, Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(-10.0), flight.wind_component());
+        let wind = header().position(|h| h == "Wind Component").unwrap();
+        assert_eq!(Some(String::from("-10.0")), flight.to_record()[wind]);
+    }
+
+    #[test]
+    fn test_tracker_holds() {
+        let mut tracker = tracker();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |seconds| start + Duration::seconds(seconds);
+        let airborne = |position, heading| Aircraft {
+            on_ground: false,
+            agl_ft: 3000.0,
+            position,
+            heading,
+            ..aircraft()
+        };
+
+        tracker.update_at(&aircraft(), None, at(0));
+        tracker.update_at(&airborne(lcph().position, 90.0), Some(lcph()), at(1));
+        // two laps of the same hold are a single hold
+        let samples = crate::hold::racetrack(lclk().position, 2);
+        for (i, (position, heading)) in samples.into_iter().enumerate() {
+            tracker.update_at(&airborne(position, heading), None, at(i as i64 + 2));
+        }
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(1, flight.holds.len());
+        assert!(flight.holds[0].distance(&lclk().position) < 100.0);
+        let holds = header().position(|h| h == "Holds").unwrap();
+        assert_eq!(Some(String::from("1")), flight.to_record()[holds]);
+    }
+
+    #[test]
+    fn test_tracker_cruise_flight_level() {
+        let mut tracker = tracker();
+        let airborne = |baro_altitude_ft| Aircraft {
+            on_ground: false,
+            agl_ft: 3000.0,
+            baro_altitude_ft,
+            elevation_ft: baro_altitude_ft + 250.0,
+            ..aircraft()
+        };
+
+        tracker.update(&aircraft(), Some(lcph()));
+        tracker.update(&airborne(1500.0), None);
+        assert_eq!(
+            None,
+            tracker
+                .current_flight
+                .as_ref()
+                .unwrap()
+                .cruise_flight_level()
+        );
+        tracker.update(&airborne(12000.0), None);
+        tracker.update(&airborne(34980.0), None);
+        tracker.update(&airborne(20000.0), None);
+        tracker.update(&aircraft(), Some(lclk()));
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(34980.0), flight.max_baro_altitude_ft);
+        assert_eq!(Some(350), flight.cruise_flight_level());
+    }
+
+    #[test]
+    fn test_tracker_parked_before_startup() {
+        let mut tracker = tracker();
+        let parked = Aircraft {
+            engine_on: false,
+            parking_brake: true,
+            ..aircraft()
+        };
+        let holding = Aircraft {
+            engine_on: false,
+            position: LatLon::new(34.72, 32.49),
+            ..aircraft()
+        };
+        let airborne = Aircraft {
+            on_ground: false,
+            agl_ft: 100.0,
+            ..aircraft()
+        };
+
+        tracker.update(&parked, Some(lcph()));
+        // stopped without the parking brake isn't parked
+        tracker.update(&holding, Some(lcph()));
+        tracker.update(&aircraft(), Some(lcph()));
+        // lifting off past the end of the airport's bounds
+        tracker.update(&airborne, None);
+
+        let flight = tracker.current_flight.as_ref().unwrap();
+        assert_eq!(Some(aircraft().position), flight.parked_at);
+        assert_eq!("LCPH", flight.origin.as_ref().unwrap().ident);
+        assert_eq!("LCPH", flight.departure.as_ref().unwrap().0.ident);
+        let parking = header().position(|h| h == "Parking Position").unwrap();
+        assert_eq!(
+            Some(String::from("N34°43'04.00\" E032°29'08.00\"")),
+            flight.to_record()[parking]
+        );
+    }
+}
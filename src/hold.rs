@@ -0,0 +1,152 @@
+use chrono::{DateTime, Duration, Utc};
+use geo::{math::relative_bearing, LatLon};
+use std::collections::VecDeque;
+
+/// The longest a lap of a hold can take (in minutes), from the fix back to the
+/// fix. A standard hold takes 4 minutes, this leaves room for longer legs.
+pub const HOLD_MAX_LAP_MIN: i64 = 8;
+
+/// How close (in meters) the aircraft has to come back to where it was a lap
+/// ago for it to be holding, leaving room for the wind.
+pub const HOLD_RADIUS_M: f64 = 2000.0;
+
+/// What counts as flying a hold, see `HoldDetector`.
+#[derive(Clone, Copy, Debug)]
+pub struct HoldConfig {
+    pub max_lap: Duration,
+    pub radius_m: f64,
+}
+
+impl Default for HoldConfig {
+    fn default() -> Self {
+        Self {
+            max_lap: Duration::minutes(HOLD_MAX_LAP_MIN),
+            radius_m: HOLD_RADIUS_M,
+        }
+    }
+}
+
+/// Looks for holds while airborne: turning all the way round within
+/// `max_lap`, and coming back to within `radius_m` of where the aircraft was
+/// a full turn earlier, like a lap of a racetrack pattern.
+///
+/// It's a heuristic, anything else flown in a closed loop looks just the same,
+/// e.g. an orbit, or flying a circuit and going around without touching down.
+/// Those are taken for holds too.
+#[derive(Clone, Debug, Default)]
+pub struct HoldDetector {
+    // the samples within the last lap, with how far the aircraft had turned
+    // by then
+    samples: VecDeque<(DateTime<Utc>, LatLon, f64)>,
+    last_heading: Option<f64>,
+    // total heading change in degrees, positive turning right
+    turned_deg: f64,
+}
+
+impl HoldDetector {
+    /// Feed the next airborne sample, returning where the aircraft was a lap
+    /// earlier when it has just flown a lap of a hold.
+    pub fn update(
+        &mut self,
+        config: &HoldConfig,
+        time: DateTime<Utc>,
+        position: LatLon,
+        heading: f64,
+    ) -> Option<LatLon> {
+        if let Some(last_heading) = self.last_heading.replace(heading) {
+            self.turned_deg += relative_bearing(last_heading, heading);
+        }
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _, _)| time - *t > config.max_lap)
+        {
+            self.samples.pop_front();
+        }
+        let lap_ago = self
+            .samples
+            .iter()
+            .rposition(|(_, _, turned)| (self.turned_deg - turned).abs() >= 360.0);
+        self.samples.push_back((time, position, self.turned_deg));
+
+        let lap_ago =
+            lap_ago.filter(|i| position.distance(&self.samples[*i].1) <= config.radius_m)?;
+        let fix = self.samples[lap_ago].1;
+        // the next lap has to be flown from after where this one started, so
+        // it isn't found again with the next sample
+        self.samples.drain(..=lap_ago);
+        Some(fix)
+    }
+}
+
+/// Samples a second apart flying `laps` of a hold at 180kt with one minute
+/// legs and right turns, after a two minute leg flying east to the fix at
+/// `fix`.
+#[cfg(test)]
+pub fn racetrack(fix: LatLon, laps: usize) -> Vec<(LatLon, f64)> {
+    use geo::math::Vec2;
+
+    // 180kt in meters per second, at a standard rate turn of 3 degrees a second
+    let speed = 92.6;
+    let mut turns = vec![0.0; 120];
+    for _ in 0..laps {
+        for turn in [3.0, 0.0, 3.0, 0.0] {
+            turns.extend([turn; 60]);
+        }
+    }
+    let mut xy = Vec2::new(-120.0 * speed, 0.0);
+    let mut heading: f64 = 90.0;
+    turns
+        .into_iter()
+        .map(|turn| {
+            heading = (heading + turn) % 360.0;
+            xy.x += speed * heading.to_radians().sin();
+            xy.y += speed * heading.to_radians().cos();
+            (LatLon::from_local_xy(&fix, &xy), heading)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn detect(samples: &[(LatLon, f64)]) -> Vec<LatLon> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let mut detector = HoldDetector::default();
+        samples
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (position, heading))| {
+                let time = start + Duration::seconds(i as i64);
+                detector.update(&HoldConfig::default(), time, *position, *heading)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hold() {
+        let fix = LatLon::new(34.875, 33.624722);
+        let laps = detect(&racetrack(fix, 2));
+        assert_eq!(2, laps.len());
+        for lap in laps {
+            assert!(lap.distance(&fix) < 100.0);
+        }
+    }
+
+    #[test]
+    fn test_no_hold_without_coming_back() {
+        let fix = LatLon::new(34.875, 33.624722);
+        // just the leg to the fix
+        assert!(detect(&racetrack(fix, 0)).is_empty());
+        // a wide turn all the way round, taking 12 minutes
+        let wide_turn = (0..=720)
+            .map(|i| {
+                let bearing = i as f64 / 2.0;
+                (fix.destination(bearing, 10_000.0), (bearing + 90.0) % 360.0)
+            })
+            .collect::<Vec<_>>();
+        assert!(detect(&wide_turn).is_empty());
+    }
+}
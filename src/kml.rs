@@ -0,0 +1,206 @@
+use crate::aircraft::FT_TO_M;
+use crate::flight::Flight;
+use crate::navdata::Airport;
+use chrono::Utc;
+use std::{
+    error::Error,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// KML timestamps are XML Schema dateTimes.
+const KML_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Escape the characters that aren't allowed in XML text.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn airport_placemark(kml: &mut String, label: &str, airport: &Airport) {
+    // careful! KML coordinates are longitude first
+    let _ = writeln!(
+        kml,
+        "    <Placemark>
+      <name>{label}: {}</name>
+      <Point>
+        <coordinates>{},{},0</coordinates>
+      </Point>
+    </Placemark>",
+        escape(&airport.ident),
+        airport.position.longitude(),
+        airport.position.latitude(),
+    );
+}
+
+/// Render a flight as a KML document, with the track as a line string and
+/// a placemark for each of the departure and arrival airports.
+///
+/// The track is drawn at the altitude above sea level of each point. With a
+/// `tolerance_m` the track is simplified to within that many meters, see
+/// `Track::simplified`.
+pub fn to_kml(flight: &Flight, tolerance_m: Option<f64>) -> String {
+    let mut kml = String::new();
+    let _ = writeln!(
+        kml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<kml xmlns=\"http://www.opengis.net/kml/2.2\">
+  <Document>
+    <name>{}</name>",
+        escape(&flight.aircraft.title)
+    );
+
+    if let Some((airport, _)) = &flight.departure {
+        airport_placemark(&mut kml, "Departure", airport);
+    }
+    if let Some((airport, _)) = &flight.arrival {
+        airport_placemark(&mut kml, "Arrival", airport);
+    }
+
+    let _ = writeln!(kml, "    <Placemark>\n      <name>Track</name>");
+    let points = match tolerance_m {
+        Some(tolerance_m) => flight.track.simplified(tolerance_m),
+        None => flight.track.points().iter().collect(),
+    };
+    if let (Some(first), Some(last)) = (points.first(), points.last()) {
+        let _ = writeln!(
+            kml,
+            "      <TimeSpan>
+        <begin>{}</begin>
+        <end>{}</end>
+      </TimeSpan>",
+            first.time.format(KML_DATE_FORMAT),
+            last.time.format(KML_DATE_FORMAT),
+        );
+    }
+    let _ = writeln!(
+        kml,
+        "      <LineString>
+        <tessellate>1</tessellate>
+        <altitudeMode>absolute</altitudeMode>
+        <coordinates>"
+    );
+    for point in points {
+        let _ = writeln!(
+            kml,
+            "          {},{},{:.0}",
+            point.position.longitude(),
+            point.position.latitude(),
+            point.elevation_ft * FT_TO_M
+        );
+    }
+    let _ = writeln!(
+        kml,
+        "        </coordinates>
+      </LineString>
+    </Placemark>
+  </Document>
+</kml>"
+    );
+    kml
+}
+
+/// Write the flight to a new KML file in `dir`, named after its taxi time.
+pub fn export(
+    flight: &Flight,
+    dir: &Path,
+    tolerance_m: Option<f64>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let started = flight.taxi_out.unwrap_or_else(Utc::now);
+    let path = dir.join(format!("{}.kml", started.format("%Y%m%d-%H%M%S")));
+    fs::write(&path, to_kml(flight, tolerance_m))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::Aircraft;
+    use crate::track::TrackPoint;
+    use geo::LatLon;
+
+    fn airport(ident: &str, lat: f64, lon: f64) -> Airport {
+        Airport {
+            id: 0,
+            ident: String::from(ident),
+            position: LatLon::new(lat, lon),
+        }
+    }
+
+    fn flight() -> Flight {
+        let aircraft = Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position: LatLon::new(34.717778, 32.485556),
+            engine_on: true,
+            on_ground: true,
+            ..Aircraft::default()
+        };
+        let mut flight = Flight::new(&aircraft);
+        for position in [LatLon::new(34.7, 32.4), LatLon::new(34.8, 33.5)] {
+            flight.track.push(TrackPoint {
+                time: Utc::now(),
+                position,
+                heading: 0.0,
+                elevation_ft: 0.0,
+            });
+        }
+        flight.depart(&airport("LCPH", 34.717778, 32.485556), &Utc::now());
+        flight.arrive(&airport("LCLK", 34.875, 33.624722), &Utc::now());
+        flight
+    }
+
+    #[test]
+    fn test_to_kml_coordinates_are_lon_lat_alt() {
+        let kml = to_kml(&flight(), None);
+        assert!(kml.contains("          32.4,34.7,0\n          33.5,34.8,0\n"));
+        assert!(!kml.contains("34.7,32.4"));
+    }
+
+    #[test]
+    fn test_to_kml_altitude() {
+        let mut flight = flight();
+        flight.track.push(TrackPoint {
+            time: Utc::now(),
+            position: LatLon::new(34.9, 34.6),
+            heading: 0.0,
+            elevation_ft: 10000.0,
+        });
+        let kml = to_kml(&flight, None);
+        assert!(kml.contains("<altitudeMode>absolute</altitudeMode>"));
+        assert!(kml.contains("          34.6,34.9,3048\n"));
+    }
+
+    #[test]
+    fn test_to_kml_airport_placemarks() {
+        let kml = to_kml(&flight(), None);
+        assert!(kml.contains("<name>Departure: LCPH</name>"));
+        assert!(kml.contains("<coordinates>32.485556,34.717778,0</coordinates>"));
+        assert!(kml.contains("<name>Arrival: LCLK</name>"));
+        assert!(kml.contains("<coordinates>33.624722,34.875,0</coordinates>"));
+    }
+
+    #[test]
+    fn test_to_kml_simplified() {
+        let mut flight = flight();
+        // back on the line between the other two
+        flight.track.push(TrackPoint {
+            time: Utc::now(),
+            position: LatLon::new(34.9, 34.6),
+            heading: 0.0,
+            elevation_ft: 0.0,
+        });
+        assert!(to_kml(&flight, None).contains("          33.5,34.8,0\n"));
+        let kml = to_kml(&flight, Some(1000.0));
+        assert!(kml.contains("          32.4,34.7,0\n          34.6,34.9,0\n"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("A &amp; B &lt;C&gt;", escape("A & B <C>"));
+    }
+}
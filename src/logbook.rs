@@ -0,0 +1,849 @@
+use crate::aircraft::NM_TO_M;
+use crate::flight::Flight;
+use crate::timezone::local_time_to_string;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::{info, warn};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Format `dt` with `format`, a time format checked by `parse_time_format`.
+pub fn date_to_string(dt: &DateTime<Utc>, format: &str) -> String {
+    dt.format(format).to_string()
+}
+
+/// Format `dt` with a chrono format string, failing on an invalid one where
+/// `format().to_string()` would panic.
+fn format_date(dt: &DateTime<Utc>, format: &str) -> Result<String, String> {
+    use std::fmt::Write;
+    let mut s = String::new();
+    write!(s, "{}", dt.format(format)).map_err(|_| format!("invalid time format: {format}"))?;
+    Ok(s)
+}
+
+/// Parse a chrono format string to log the times in instead of `DATE_FORMAT`,
+/// checked by formatting a sample date with it and reading that back, so that
+/// the logbook can still be read.
+pub fn parse_time_format(format: &str) -> Result<String, String> {
+    let sample = format_date(&Utc::now(), format)?;
+    NaiveDateTime::parse_from_str(&sample, format).map_err(|_| {
+        format!("invalid time format: {format}, it needs the date and time to be read back")
+    })?;
+    Ok(format.to_string())
+}
+
+/// Format a duration as hours and minutes, e.g. `1:05`.
+pub fn duration_to_string(duration: &Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Format a distance in meters as nautical miles.
+fn distance_to_string(meters: f64) -> String {
+    format!("{:.2}", meters / NM_TO_M)
+}
+
+/// The version of the logbook, and of the protocol the sim's data was
+/// received over if it has one, e.g. `0.1.0 (protocol 1)`.
+fn version_to_string(protocol_version: Option<u32>) -> String {
+    let version = env!("CARGO_PKG_VERSION");
+    match protocol_version {
+        Some(protocol) => format!("{version} (protocol {protocol})"),
+        None => version.to_string(),
+    }
+}
+
+/// Format a course in whole degrees with 3 digits, north as `360` the way
+/// runways are numbered, e.g. `095`.
+fn course_to_string(course: f64) -> String {
+    match course.round() as u32 % 360 {
+        0 => String::from("360"),
+        degrees => format!("{degrees:03}"),
+    }
+}
+
+/// Format a flight level with 3 digits, e.g. `FL050`.
+fn flight_level_to_string(flight_level: u32) -> String {
+    format!("FL{flight_level:03}")
+}
+
+/// A column of the logbook, its header and how to get it from a flight, with
+/// its times in the given format.
+pub type Column = (&'static str, fn(&Flight, &str) -> Option<String>);
+
+/// The columns of the logbook, in order. New columns go at the end, existing
+/// logbooks are extended with them when they're opened, see `open`.
+pub const COLUMNS: [Column; 38] = [
+    ("Aircraft Name", |f, _| Some(f.aircraft.title.clone())),
+    ("Aircraft ICAO", |f, _| Some(f.aircraft.icao.clone())),
+    ("Registration", |f, _| Some(f.aircraft.registration.clone())),
+    ("Taxi Time", |f, format| {
+        f.taxi_out.map(|dt| date_to_string(&dt, format))
+    }),
+    ("Departure ICAO", |f, _| {
+        f.departure
+            .as_ref()
+            .map(|(airport, _)| airport.ident.clone())
+    }),
+    ("Departure Time", |f, format| {
+        f.departure
+            .as_ref()
+            .map(|(_, dt)| date_to_string(dt, format))
+    }),
+    ("Arrival ICAO", |f, _| {
+        f.arrival.as_ref().map(|(airport, _)| airport.ident.clone())
+    }),
+    ("Arrival Time", |f, format| {
+        f.arrival.as_ref().map(|(_, dt)| date_to_string(dt, format))
+    }),
+    ("Shutdown Time", |f, format| {
+        f.shutdown.map(|dt| date_to_string(&dt, format))
+    }),
+    ("Block Time", |f, _| {
+        f.block_time().map(|d| duration_to_string(&d))
+    }),
+    ("Air Time", |f, _| {
+        f.air_time().map(|d| duration_to_string(&d))
+    }),
+    ("Category", |f, _| f.category.map(|c| c.to_string())),
+    ("Aborted Takeoffs", |f, _| {
+        Some(f.aborted_takeoffs.to_string())
+    }),
+    ("Departure Local Time", |f, _| {
+        f.departure
+            .as_ref()
+            .map(|(airport, dt)| local_time_to_string(dt, airport.position))
+    }),
+    ("Arrival Local Time", |f, _| {
+        f.arrival
+            .as_ref()
+            .map(|(airport, dt)| local_time_to_string(dt, airport.position))
+    }),
+    ("Hobbs Time", |f, _| {
+        (f.hobbs_time > Duration::zero()).then(|| duration_to_string(&f.hobbs_time))
+    }),
+    ("Taxi Out Distance", |f, _| {
+        f.taxi_out_distance().map(distance_to_string)
+    }),
+    ("Taxi In Distance", |f, _| {
+        f.taxi_in_distance().map(distance_to_string)
+    }),
+    ("Go Arounds", |f, _| Some(f.go_arounds.to_string())),
+    ("Wheels Up", |f, format| {
+        f.wheels_up.map(|dt| date_to_string(&dt, format))
+    }),
+    ("Wheels Down", |f, format| {
+        f.wheels_down.map(|dt| date_to_string(&dt, format))
+    }),
+    ("Wind Component", |f, _| {
+        f.wind_component().map(|kt| format!("{kt:.1}"))
+    }),
+    ("Parking Position", |f, _| {
+        f.parked_at.map(|p| p.to_dms_string())
+    }),
+    ("Touch and Goes", |f, _| f.touch_and_go_summary()),
+    ("Version", |f, _| {
+        Some(version_to_string(f.aircraft.protocol_version))
+    }),
+    ("Cruise FL", |f, _| {
+        f.cruise_flight_level().map(flight_level_to_string)
+    }),
+    ("Top of Climb", |f, format| {
+        f.top_of_climb().map(|dt| date_to_string(&dt, format))
+    }),
+    ("Top of Descent", |f, format| {
+        f.top_of_descent().map(|dt| date_to_string(&dt, format))
+    }),
+    ("Holds", |f, _| Some(f.holds.len().to_string())),
+    ("Engine Cycles", |f, _| Some(f.engine_cycles.to_string())),
+    ("Departure Course", |f, _| {
+        f.departure_course.map(course_to_string)
+    }),
+    ("Arrival Course", |f, _| {
+        f.arrival_course.map(course_to_string)
+    }),
+    ("Great Circle Distance", |f, _| {
+        f.route_distances()
+            .map(|(great_circle, _)| distance_to_string(great_circle))
+    }),
+    ("Rhumb Line Distance", |f, _| {
+        f.route_distances()
+            .map(|(_, rhumb_line)| distance_to_string(rhumb_line))
+    }),
+    ("Origin Position", |f, _| {
+        Some(f.origin_position.to_dms_string())
+    }),
+    ("Shutdown Position", |f, _| {
+        f.shutdown_position.map(|p| p.to_dms_string())
+    }),
+    ("Rotation Speed", |f, _| {
+        f.rotation_speed_kt.map(|kt| format!("{kt:.0}"))
+    }),
+    ("Touchdown Speed", |f, _| {
+        f.touchdown_speed_kt.map(|kt| format!("{kt:.0}"))
+    }),
+];
+
+/// The logbook's header row.
+pub fn header() -> impl Iterator<Item = &'static str> {
+    COLUMNS.iter().map(|(header, _)| *header)
+}
+
+/// Parse the logbook's field delimiter, a single ASCII character, e.g. `;`,
+/// other than the quote and line breaks that CSV already gives a meaning.
+pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [delimiter] if delimiter.is_ascii() && !b"\"\r\n".contains(delimiter) => Ok(*delimiter),
+        _ => Err(format!(
+            "invalid delimiter: {s:?}, expected a single ASCII character other than a quote or a line break"
+        )),
+    }
+}
+
+fn writer(f: &File, delimiter: u8) -> csv::Writer<&File> {
+    csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(f)
+}
+
+/// Fail if the existing logbook's header is separated with another delimiter,
+/// rather than appending rows that don't line up with it.
+fn check_delimiter(path: &Path, delimiter: u8) -> Result<(), Box<dyn Error>> {
+    let mut existing = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut existing)?;
+    let first_column = header().next().unwrap_or_default();
+    match existing.strip_prefix(first_column).map(str::as_bytes) {
+        Some([found, ..]) if *found != delimiter => Err(format!(
+            "{} is separated with {:?}, pass --delimiter {:?} to log to it or log to another file instead.",
+            path.display(),
+            *found as char,
+            *found as char
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Add the columns added to the logbook since it was started to its header,
+/// with the existing rows left empty in them, so that new rows line up with
+/// the header.
+fn add_missing_columns(path: &Path, delimiter: u8) -> Result<(), Box<dyn Error>> {
+    let rows = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?
+        .into_records()
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(existing) = rows.first() else {
+        return Ok(());
+    };
+    if existing.len() >= COLUMNS.len() {
+        return Ok(());
+    }
+    if !existing.iter().zip(header()).all(|(a, b)| a == b) {
+        return Err(format!(
+            "{} doesn't have the logbook's columns, log to another file instead.",
+            path.display()
+        )
+        .into());
+    }
+    info!(
+        "Adding {} new column(s) to {}",
+        COLUMNS.len() - existing.len(),
+        path.display()
+    );
+    // written alongside and moved over the logbook once complete, so that a
+    // failure part way through leaves the flights already logged as they were
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut csv = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(&temp)?;
+    csv.write_record(header())?;
+    for row in &rows[1..] {
+        let padding = COLUMNS.len().saturating_sub(row.len());
+        csv.write_record(row.iter().chain(std::iter::repeat_n("", padding)))?;
+    }
+    csv.flush()?;
+    csv.get_ref().sync_all()?;
+    drop(csv);
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Fail if the last flight in the existing logbook has its taxi time in
+/// another format than `time_format`, rather than mixing formats in the file.
+fn check_time_format(path: &Path, delimiter: u8, time_format: &str) -> Result<(), Box<dyn Error>> {
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)?;
+    let Some(column) = csv.headers()?.iter().position(|h| h == "Taxi Time") else {
+        return Ok(());
+    };
+    let Some(last) = csv.into_records().filter_map(Result::ok).last() else {
+        return Ok(());
+    };
+    match last.get(column).filter(|time| !time.is_empty()) {
+        Some(time) if NaiveDateTime::parse_from_str(time, time_format).is_err() => Err(format!(
+            "{} has its times logged like {time}, not with --time-format {time_format}, log to another file instead.",
+            path.display()
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Open the logbook for appending, adding the header to a new one and the
+/// columns added since to an existing one.
+fn open(path: &Path, delimiter: u8, time_format: &str) -> Result<File, Box<dyn Error>> {
+    if std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() > 0) {
+        check_delimiter(path, delimiter)?;
+        add_missing_columns(path, delimiter)?;
+        check_time_format(path, delimiter, time_format)?;
+    }
+    let f = File::options().create(true).append(true).open(path)?;
+    if f.metadata()?.len() == 0 {
+        writer(&f, delimiter).write_record(header())?;
+    }
+    Ok(f)
+}
+
+/// Where to write the flights that couldn't be logged to `path` when exiting,
+/// e.g. `logbook-20240101104500.csv` next to `logbook.csv`.
+fn fallback_path(path: &Path, now: &DateTime<Utc>) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}-{}.csv", now.format("%Y%m%d%H%M%S")))
+}
+
+/// How often the logbook starts a new file, named after the day or month
+/// it's for, e.g. `logbook-2024-01.csv`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    Daily,
+    Monthly,
+}
+
+impl Rotation {
+    /// The file to log to at `now`, next to `path`.
+    fn path(&self, path: &Path, now: &DateTime<Utc>) -> PathBuf {
+        let period = match self {
+            Rotation::Daily => now.format("%Y-%m-%d"),
+            Rotation::Monthly => now.format("%Y-%m"),
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{stem}-{period}.csv"))
+    }
+}
+
+impl std::str::FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(Rotation::Daily),
+            "monthly" => Ok(Rotation::Monthly),
+            _ => Err(format!(
+                "invalid rotation: {s}, valid options: daily, monthly"
+            )),
+        }
+    }
+}
+
+fn write_record(f: &File, record: &[String], delimiter: u8) -> Result<(), Box<dyn Error>> {
+    let mut csv = writer(f, delimiter);
+    csv.write_record(record)?;
+    csv.flush()?;
+    Ok(())
+}
+
+/// The CSV logbook.
+///
+/// When the file can't be written to, e.g. on Windows while it's open in
+/// Excel, flights are kept in memory and retried with the next flight. Any
+/// still left when exiting are written to a fallback file instead.
+///
+/// A rotating logbook switches to a new file for each day or month, checked
+/// as each flight is logged.
+pub struct Logbook {
+    path: PathBuf,
+    delimiter: u8,
+    time_format: String,
+    file: Option<File>,
+    // how it rotates, and the path the rotated files are named after
+    rotation: Option<(Rotation, PathBuf)>,
+    // records that couldn't be written yet, oldest first
+    pending: Vec<Vec<String>>,
+}
+
+impl Logbook {
+    /// Log to `path`, separating the fields with `delimiter`, e.g. `;` for
+    /// spreadsheets in locales using a decimal comma, with the times in
+    /// `time_format`, e.g. `DATE_FORMAT`.
+    pub fn new(path: &Path, delimiter: u8, time_format: &str) -> Self {
+        let file = match open(path, delimiter, time_format) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                warn!(
+                    "couldn't open {}: {e}, flights will be kept until it can be written to.",
+                    path.display()
+                );
+                None
+            }
+        };
+        Logbook {
+            path: path.to_path_buf(),
+            delimiter,
+            time_format: time_format.to_string(),
+            file,
+            rotation: None,
+            pending: vec![],
+        }
+    }
+
+    /// Log to a new file next to `path` every day or month, starting with
+    /// the current one, e.g. `logbook-2024-01.csv`.
+    pub fn rotating(path: &Path, delimiter: u8, time_format: &str, rotation: Rotation) -> Self {
+        let mut logbook = Self::new(&rotation.path(path, &Utc::now()), delimiter, time_format);
+        logbook.rotation = Some((rotation, path.to_path_buf()));
+        logbook
+    }
+
+    /// Switch to the file for `now` if it's moved on to another day or
+    /// month, after writing what's pending to the current one. The new file
+    /// is started with the header.
+    pub fn rotate(&mut self, now: &DateTime<Utc>) {
+        let Some((rotation, path)) = &self.rotation else {
+            return;
+        };
+        let path = rotation.path(path, now);
+        if path == self.path {
+            return;
+        }
+        // anything that still can't be written goes to the new file instead
+        let _ = self.write_pending();
+        if let Some(file) = self.file.take() {
+            if let Err(e) = file.sync_data() {
+                warn!("couldn't sync {}: {e}", self.path.display());
+            }
+        }
+        info!("Rotating the logbook to {}", path.display());
+        self.path = path;
+    }
+
+    pub fn log(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+        self.rotate(&flight.shutdown.unwrap_or_else(Utc::now));
+        // change None to ""
+        let record = flight
+            .to_record_with_time_format(&self.time_format)
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect();
+        self.pending.push(record);
+        if let Err(e) = self.write_pending() {
+            warn!(
+                "couldn't write to {}: {e}, is it open in another program? Keeping {} flight(s) to retry with the next one.",
+                self.path.display(),
+                self.pending.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the pending records, reopening the file if it couldn't be
+    /// written to before.
+    fn write_pending(&mut self) -> Result<(), Box<dyn Error>> {
+        // dropped on failure, so that it's reopened on the next attempt
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => open(&self.path, self.delimiter, &self.time_format)?,
+        };
+        while let Some(record) = self.pending.first() {
+            write_record(&file, record, self.delimiter)?;
+            self.pending.remove(0);
+        }
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Make sure everything logged so far is written to the file, or to a
+    /// fallback file if the logbook still can't be written to.
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Err(e) = self.write_pending() {
+            let fallback = fallback_path(&self.path, &Utc::now());
+            let file = open(&fallback, self.delimiter, &self.time_format)?;
+            for record in &self.pending {
+                write_record(&file, record, self.delimiter)?;
+            }
+            file.sync_data()?;
+            warn!(
+                "couldn't write to {}: {e}, wrote {} flight(s) to {} instead.",
+                self.path.display(),
+                self.pending.len(),
+                fallback.display()
+            );
+            self.pending.clear();
+        }
+        if let Some(file) = &self.file {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::Aircraft;
+    use crate::navdata::Airport;
+    use chrono::TimeZone;
+    use geo::LatLon;
+
+    #[test]
+    fn test_format_date() {
+        let dt = Utc.with_ymd_and_hms(2024, 1, 1, 10, 45, 0).unwrap();
+        assert_eq!(
+            Ok(String::from("2024-01-01 10:45:00")),
+            format_date(&dt, DATE_FORMAT)
+        );
+        assert_eq!(
+            Ok(String::from("2024-01-01T10:45:00Z")),
+            format_date(&dt, "%Y-%m-%dT%H:%M:%SZ")
+        );
+        assert_eq!(
+            Ok(String::from("01/01/2024 10:45")),
+            format_date(&dt, "%d/%m/%Y %H:%M")
+        );
+        assert_eq!(
+            Err(String::from("invalid time format: %Y-%Q")),
+            format_date(&dt, "%Y-%Q")
+        );
+    }
+
+    #[test]
+    fn test_parse_time_format() {
+        assert_eq!(
+            Ok(String::from("%Y-%m-%dT%H:%M:%SZ")),
+            parse_time_format("%Y-%m-%dT%H:%M:%SZ")
+        );
+        assert!(parse_time_format("%Y-%Q").is_err());
+        // can't be read back without the time
+        assert!(parse_time_format("%Y-%m-%d").is_err());
+    }
+
+    #[test]
+    fn test_open_with_another_time_format() {
+        let dir = std::env::temp_dir().join(format!("logbook-time-format-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logbook.csv");
+        let mut flight = Flight::new(&Aircraft::default());
+        flight.taxi_out = Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap());
+        let mut logbook = Logbook::new(&path, b',', DATE_FORMAT);
+        logbook.log(&flight).unwrap();
+        logbook.flush().unwrap();
+        let existing = std::fs::read_to_string(&path).unwrap();
+
+        assert!(open(&path, b',', "%Y-%m-%dT%H:%M:%SZ").is_err());
+        assert_eq!(existing, std::fs::read_to_string(&path).unwrap());
+        assert!(open(&path, b',', DATE_FORMAT).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_duration_to_string() {
+        assert_eq!("0:30", duration_to_string(&Duration::minutes(30)));
+        assert_eq!("1:05", duration_to_string(&Duration::minutes(65)));
+        assert_eq!("12:00", duration_to_string(&Duration::hours(12)));
+    }
+
+    #[test]
+    fn test_course_to_string() {
+        assert_eq!("095", course_to_string(94.6));
+        assert_eq!("287", course_to_string(287.0));
+        assert_eq!("360", course_to_string(359.7));
+        assert_eq!("360", course_to_string(0.2));
+    }
+
+    #[test]
+    fn test_log_adds_missing_columns() {
+        let dir = std::env::temp_dir().join(format!("logbook-columns-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logbook.csv");
+        std::fs::write(
+            &path,
+            "Aircraft Name,Aircraft ICAO,Registration\nCessna 172,C172,N172SP\n",
+        )
+        .unwrap();
+
+        let mut logbook = Logbook::new(&path, b',', DATE_FORMAT);
+        logbook.log(&Flight::new(&Aircraft::default())).unwrap();
+        logbook.flush().unwrap();
+
+        let rows = csv::Reader::from_path(&path)
+            .unwrap()
+            .into_records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(2, rows.len());
+        assert!(rows.iter().all(|row| row.len() == COLUMNS.len()));
+        assert_eq!(Some("N172SP"), rows[0].get(2));
+        assert_eq!(Some(""), rows[0].get(3));
+        assert!(!dir.join("logbook.csv.tmp").exists());
+
+        // a logbook that isn't ours is left alone
+        std::fs::write(&path, "Date,Route\n").unwrap();
+        assert!(open(&path, b',', DATE_FORMAT).is_err());
+        assert_eq!("Date,Route\n", std::fs::read_to_string(&path).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_positions() {
+        let origin = header().position(|h| h == "Origin Position").unwrap();
+        let shutdown = header().position(|h| h == "Shutdown Position").unwrap();
+        let mut flight = Flight::new(&Aircraft {
+            position: LatLon::new(34.717778, 32.485556),
+            ..Aircraft::default()
+        });
+        assert_eq!(None, flight.to_record()[shutdown]);
+        flight.shutdown_position = Some(LatLon::new(34.875, 33.624722));
+        let record = flight.to_record();
+        assert_eq!(
+            Some(String::from("N34°43'04.00\" E032°29'08.00\"")),
+            record[origin]
+        );
+        assert_eq!(
+            Some(String::from("N34°52'30.00\" E033°37'29.00\"")),
+            record[shutdown]
+        );
+    }
+
+    #[test]
+    fn test_record_version() {
+        let version = header().position(|h| h == "Version").unwrap();
+        let mut flight = Flight::new(&Aircraft::default());
+        assert_eq!(
+            Some(env!("CARGO_PKG_VERSION").to_string()),
+            flight.to_record()[version]
+        );
+        flight.aircraft.protocol_version = Some(1);
+        assert_eq!(
+            Some(format!("{} (protocol 1)", env!("CARGO_PKG_VERSION"))),
+            flight.to_record()[version]
+        );
+    }
+
+    #[test]
+    fn test_record_hobbs_time() {
+        let hobbs_time = header().position(|h| h == "Hobbs Time").unwrap();
+        let mut flight = Flight::new(&Aircraft::default());
+        assert_eq!(None, flight.to_record()[hobbs_time]);
+        flight.hobbs_time = Duration::minutes(65);
+        assert_eq!(Some(String::from("1:05")), flight.to_record()[hobbs_time]);
+    }
+
+    #[test]
+    fn test_record_cruise_flight_level() {
+        let cruise_fl = header().position(|h| h == "Cruise FL").unwrap();
+        let mut flight = Flight::new(&Aircraft::default());
+        assert_eq!(None, flight.to_record()[cruise_fl]);
+        flight.max_baro_altitude_ft = Some(4520.0);
+        assert_eq!(Some(String::from("FL045")), flight.to_record()[cruise_fl]);
+        flight.max_baro_altitude_ft = Some(37010.0);
+        assert_eq!(Some(String::from("FL370")), flight.to_record()[cruise_fl]);
+    }
+
+    #[test]
+    fn test_record_route_distances() {
+        let great_circle = header().position(|h| h == "Great Circle Distance").unwrap();
+        let rhumb_line = header().position(|h| h == "Rhumb Line Distance").unwrap();
+        let airport = |ident: &str, position| Airport {
+            id: 0,
+            ident: ident.to_string(),
+            position,
+        };
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let mut flight = Flight::new(&Aircraft::default());
+        flight.departure = Some((airport("KJFK", LatLon::new(40.639722, -73.778889)), time));
+        assert_eq!(None, flight.to_record()[great_circle]);
+        assert_eq!(None, flight.to_record()[rhumb_line]);
+
+        flight.arrival = Some((airport("EGLL", LatLon::new(51.4775, -0.461389)), time));
+        let record = flight.to_record();
+        assert_eq!(Some(String::from("2991.07")), record[great_circle]);
+        assert_eq!(Some(String::from("3109.09")), record[rhumb_line]);
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(Ok(b';'), parse_delimiter(";"));
+        assert_eq!(Ok(b','), parse_delimiter(","));
+        assert!(parse_delimiter("").is_err());
+        assert!(parse_delimiter(";;").is_err());
+        assert!(parse_delimiter("§").is_err());
+        for reserved in ["\"", "\n", "\r"] {
+            assert!(parse_delimiter(reserved).is_err());
+        }
+    }
+
+    #[test]
+    fn test_open_with_another_delimiter() {
+        let dir = std::env::temp_dir().join(format!("logbook-delimiter-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logbook.csv");
+        drop(open(&path, b',', DATE_FORMAT).unwrap());
+        let existing = std::fs::read_to_string(&path).unwrap();
+
+        assert!(open(&path, b';', DATE_FORMAT).is_err());
+        assert_eq!(existing, std::fs::read_to_string(&path).unwrap());
+        assert!(open(&path, b',', DATE_FORMAT).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_log_with_delimiter() {
+        let dir = std::env::temp_dir().join(format!("logbook-semicolon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logbook.csv");
+        let mut flight = Flight::new(&Aircraft::default());
+        flight.aircraft.title = String::from("Cessna 172; G1000");
+        flight.aircraft.icao = String::from("C172");
+
+        let mut logbook = Logbook::new(&path, b';', DATE_FORMAT);
+        logbook.log(&flight).unwrap();
+        logbook.flush().unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("Aircraft Name;Aircraft ICAO;Registration;"));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("\"Cessna 172; G1000\";C172;;"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Stands in for a locked logbook, a directory can't be opened as a file.
+    fn lock(path: &Path) {
+        std::fs::create_dir(path).unwrap();
+    }
+
+    #[test]
+    fn test_log_retries_while_unwritable() {
+        let dir = std::env::temp_dir().join(format!("logbook-retry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logbook.csv");
+        let flight = Flight::new(&Aircraft::default());
+
+        lock(&path);
+        let mut logbook = Logbook::new(&path, b',', DATE_FORMAT);
+        logbook.log(&flight).unwrap();
+        assert_eq!(1, logbook.pending.len());
+
+        std::fs::remove_dir(&path).unwrap();
+        logbook.log(&flight).unwrap();
+        assert!(logbook.pending.is_empty());
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(3, csv.lines().count());
+        assert!(csv.starts_with("Aircraft Name,"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_flush_writes_fallback_while_unwritable() {
+        let dir = std::env::temp_dir().join(format!("logbook-fallback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logbook.csv");
+
+        lock(&path);
+        let mut logbook = Logbook::new(&path, b',', DATE_FORMAT);
+        logbook.log(&Flight::new(&Aircraft::default())).unwrap();
+        logbook.flush().unwrap();
+        assert!(logbook.pending.is_empty());
+
+        let fallback = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|p| p.is_file())
+            .unwrap();
+        assert!(fallback
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("logbook-"));
+        assert_eq!(
+            2,
+            std::fs::read_to_string(&fallback).unwrap().lines().count()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation_path() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 15, 10, 45, 0).unwrap();
+        let path = Path::new("flights/logbook.csv");
+        assert_eq!(
+            Path::new("flights/logbook-2024-01.csv"),
+            Rotation::Monthly.path(path, &now)
+        );
+        assert_eq!(
+            Path::new("flights/logbook-2024-01-15.csv"),
+            Rotation::Daily.path(path, &now)
+        );
+        assert_eq!(Ok(Rotation::Monthly), "monthly".parse());
+        assert!("weekly".parse::<Rotation>().is_err());
+    }
+
+    #[test]
+    fn test_log_rotates() {
+        let dir = std::env::temp_dir().join(format!("logbook-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut logbook = Logbook::rotating(
+            &dir.join("logbook.csv"),
+            b',',
+            DATE_FORMAT,
+            Rotation::Monthly,
+        );
+        let mut flight = Flight::new(&Aircraft::default());
+
+        flight.shutdown = Some(Utc.with_ymd_and_hms(2024, 1, 31, 23, 0, 0).unwrap());
+        logbook.log(&flight).unwrap();
+        logbook.log(&flight).unwrap();
+        flight.shutdown = Some(Utc.with_ymd_and_hms(2024, 2, 1, 1, 0, 0).unwrap());
+        logbook.log(&flight).unwrap();
+        logbook.flush().unwrap();
+
+        let january = std::fs::read_to_string(dir.join("logbook-2024-01.csv")).unwrap();
+        assert_eq!(3, january.lines().count());
+        let february = std::fs::read_to_string(dir.join("logbook-2024-02.csv")).unwrap();
+        assert_eq!(2, february.lines().count());
+        assert!(february.starts_with("Aircraft Name,"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fallback_path() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 10, 45, 0).unwrap();
+        assert_eq!(
+            Path::new("flights/logbook-20240101104500.csv"),
+            fallback_path(Path::new("flights/logbook.csv"), &now)
+        );
+    }
+}
@@ -0,0 +1,486 @@
+use crate::logbook::DATE_FORMAT;
+use crate::timezone::LOCAL_DATE_FORMAT;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Utc};
+use geo::LatLon;
+use std::{error::Error, fs::File, io::Read, path::Path};
+
+fn string_to_date(s: &str, format: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
+    Ok(NaiveDateTime::parse_from_str(s, format)?.and_utc())
+}
+
+fn string_to_local_date(s: &str) -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+    Ok(DateTime::parse_from_str(s, LOCAL_DATE_FORMAT)?)
+}
+
+/// Parse a duration written by `duration_to_string`.
+fn string_to_duration(s: &str) -> Result<Duration, Box<dyn Error>> {
+    let (hours, minutes) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid duration: {s}"))?;
+    Ok(Duration::hours(hours.parse()?) + Duration::minutes(minutes.parse()?))
+}
+
+/// Parse a position written in degrees, minutes and seconds by
+/// `LatLon::to_dms_string`.
+fn string_to_position(s: &str) -> Result<LatLon, Box<dyn Error>> {
+    Ok(LatLon::from_dms_str(s)?)
+}
+
+/// Parse a flight level, e.g. `FL350`.
+fn string_to_flight_level(s: &str) -> Result<u32, Box<dyn Error>> {
+    let flight_level = s
+        .strip_prefix("FL")
+        .ok_or_else(|| format!("invalid flight level: {s}"))?;
+    Ok(flight_level.parse()?)
+}
+
+/// A flight as read back from the logbook.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlightRecord {
+    pub aircraft_name: String,
+    pub aircraft_icao: String,
+    pub registration: String,
+    pub taxi_out: Option<DateTime<Utc>>,
+    pub departure_icao: Option<String>,
+    pub departure_time: Option<DateTime<Utc>>,
+    pub arrival_icao: Option<String>,
+    pub arrival_time: Option<DateTime<Utc>>,
+    pub shutdown: Option<DateTime<Utc>>,
+    pub block_time: Option<Duration>,
+    pub air_time: Option<Duration>,
+    pub category: Option<String>,
+    pub aborted_takeoffs: Option<u32>,
+    pub departure_local_time: Option<DateTime<FixedOffset>>,
+    pub arrival_local_time: Option<DateTime<FixedOffset>>,
+    pub hobbs_time: Option<Duration>,
+    /// In nautical miles.
+    pub taxi_out_distance: Option<f64>,
+    pub taxi_in_distance: Option<f64>,
+    pub go_arounds: Option<u32>,
+    pub wheels_up: Option<DateTime<Utc>>,
+    pub wheels_down: Option<DateTime<Utc>>,
+    /// In knots, positive for a tailwind.
+    pub wind_component: Option<f64>,
+    /// To a hundredth of a second of arc, as logged.
+    pub parking_position: Option<LatLon>,
+    /// The touch and goes at each airport, e.g. `3 at LCPH, 1 at LCLK`.
+    pub touch_and_goes: Option<String>,
+    /// The version of the logbook that logged the flight, e.g. `0.1.0 (protocol 1)`.
+    pub version: Option<String>,
+    pub cruise_flight_level: Option<u32>,
+    pub top_of_climb: Option<DateTime<Utc>>,
+    pub top_of_descent: Option<DateTime<Utc>>,
+    pub holds: Option<u32>,
+    pub engine_cycles: Option<u32>,
+    /// In whole degrees.
+    pub departure_course: Option<u32>,
+    pub arrival_course: Option<u32>,
+    /// In nautical miles, between the departure and arrival airports.
+    pub great_circle_distance: Option<f64>,
+    pub rhumb_line_distance: Option<f64>,
+    pub origin_position: Option<LatLon>,
+    pub shutdown_position: Option<LatLon>,
+    /// Ground speeds in knots, when leaving the ground and touching down.
+    pub rotation_speed: Option<f64>,
+    pub touchdown_speed: Option<f64>,
+}
+
+/// Reads the flights written by `Logbook::log`.
+///
+/// Columns are looked up by their header, so logbooks written before a column
+/// was added can still be read, with the missing values as `None`. A malformed
+/// row is yielded as an error and doesn't stop the iteration.
+pub struct LogbookReader<R> {
+    // the logbook until the first flight is read, along with its header
+    reader: Option<R>,
+    header: csv::StringRecord,
+    records: Option<csv::StringRecordsIntoIter<R>>,
+    delimiter: u8,
+    time_format: String,
+}
+
+impl LogbookReader<File> {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_reader(File::open(path)?))
+    }
+}
+
+impl<R: Read> LogbookReader<R> {
+    pub fn from_reader(reader: R) -> Self {
+        LogbookReader {
+            reader: Some(reader),
+            header: csv::StringRecord::new(),
+            records: None,
+            delimiter: b',',
+            time_format: DATE_FORMAT.to_string(),
+        }
+    }
+
+    /// Read the fields separated by `delimiter` instead of a comma, the
+    /// logbook's `--delimiter`.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Read the times in `format` instead of `DATE_FORMAT`, the logbook's
+    /// `--time-format`.
+    pub fn time_format(mut self, format: &str) -> Self {
+        self.time_format = format.to_string();
+        self
+    }
+
+    /// Return the field under `column`, empty fields are treated as missing.
+    fn field<'a>(&self, record: &'a csv::StringRecord, column: &str) -> Option<&'a str> {
+        let index = self.header.iter().position(|h| h == column)?;
+        record.get(index).filter(|field| !field.is_empty())
+    }
+
+    fn string(&self, record: &csv::StringRecord, column: &str) -> String {
+        self.field(record, column).unwrap_or("").to_string()
+    }
+
+    fn date(
+        &self,
+        record: &csv::StringRecord,
+        column: &str,
+    ) -> Result<Option<DateTime<Utc>>, Box<dyn Error>> {
+        self.field(record, column)
+            .map(|field| {
+                string_to_date(field, &self.time_format)
+                    .map_err(|e| format!("{column}: {e}").into())
+            })
+            .transpose()
+    }
+
+    fn local_date(
+        &self,
+        record: &csv::StringRecord,
+        column: &str,
+    ) -> Result<Option<DateTime<FixedOffset>>, Box<dyn Error>> {
+        self.field(record, column)
+            .map(string_to_local_date)
+            .transpose()
+    }
+
+    fn duration(
+        &self,
+        record: &csv::StringRecord,
+        column: &str,
+    ) -> Result<Option<Duration>, Box<dyn Error>> {
+        self.field(record, column)
+            .map(string_to_duration)
+            .transpose()
+    }
+
+    fn parse(&self, record: &csv::StringRecord) -> Result<FlightRecord, Box<dyn Error>> {
+        Ok(FlightRecord {
+            aircraft_name: self.string(record, "Aircraft Name"),
+            aircraft_icao: self.string(record, "Aircraft ICAO"),
+            registration: self.string(record, "Registration"),
+            taxi_out: self.date(record, "Taxi Time")?,
+            departure_icao: self.field(record, "Departure ICAO").map(String::from),
+            departure_time: self.date(record, "Departure Time")?,
+            arrival_icao: self.field(record, "Arrival ICAO").map(String::from),
+            arrival_time: self.date(record, "Arrival Time")?,
+            shutdown: self.date(record, "Shutdown Time")?,
+            block_time: self.duration(record, "Block Time")?,
+            air_time: self.duration(record, "Air Time")?,
+            category: self.field(record, "Category").map(String::from),
+            aborted_takeoffs: self
+                .field(record, "Aborted Takeoffs")
+                .map(str::parse)
+                .transpose()?,
+            departure_local_time: self.local_date(record, "Departure Local Time")?,
+            arrival_local_time: self.local_date(record, "Arrival Local Time")?,
+            hobbs_time: self.duration(record, "Hobbs Time")?,
+            taxi_out_distance: self
+                .field(record, "Taxi Out Distance")
+                .map(str::parse)
+                .transpose()?,
+            taxi_in_distance: self
+                .field(record, "Taxi In Distance")
+                .map(str::parse)
+                .transpose()?,
+            go_arounds: self
+                .field(record, "Go Arounds")
+                .map(str::parse)
+                .transpose()?,
+            wheels_up: self.date(record, "Wheels Up")?,
+            wheels_down: self.date(record, "Wheels Down")?,
+            wind_component: self
+                .field(record, "Wind Component")
+                .map(str::parse)
+                .transpose()?,
+            parking_position: self
+                .field(record, "Parking Position")
+                .map(string_to_position)
+                .transpose()?,
+            touch_and_goes: self.field(record, "Touch and Goes").map(String::from),
+            version: self.field(record, "Version").map(String::from),
+            cruise_flight_level: self
+                .field(record, "Cruise FL")
+                .map(string_to_flight_level)
+                .transpose()?,
+            top_of_climb: self.date(record, "Top of Climb")?,
+            top_of_descent: self.date(record, "Top of Descent")?,
+            holds: self.field(record, "Holds").map(str::parse).transpose()?,
+            engine_cycles: self
+                .field(record, "Engine Cycles")
+                .map(str::parse)
+                .transpose()?,
+            departure_course: self
+                .field(record, "Departure Course")
+                .map(str::parse)
+                .transpose()?,
+            arrival_course: self
+                .field(record, "Arrival Course")
+                .map(str::parse)
+                .transpose()?,
+            great_circle_distance: self
+                .field(record, "Great Circle Distance")
+                .map(str::parse)
+                .transpose()?,
+            rhumb_line_distance: self
+                .field(record, "Rhumb Line Distance")
+                .map(str::parse)
+                .transpose()?,
+            origin_position: self
+                .field(record, "Origin Position")
+                .map(string_to_position)
+                .transpose()?,
+            shutdown_position: self
+                .field(record, "Shutdown Position")
+                .map(string_to_position)
+                .transpose()?,
+            rotation_speed: self
+                .field(record, "Rotation Speed")
+                .map(str::parse)
+                .transpose()?,
+            touchdown_speed: self
+                .field(record, "Touchdown Speed")
+                .map(str::parse)
+                .transpose()?,
+        })
+    }
+}
+
+impl<R: Read> Iterator for LogbookReader<R> {
+    type Item = Result<FlightRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(reader) = self.reader.take() {
+            let mut csv = csv::ReaderBuilder::new()
+                .delimiter(self.delimiter)
+                .from_reader(reader);
+            match csv.headers() {
+                Ok(header) => self.header = header.clone(),
+                Err(e) => return Some(Err(e.into())),
+            }
+            self.records = Some(csv.into_records());
+        }
+        let record = match self.records.as_mut()?.next()? {
+            Ok(record) => record,
+            Err(e) => {
+                return Some(Err(match e.kind() {
+                    csv::ErrorKind::UnequalLengths {
+                        pos,
+                        expected_len,
+                        len,
+                    } => {
+                        let line = pos.as_ref().map(|p| p.line()).unwrap_or_default();
+                        format!("line {line}: expected {expected_len} fields, found {len}").into()
+                    }
+                    _ => e.into(),
+                }))
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        Some(
+            self.parse(&record)
+                .map_err(|e| format!("line {line}: {e}").into()),
+        )
+    }
+}
+
+/// Read every flight in the logbook without changing it, returning how many
+/// rows there are and what's wrong with each malformed one, e.g. a short row
+/// or a date that can't be read, by line number.
+pub fn verify<R: Read>(reader: LogbookReader<R>) -> (usize, Vec<String>) {
+    let mut rows = 0;
+    let mut problems = vec![];
+    for record in reader {
+        rows += 1;
+        if let Err(e) = record {
+            problems.push(e.to_string());
+        }
+    }
+    (rows, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const FIXTURE: &str = "\
+Aircraft Name,Aircraft ICAO,Registration,Taxi Time,Departure ICAO,Departure Time,Arrival ICAO,Arrival Time,Shutdown Time,Block Time,Air Time,Category,Aborted Takeoffs,Departure Local Time,Arrival Local Time,Hobbs Time,Taxi Out Distance,Taxi In Distance,Go Arounds,Wheels Up,Wheels Down,Wind Component,Parking Position,Touch and Goes,Version,Cruise FL,Top of Climb,Top of Descent,Holds,Engine Cycles,Departure Course,Arrival Course,Great Circle Distance,Rhumb Line Distance,Origin Position,Shutdown Position,Rotation Speed,Touchdown Speed
+Challenger 650,CL60,C-FAAV,2024-01-01 10:00:00,LCPH,2024-01-01 10:10:00,LCLK,2024-01-01 10:40:00,2024-01-01 10:45:00,0:45,0:30,cross-country,1,2024-01-01 12:10:00 +02:00,2024-01-01 12:40:00 +02:00,0:42,1.25,0.80,2,2024-01-01 10:09:30,2024-01-01 10:39:45,-12.5,\"N34°43'04.00\"\" E032°29'08.00\"\"\",\"3 at LCPH, 1 at LCLK\",0.1.0 (protocol 1),FL350,2024-01-01 10:25:00,2024-01-01 10:30:00,1,2,287,095,56.95,56.95,\"N34°43'04.00\"\" E032°29'08.00\"\"\",\"N34°52'30.00\"\" E033°37'29.00\"\"\",138,122
+Challenger 650,CL60,C-FAAV,yesterday,LCPH,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,
+";
+
+    #[test]
+    fn test_verify() {
+        let fixture = "\
+Aircraft Name,Departure ICAO,Departure Time
+Challenger 650,LCPH,2024-01-01 10:10:00
+Challenger 650,LCPH
+Challenger 650,LCPH,01/01/2024 10:10
+Cessna 172,LCLK,
+";
+        let (rows, problems) = verify(LogbookReader::from_reader(fixture.as_bytes()));
+        assert_eq!(4, rows);
+        assert_eq!(2, problems.len());
+        assert_eq!("line 3: expected 3 fields, found 2", problems[0]);
+        assert!(problems[1].starts_with("line 4: Departure Time: "));
+    }
+
+    #[test]
+    fn test_string_to_duration() {
+        assert_eq!(Duration::minutes(65), string_to_duration("1:05").unwrap());
+        assert!(string_to_duration("65").is_err());
+    }
+
+    #[test]
+    fn test_reader_good_and_malformed_rows() {
+        let mut reader = LogbookReader::from_reader(FIXTURE.as_bytes());
+
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!("Challenger 650", record.aircraft_name);
+        assert_eq!("CL60", record.aircraft_icao);
+        assert_eq!("C-FAAV", record.registration);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap()),
+            record.taxi_out
+        );
+        assert_eq!(Some(String::from("LCPH")), record.departure_icao);
+        assert_eq!(Some(String::from("LCLK")), record.arrival_icao);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 45, 0).unwrap()),
+            record.shutdown
+        );
+        assert_eq!(Some(Duration::minutes(45)), record.block_time);
+        assert_eq!(Some(Duration::minutes(30)), record.air_time);
+        assert_eq!(Some(String::from("cross-country")), record.category);
+        assert_eq!(Some(1), record.aborted_takeoffs);
+        assert_eq!(Some(Duration::minutes(42)), record.hobbs_time);
+        assert_eq!(Some(1.25), record.taxi_out_distance);
+        assert_eq!(Some(0.8), record.taxi_in_distance);
+        assert_eq!(Some(2), record.go_arounds);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 9, 30).unwrap()),
+            record.wheels_up
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 39, 45).unwrap()),
+            record.wheels_down
+        );
+        assert_eq!(Some(-12.5), record.wind_component);
+        assert_eq!(
+            Some(String::from("N34°43'04.00\" E032°29'08.00\"")),
+            record.parking_position.map(|p| p.to_dms_string())
+        );
+        assert_eq!(
+            Some(String::from("3 at LCPH, 1 at LCLK")),
+            record.touch_and_goes
+        );
+        assert_eq!(Some(String::from("0.1.0 (protocol 1)")), record.version);
+        assert_eq!(Some(350), record.cruise_flight_level);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 25, 0).unwrap()),
+            record.top_of_climb
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 30, 0).unwrap()),
+            record.top_of_descent
+        );
+        assert_eq!(Some(1), record.holds);
+        assert_eq!(Some(2), record.engine_cycles);
+        assert_eq!(Some(287), record.departure_course);
+        assert_eq!(Some(95), record.arrival_course);
+        assert_eq!(Some(56.95), record.great_circle_distance);
+        assert_eq!(Some(56.95), record.rhumb_line_distance);
+        assert_eq!(
+            Some(String::from("N34°43'04.00\" E032°29'08.00\"")),
+            record.origin_position.map(|p| p.to_dms_string())
+        );
+        assert_eq!(
+            Some(String::from("N34°52'30.00\" E033°37'29.00\"")),
+            record.shutdown_position.map(|p| p.to_dms_string())
+        );
+        assert_eq!(Some(138.0), record.rotation_speed);
+        assert_eq!(Some(122.0), record.touchdown_speed);
+        assert_eq!(
+            record.departure_time,
+            record.departure_local_time.map(|dt| dt.with_timezone(&Utc))
+        );
+        assert_eq!(
+            Some(FixedOffset::east_opt(2 * 3600).unwrap()),
+            record.departure_local_time.map(|dt| *dt.offset())
+        );
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert!(error.to_string().starts_with("line 3:"));
+
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_reader_time_format() {
+        let csv = "Aircraft Name,Taxi Time\nCessna 172,2024-01-01T10:00:00Z\n";
+        let record = LogbookReader::from_reader(csv.as_bytes())
+            .time_format("%Y-%m-%dT%H:%M:%SZ")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap()),
+            record.taxi_out
+        );
+        // not in the default format
+        let mut reader = LogbookReader::from_reader(csv.as_bytes());
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_reader_delimiter() {
+        let csv = "Aircraft Name;Departure ICAO;Block Time\n\"Cessna 172; G1000\";LCPH;1:05\n";
+        let record = LogbookReader::from_reader(csv.as_bytes())
+            .delimiter(b';')
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!("Cessna 172; G1000", record.aircraft_name);
+        assert_eq!(Some(String::from("LCPH")), record.departure_icao);
+        assert_eq!(Some(Duration::minutes(65)), record.block_time);
+        // read as comma separated, none of the columns are found
+        let record = LogbookReader::from_reader(csv.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, record.departure_icao);
+    }
+
+    #[test]
+    fn test_reader_empty_fields_are_none() {
+        let csv = "Aircraft Name,Departure ICAO,Arrival Time\nCessna 172,,\n";
+        let record = LogbookReader::from_reader(csv.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!("Cessna 172", record.aircraft_name);
+        assert_eq!(None, record.departure_icao);
+        assert_eq!(None, record.arrival_time);
+        assert_eq!(None, record.block_time);
+    }
+}
@@ -0,0 +1,69 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Logs to the console, warnings and errors to stderr and everything else to
+/// stdout.
+struct ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("ERROR: {}", record.args()),
+            Level::Warn => eprintln!("WARNING: {}", record.args()),
+            _ => println!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// The most detailed level that's logged. Everything is logged by default,
+/// including each sample from the sim, but only warnings and errors when
+/// `quiet`, e.g. when running in the background.
+pub fn level(quiet: bool) -> LevelFilter {
+    if quiet {
+        LevelFilter::Warn
+    } else {
+        LevelFilter::Debug
+    }
+}
+
+pub fn init(quiet: bool) -> Result<(), log::SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(level(quiet));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(level: Level) -> bool {
+        LOGGER.enabled(&Metadata::builder().level(level).build())
+    }
+
+    #[test]
+    fn test_quiet() {
+        init(true).unwrap();
+        assert_eq!(LevelFilter::Warn, log::max_level());
+        assert!(enabled(Level::Error));
+        assert!(enabled(Level::Warn));
+        assert!(!enabled(Level::Info));
+        assert!(!enabled(Level::Debug));
+        log::error!("errors are still logged when quiet");
+    }
+
+    #[test]
+    fn test_level() {
+        assert_eq!(LevelFilter::Debug, level(false));
+        assert_eq!(LevelFilter::Warn, level(true));
+    }
+}
@@ -1,250 +1,611 @@
 use crate::aircraft::Aircraft;
-use crate::sim_connection::{SimConnection, SimMessage};
+use crate::flight::{FlightConfig, FlightEvent, FlightTracker, LogThreshold};
+use crate::navdata::{search_within, Navdata};
+use crate::sim_connection::{SimChoice, SimConnection, SimMessage};
+use crate::sink::FlightSink;
+use crate::throttle::Throttled;
+use crate::watchdog::Watchdog;
 use chrono::{DateTime, Utc};
-use geo::LatLon;
-use rusqlite::OptionalExtension;
-use std::{error::Error, fs::File, path::Path};
+use log::{error, info, warn};
+use std::{
+    error::Error,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 mod aircraft;
+mod clock;
+mod flight;
+mod hold;
+mod kml;
+mod logbook;
+mod logbook_reader;
+mod logger;
+mod monitor;
 mod msfs;
+mod navdata;
+mod replay;
 mod sim_connection;
+mod sink;
+mod sqlite_logbook;
+mod stats;
+mod status;
+mod throttle;
+mod timezone;
+mod track;
+mod watchdog;
 mod xplane;
 
-// some fields aren't used, but are useful for debugging
-#[allow(dead_code)]
-#[derive(Clone, Debug)]
-struct Airport {
-    id: i64,
-    ident: String,
-    position: LatLon,
+fn pick_sim() -> Result<SimChoice, String> {
+    SimChoice::from_arg(std::env::args().nth(1).as_deref())
 }
 
-fn search_within(
-    navdata: &rusqlite::Connection,
-    origin: LatLon,
-) -> Result<Option<Airport>, Box<dyn Error>> {
-    let mut stmt = navdata.prepare(
-        "
-select airport_id, ident, laty, lonx
-  from airport
-  where airport_id in (
-    select airport_id from airport_coords where
-        left_lonx <= ?1 and right_lonx >= ?1 and
-        bottom_laty <= ?2 and top_laty >= ?2
-  );
-    ",
-    )?;
-    stmt.query_row([origin.longitude(), origin.latitude()], |row| {
-        Ok(Airport {
-            id: row.get(0)?,
-            ident: row.get(1)?,
-            position: LatLon::new(row.get(2)?, row.get(3)?),
-        })
-    })
-    // it is acceptable to not receive a record
-    .optional()
-    // convert rusqlite::Error into error::Error
-    .map_err(|e| e.into())
+/// Return whether `flag` was passed on the command line.
+fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|arg| arg == flag)
 }
 
-pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Return the value following `flag` on the command line, if it was passed.
+fn flag_value(flag: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != flag).nth(1)
+}
+
+/// The flight's thresholds, from the defaults overridden by any flags passed.
+fn flight_config() -> Result<FlightConfig, Box<dyn Error>> {
+    let mut config = FlightConfig::default();
+    if let Some(nm) = flag_value("--cross-country-nm") {
+        config.cross_country_nm = nm.parse()?;
+    }
+    if let Some(policy) = flag_value("--on-aircraft-change") {
+        config.aircraft_change = policy.parse()?;
+    }
+    if let Some(policy) = flag_value("--ground-policy") {
+        config.ground_policy = policy.parse()?;
+    }
+    if let Some(secs) = flag_value("--frozen-feed-secs") {
+        config.frozen_feed = chrono::Duration::seconds(secs.parse()?);
+    }
+    if let Some(km) = flag_value("--reposition-km") {
+        config.reposition_km = km.parse()?;
+        if !(config.reposition_km.is_finite() && config.reposition_km > 0.0) {
+            return Err("--reposition-km must be above 0".into());
+        }
+    }
+    if let Some(minutes) = flag_value("--turnaround-minutes") {
+        config.turnaround = Some(chrono::Duration::minutes(minutes.parse()?));
+    }
+    if let Some(meters) = flag_value("--departure-bias-m") {
+        config.departure_bias_m = meters.parse()?;
+    }
+    if let Some(meters) = flag_value("--hold-radius-m") {
+        config.hold.radius_m = meters.parse()?;
+    }
+    if let Some(minutes) = flag_value("--hold-max-lap-minutes") {
+        config.hold.max_lap = chrono::Duration::minutes(minutes.parse()?);
+    }
+    config.log_threshold = LogThreshold {
+        min_distance_nm: flag_value("--min-distance-nm")
+            .map(|nm| nm.parse())
+            .transpose()?,
+        min_duration: flag_value("--min-duration-min")
+            .map(|minutes| minutes.parse().map(chrono::Duration::minutes))
+            .transpose()?,
+    };
+    Ok(config)
+}
 
-fn date_to_string(dt: &DateTime<Utc>) -> String {
-    dt.format(DATE_FORMAT).to_string()
+/// The logbook's field delimiter, from `--delimiter`.
+fn delimiter() -> Result<u8, String> {
+    match flag_value("--delimiter") {
+        Some(delimiter) => logbook::parse_delimiter(&delimiter),
+        None => Ok(b','),
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-enum FlightState {
-    Preflight,
-    Taxi,
-    EnRoute,
-    Landed,
-    Complete,
+/// The format of the logbook's times, from `--time-format`.
+fn time_format() -> Result<String, String> {
+    match flag_value("--time-format") {
+        Some(format) => logbook::parse_time_format(&format),
+        None => Ok(logbook::DATE_FORMAT.to_string()),
+    }
 }
 
-#[derive(Clone, Debug)]
-struct Flight {
-    aircraft: Aircraft,
-    state: FlightState,
-    taxi_out: Option<DateTime<Utc>>,
-    departure: Option<(Airport, DateTime<Utc>)>,
-    arrival: Option<(Airport, DateTime<Utc>)>,
-    shutdown: Option<DateTime<Utc>>,
+/// Tracks the flights flown in the sim, sending them to the sinks.
+struct Session {
+    navdata: Navdata,
+    tracker: FlightTracker,
+    log_threshold: LogThreshold,
+    sinks: Vec<Box<dyn FlightSink>>,
+    status: Option<status::SharedStatus>,
 }
 
-impl Flight {
-    fn new(aircraft: &Aircraft) -> Self {
-        Flight {
-            aircraft: aircraft.clone(),
-            state: FlightState::Preflight,
-            taxi_out: None,
-            departure: None,
-            arrival: None,
-            shutdown: None,
+impl Session {
+    /// Handle the sim's messages until `running` is cleared, or until the sim
+    /// quits with `stop_on_quit`, e.g. at the end of a replay. The sinks are
+    /// flushed before returning.
+    fn run(
+        &mut self,
+        sim: &mut impl SimConnection<Error = Box<dyn Error>>,
+        running: &AtomicBool,
+        stop_on_quit: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let result = self.handle_messages(sim, running, stop_on_quit);
+        sink::flush(&mut self.sinks);
+        result
+    }
+
+    fn handle_messages(
+        &mut self,
+        sim: &mut impl SimConnection<Error = Box<dyn Error>>,
+        running: &AtomicBool,
+        stop_on_quit: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        while running.load(Ordering::SeqCst) {
+            match sim.next_message() {
+                Ok(SimMessage::SimData(aircraft)) => self.update(aircraft)?,
+                Ok(SimMessage::SimDataBatch(batch)) => self.update_batch(batch)?,
+                Ok(SimMessage::Connecting) => {
+                    info!("Connecting to simulator...")
+                }
+                Ok(SimMessage::Open) => {
+                    info!("Simulator connection established.")
+                }
+                Ok(SimMessage::Quit) => {
+                    warn!("Simulator connection closed.");
+                    // keep the flight in progress as it is now, in case the
+                    // sim doesn't come back
+                    if self.tracker.current_flight.is_some() {
+                        sink::current(&mut self.sinks, self.tracker.current_flight.as_ref());
+                    }
+                    sink::flush(&mut self.sinks);
+                    if stop_on_quit {
+                        break;
+                    }
+                }
+                msg => warn!("Unhandled message received: {:?}", msg),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle samples received together at once, oldest first, at the times
+    /// from `FlightTracker::batch_times`. Only the latest sample moves the
+    /// flight on and the others just go into its track, sparing a navdata
+    /// query each, unless the aircraft left or touched the ground or its
+    /// engines were started or shut down within the batch.
+    fn update_batch(&mut self, batch: Vec<Aircraft>) -> Result<(), Box<dyn Error>> {
+        let times = self.tracker.batch_times(batch.len());
+        let transitions = batch.windows(2).any(|pair| {
+            pair[0].on_ground != pair[1].on_ground
+                || pair[0].gear_on_ground != pair[1].gear_on_ground
+                || pair[0].engine_on != pair[1].engine_on
+        });
+        let latest = batch.len().saturating_sub(1);
+        for (i, (aircraft, now)) in batch.into_iter().zip(times).enumerate() {
+            if transitions || i == latest {
+                self.update_at(aircraft, now)?;
+            } else {
+                self.tracker.update_track(&aircraft, now);
+            }
         }
+        Ok(())
     }
 
-    fn arrive(&mut self, airport: &Airport, time: &DateTime<Utc>) {
-        self.arrival = Some((airport.clone(), *time));
+    fn update(&mut self, aircraft: Aircraft) -> Result<(), Box<dyn Error>> {
+        let closest_airport = search_within(&self.navdata, aircraft.position)?;
+        let event = self.tracker.update(&aircraft, closest_airport);
+        self.on_update(event, aircraft);
+        Ok(())
     }
 
-    fn depart(&mut self, airport: &Airport, time: &DateTime<Utc>) {
-        self.departure = Some((airport.clone(), *time));
+    /// Same as `update`, with the sample received at `now`.
+    fn update_at(&mut self, aircraft: Aircraft, now: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+        let closest_airport = search_within(&self.navdata, aircraft.position)?;
+        let event = self.tracker.update_at(&aircraft, closest_airport, now);
+        self.on_update(event, aircraft);
+        Ok(())
     }
 
-    fn to_record(&self) -> Vec<Option<String>> {
-        vec![
-            Some(self.aircraft.title.clone()),
-            Some(self.aircraft.icao.clone()),
-            Some(self.aircraft.registration.clone()),
-            self.taxi_out.map(|dt| date_to_string(&dt)),
-            self.departure.clone().map(|d| d.0.ident),
-            self.departure.clone().map(|d| date_to_string(&d.1)),
-            self.arrival.clone().map(|a| a.0.ident),
-            self.arrival.clone().map(|a| date_to_string(&a.1)),
-            self.shutdown.map(|dt| date_to_string(&dt)),
-        ]
+    /// Send the flight to the sinks and the status after an update.
+    fn on_update(&mut self, event: Option<FlightEvent>, aircraft: Aircraft) {
+        match &event {
+            Some(FlightEvent::Departed(Some(airport))) => info!("Departed {}.", airport.ident),
+            Some(FlightEvent::Arrived(Some(airport))) => info!("Arrived at {}.", airport.ident),
+            Some(FlightEvent::Completed(flight)) => {
+                if self.log_threshold.is_below(flight) {
+                    info!("Flight completed, but too short to log.");
+                } else {
+                    info!("Flight completed!");
+                    sink::complete(&mut self.sinks, flight);
+                }
+            }
+            _ => {}
+        }
+        if event.is_some() {
+            sink::current(&mut self.sinks, self.tracker.current_flight.as_ref());
+        }
+        if let Some(status) = &self.status {
+            let mut status = status.lock().unwrap();
+            status.flight = self
+                .tracker
+                .current_flight
+                .as_ref()
+                .map(status::FlightStatus::from);
+            status.aircraft = Some(aircraft);
+        }
     }
 }
 
-pub const CSV_HEADER: [&str; 9] = [
-    "Aircraft Name",
-    "Aircraft ICAO",
-    "Registration",
-    "Taxi Time",
-    "Departure ICAO",
-    "Departure Time",
-    "Arrival ICAO",
-    "Arrival Time",
-    "Shutdown Time",
-];
-
-struct Logbook(File);
+fn main() -> Result<(), Box<dyn Error>> {
+    logger::init(has_flag("--quiet"))?;
+    if std::env::args().nth(1).as_deref() == Some("stats") {
+        let path = std::env::args()
+            .nth(2)
+            .filter(|arg| !arg.starts_with("--"))
+            .unwrap_or("logbook.csv".to_owned());
+        let since = flag_value("--since")
+            .map(|since| stats::parse_since(&since))
+            .transpose()?;
+        print!(
+            "{}",
+            stats::Stats::read(Path::new(&path), since, delimiter()?, &time_format()?)?
+        );
+        return Ok(());
+    }
+    if std::env::args().nth(1).as_deref() == Some("check-navdata") {
+        let path = std::env::args()
+            .nth(2)
+            .expect("USAGE: logbook.exe check-navdata <PATH>");
+        let airports = navdata::check(Path::new(&path))?;
+        println!("{path}: OK, {airports} airports");
+        return Ok(());
+    }
+    if std::env::args().nth(1).as_deref() == Some("verify") {
+        let path = std::env::args()
+            .nth(2)
+            .expect("USAGE: logbook.exe verify <PATH>");
+        let reader = logbook_reader::LogbookReader::open(Path::new(&path))?
+            .delimiter(delimiter()?)
+            .time_format(&time_format()?);
+        let (rows, problems) = logbook_reader::verify(reader);
+        if problems.is_empty() {
+            println!("{path}: OK, {rows} flights");
+            return Ok(());
+        }
+        for problem in &problems {
+            error!("{path}: {problem}");
+        }
+        error!("{path}: {} of {rows} flights are malformed", problems.len());
+        std::process::exit(1);
+    }
+    if std::env::args().nth(1).as_deref() == Some("monitor") {
+        let running = Arc::new(AtomicBool::new(true));
+        let handler_running = running.clone();
+        ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))?;
+        return monitor::run(&mut xplane::Xplane::connect(), &running);
+    }
 
-impl Logbook {
-    fn new(path: &Path) -> Result<Self, Box<dyn Error>> {
-        let should_add_header = !path.exists();
-        let f = File::options().create(true).append(true).open(path)?;
+    let sim_choice = match pick_sim() {
+        Ok(sim_choice) => sim_choice,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(2);
+        }
+    };
+    let formats = sink::parse_formats(&flag_value("--format").unwrap_or("csv".to_owned()))?;
+    let sample_interval = match flag_value("--sample-interval") {
+        Some(secs) => Duration::from_secs_f64(secs.parse()?),
+        None => Duration::ZERO,
+    };
+    let config = flight_config()?;
+    let watchdog_timeout = match flag_value("--watchdog-secs") {
+        Some(secs) => Some(Duration::from_secs(secs.parse()?)),
+        None => None,
+    };
+    let navdata_path = match sim_choice {
+        SimChoice::Msfs => "navdata/msfs.sqlite",
+        // replays are in the X-Plane plugin's format
+        SimChoice::Xp12 | SimChoice::File => "navdata/xp12.sqlite",
+    };
+    let navdata_path = flag_value("--navdata").unwrap_or(navdata_path.to_owned());
+    let navdata = navdata::open(Path::new(&navdata_path))?;
 
-        if should_add_header {
-            csv::Writer::from_writer(&f).write_record(CSV_HEADER)?;
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))?;
+    let sim: Box<dyn SimConnection<Error = Box<dyn Error>>> = match sim_choice {
+        SimChoice::Msfs => Box::new(msfs::Msfs::connect().until_stopped(running.clone())),
+        SimChoice::Xp12 if has_flag("--batch") => Box::new(xplane::Xplane::connect().batched()),
+        SimChoice::Xp12 => Box::new(xplane::Xplane::connect()),
+        SimChoice::File => {
+            let path =
+                flag_value("--replay-path").expect("USAGE: logbook.exe FILE --replay-path <PATH>");
+            let speed: f64 = match flag_value("--replay-speed") {
+                Some(speed) => speed.parse()?,
+                None => 1.0,
+            };
+            if !(speed.is_finite() && speed > 0.0) {
+                return Err("--replay-speed must be above 0".into());
+            }
+            Box::new(replay::FileReplay::open(Path::new(&path), speed)?)
+        }
+    };
+    let sim: Box<dyn SimConnection<Error = Box<dyn Error>>> = match flag_value("--record") {
+        Some(path) => Box::new(replay::Recorder::create(sim, Path::new(&path))?),
+        None => sim,
+    };
+    let mut sim = Watchdog::new(
+        Throttled::new(sim, sample_interval),
+        watchdog_timeout,
+        has_flag("--watchdog-reconnect"),
+    );
+    let mut sink_options = sink::SinkOptions {
+        per_aircraft: has_flag("--per-aircraft"),
+        track_tolerance_m: flag_value("--track-tolerance-m")
+            .map(|meters| meters.parse())
+            .transpose()?,
+        rotation: flag_value("--rotate")
+            .map(|rotation| rotation.parse())
+            .transpose()?,
+        ..sink::SinkOptions::default()
+    };
+    sink_options.time_format = time_format()?;
+    sink_options.delimiter = delimiter()?;
+    let sinks = sink::sinks(&formats, Path::new("."), &sink_options)?;
+    let destination = match flag_value("--destination") {
+        Some(ident) => Some(
+            navdata::find_airport(&navdata, &ident)?
+                .ok_or_else(|| format!("unknown destination airport: {ident}"))?,
+        ),
+        None => None,
+    };
+    let status = match flag_value("--status-addr") {
+        Some(addr) => {
+            let shared = status::SharedStatus::default();
+            shared.lock().unwrap().destination = destination;
+            let addr = status::serve(&addr, shared.clone())?;
+            info!("Serving the flight status on http://{addr}");
+            Some(shared)
         }
+        None => None,
+    };
+    let mut session = Session {
+        navdata,
+        tracker: FlightTracker::new(config),
+        log_threshold: config.log_threshold,
+        sinks,
+        status,
+    };
+    session.run(&mut sim, &running, sim_choice == SimChoice::File)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flight::{Flight, FlightState};
+    use crate::logbook::header;
+    use crate::sim_connection::StubConnection;
+    use geo::LatLon;
+    use std::{cell::RefCell, rc::Rc};
 
-        Ok(Logbook(f))
+    fn sample(position: LatLon, engine_on: bool, ground_speed: f64, agl_ft: f64) -> SimMessage {
+        SimMessage::SimData(aircraft(position, engine_on, ground_speed, agl_ft))
     }
 
-    fn log(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
-        let mut csv = csv::Writer::from_writer(&self.0);
-        // change None to ""
-        for field in flight.to_record() {
-            csv.write_field(field.unwrap_or("".to_string()))?;
+    fn aircraft(position: LatLon, engine_on: bool, ground_speed: f64, agl_ft: f64) -> Aircraft {
+        Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position,
+            engine_on,
+            on_ground: agl_ft == 0.0,
+            ground_speed,
+            agl_ft,
+            ..Aircraft::default()
         }
-        csv.write_record(None::<&[u8]>)?;
-        csv.flush()?;
-        Ok(())
     }
-}
 
-fn pick_sim() -> String {
-    let allowed_choices = vec!["MSFS".to_owned(), "XP12".to_owned()];
-    let choice = std::env::args()
-        .nth(1)
-        .expect("USAGE: logbook.exe <SIM NAME>");
-    if !allowed_choices.contains(&choice) {
-        panic!("Invalid sim provided: {choice}, valid options: {allowed_choices:?}");
+    /// Keeps the state of the flight in progress each time it's stored.
+    struct CurrentSink(Rc<RefCell<Vec<Option<FlightState>>>>);
+
+    impl FlightSink for CurrentSink {
+        fn on_complete(&mut self, _: &Flight) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn on_current(&mut self, flight: Option<&Flight>) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().push(flight.map(|flight| flight.state));
+            Ok(())
+        }
     }
-    choice
-}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let sim_choice = pick_sim();
-    let navdata_path = match sim_choice.as_str() {
-        "MSFS" => "navdata/msfs.sqlite",
-        "XP12" => "navdata/xp12.sqlite",
-        _ => unreachable!(),
-    };
-    let navdata = rusqlite::Connection::open(navdata_path)?;
-    navdata.execute(
-        "
-        create virtual table if not exists airport_coords using rtree(
-            airport_id, left_lonx, right_lonx, bottom_laty, top_laty
-        )
-    ",
-        (),
-    )?;
-    navdata.execute(
-        "
-        insert or ignore into airport_coords
-            select airport_id, left_lonx, right_lonx, bottom_laty, top_laty from airport
-    ",
-        (),
-    )?;
-
-    let mut sim: Box<dyn SimConnection<Error = Box<dyn std::error::Error>>> =
-        match sim_choice.as_str() {
-            "MSFS" => Box::new(msfs::Msfs::connect()),
-            "XP12" => Box::new(xplane::Xplane::connect()?),
-            _ => unreachable!(),
-        };
-    let mut logbook = Logbook::new(Path::new("logbook.csv"))?;
-    let mut current_flight: Option<Flight> = None;
-    loop {
-        match sim.next_message() {
-            Ok(SimMessage::SimData(aircraft)) => {
-                // initialize current flight if there isn't one
-                if current_flight.is_none() {
-                    current_flight = Some(Flight::new(&aircraft));
-                }
+    /// Counts how many times it's flushed.
+    struct FlushCountingSink(Rc<RefCell<usize>>);
 
-                let closest_airport = search_within(&navdata, aircraft.position)?;
-                let flight = current_flight.as_mut().unwrap();
-                println!("{:?}", flight);
-                match flight.state {
-                    FlightState::Preflight => {
-                        if aircraft.engine_on {
-                            flight.taxi_out = Some(Utc::now());
-                            flight.state = FlightState::Taxi;
-                        }
-                    }
-                    FlightState::Taxi => {
-                        if !aircraft.on_ground {
-                            let airport = closest_airport.expect("invalid takeoff airport");
-                            flight.depart(&airport, &Utc::now());
-                            flight.state = FlightState::EnRoute;
-                        }
-                    }
-                    FlightState::EnRoute => {
-                        if aircraft.on_ground {
-                            let airport = closest_airport.expect("invalid landing airport");
-                            flight.arrive(&airport, &Utc::now());
-                            flight.state = FlightState::Landed;
-                        }
-                    }
-                    FlightState::Landed => {
-                        if !aircraft.on_ground {
-                            // did a touch and go or a go around
-                            flight.state = FlightState::EnRoute;
-                        } else if !aircraft.engine_on {
-                            flight.shutdown = Some(Utc::now());
-                            flight.state = FlightState::Complete;
-                        }
-                    }
-                    FlightState::Complete => {
-                        println!("Flight completed!");
-                        // store record
-                        logbook.log(flight)?;
-                        // reset flight
-                        current_flight = None;
-                    }
-                }
-            }
-            Ok(SimMessage::Open) => {
-                println!("Simulator connection established.")
-            }
-            Ok(SimMessage::Quit) => {
-                println!("Simulator connection closed.");
-            }
-            msg => eprintln!("Unhandled message received: {:?}", msg),
+    impl FlightSink for FlushCountingSink {
+        fn on_complete(&mut self, _: &Flight) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+            *self.0.borrow_mut() += 1;
+            Ok(())
         }
     }
+
+    /// Fails on every message, like a sim that can't be reached.
+    struct FailingConnection;
+
+    impl SimConnection for FailingConnection {
+        type Error = Box<dyn Error>;
+
+        fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+            Err("connection refused".into())
+        }
+
+        fn close(&mut self) {}
+    }
+
+    fn session(sinks: Vec<Box<dyn FlightSink>>) -> Session {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection.execute_batch(navdata::TEST_AIRPORTS).unwrap();
+        Session {
+            navdata: navdata::from_connection(connection).unwrap(),
+            tracker: FlightTracker::new(FlightConfig::default()),
+            log_threshold: LogThreshold::default(),
+            sinks,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn test_session_stops_on_quit() {
+        let stored = Rc::new(RefCell::new(vec![]));
+        let mut session = session(vec![Box::new(CurrentSink(stored.clone()))]);
+        let lcph = LatLon::new(34.717778, 32.485556);
+        let mut sim = StubConnection::new([
+            sample(lcph, false, 0.0, 0.0),
+            sample(lcph, true, 0.0, 0.0),
+            sample(lcph, true, 10.0, 0.0),
+            SimMessage::Quit,
+            sample(lcph, true, 150.0, 100.0),
+        ]);
+
+        session.run(&mut sim, &AtomicBool::new(true), true).unwrap();
+
+        // nothing after the quit was processed
+        let flight = session.tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Taxi, flight.state);
+        // stored when taxiing, and again as it was when the sim quit
+        assert_eq!(
+            vec![Some(FlightState::Taxi), Some(FlightState::Taxi)],
+            *stored.borrow()
+        );
+    }
+
+    #[test]
+    fn test_session_flushes_on_error() {
+        let flushes = Rc::new(RefCell::new(0));
+        let mut session = session(vec![Box::new(FlushCountingSink(flushes.clone()))]);
+
+        let result = session.run(&mut FailingConnection, &AtomicBool::new(true), false);
+
+        assert!(result.is_err());
+        assert_eq!(1, *flushes.borrow());
+    }
+
+    #[test]
+    fn test_session_batch() {
+        let mut session = session(vec![]);
+        let lcph = LatLon::new(34.717778, 32.485556);
+        let taxiway = [
+            LatLon::new(34.717, 32.485),
+            LatLon::new(34.716, 32.484),
+            LatLon::new(34.715, 32.483),
+        ];
+        let mut sim = StubConnection::new([
+            sample(lcph, true, 0.0, 0.0),
+            SimMessage::SimDataBatch(vec![
+                aircraft(taxiway[0], true, 10.0, 0.0),
+                aircraft(taxiway[1], true, 15.0, 0.0),
+                aircraft(taxiway[2], true, 15.0, 0.0),
+            ]),
+        ]);
+
+        session.run(&mut sim, &AtomicBool::new(true), true).unwrap();
+
+        let flight = session.tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::Taxi, flight.state);
+        let points = flight.track.points();
+        assert_eq!(
+            vec![lcph, taxiway[0], taxiway[1], taxiway[2]],
+            points
+                .iter()
+                .map(|point| point.position)
+                .collect::<Vec<_>>()
+        );
+        // each at its own time, in order
+        assert!(points.windows(2).all(|pair| pair[0].time < pair[1].time));
+    }
+
+    #[test]
+    fn test_session_batch_with_takeoff() {
+        let mut session = session(vec![]);
+        let lcph = LatLon::new(34.717778, 32.485556);
+        let runway = [
+            LatLon::new(34.715, 32.48),
+            LatLon::new(34.712, 32.475),
+            LatLon::new(34.709, 32.47),
+        ];
+        let mut sim = StubConnection::new([
+            sample(lcph, true, 0.0, 0.0),
+            SimMessage::SimDataBatch(vec![
+                aircraft(runway[0], true, 140.0, 0.0),
+                aircraft(runway[1], true, 150.0, 50.0),
+                aircraft(runway[2], true, 160.0, 300.0),
+            ]),
+        ]);
+
+        session.run(&mut sim, &AtomicBool::new(true), true).unwrap();
+
+        let flight = session.tracker.current_flight.as_ref().unwrap();
+        assert_eq!(FlightState::EnRoute, flight.state);
+        // left the ground with the sample before the latest
+        let points = flight.track.points();
+        assert_eq!(Some(points[2].time), flight.wheels_up);
+        assert_eq!(Some(150.0), flight.rotation_speed_kt);
+    }
+
+    #[test]
+    fn test_session_logs_flight_to_csv() {
+        let dir = std::env::temp_dir().join(format!("logbook-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut session = session(
+            sink::sinks(&[sink::Format::Csv], &dir, &sink::SinkOptions::default()).unwrap(),
+        );
+        let lcph = LatLon::new(34.717778, 32.485556);
+        let lclk = LatLon::new(34.875, 33.624722);
+        let mut sim = StubConnection::new([
+            SimMessage::Connecting,
+            SimMessage::Open,
+            sample(lcph, false, 0.0, 0.0),
+            sample(lcph, true, 0.0, 0.0),
+            sample(lcph, true, 140.0, 0.0),
+            sample(lcph, true, 150.0, 100.0),
+            sample(LatLon::new(34.8, 33.0), true, 400.0, 20000.0),
+            sample(lclk, true, 130.0, 100.0),
+            sample(lclk, true, 110.0, 0.0),
+            sample(lclk, true, 10.0, 0.0),
+            sample(lclk, false, 0.0, 0.0),
+            sample(lclk, false, 0.0, 0.0),
+        ]);
+
+        session.run(&mut sim, &AtomicBool::new(true), true).unwrap();
+
+        let csv = std::fs::read_to_string(dir.join("logbook.csv")).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            header().collect::<Vec<_>>().join(","),
+            lines.next().unwrap()
+        );
+        let record: Vec<&str> = lines.next().unwrap().split(',').collect();
+        let field = |name| record[header().position(|h| h == name).unwrap()];
+        assert_eq!("Challenger 650", field("Aircraft Name"));
+        assert_eq!("CL60", field("Aircraft ICAO"));
+        assert_eq!("C-FAAV", field("Registration"));
+        assert_eq!("LCPH", field("Departure ICAO"));
+        assert_eq!("LCLK", field("Arrival ICAO"));
+        assert_eq!("cross-country", field("Category"));
+        assert_eq!("0", field("Go Arounds"));
+        assert_eq!("1", field("Engine Cycles"));
+        assert_eq!(None, lines.next());
+        assert!(session.tracker.current_flight.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
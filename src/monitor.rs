@@ -0,0 +1,104 @@
+use crate::aircraft::Aircraft;
+use crate::sim_connection::{SimConnection, SimMessage};
+use log::{info, warn};
+use std::{
+    error::Error,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Clears the terminal and moves the cursor back to the top left, so that
+/// each sample is drawn over the previous one.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// The aircraft as a table of its fields, one to a line, e.g.
+///
+/// ```text
+/// Aircraft      Challenger 650 (CL60) C-FAAV
+/// Position      34°43'4.00"N 32°29'8.00"E
+/// Engines       running
+/// Ground        on the ground
+/// ```
+pub fn table(aircraft: &Aircraft) -> String {
+    let (latitude, longitude) = aircraft.position.to_dms();
+    let rows = [
+        (
+            "Aircraft",
+            format!(
+                "{} ({}) {}",
+                aircraft.title, aircraft.icao, aircraft.registration
+            ),
+        ),
+        ("Position", format!("{latitude} {longitude}")),
+        (
+            "Engines",
+            String::from(if aircraft.engine_on { "running" } else { "off" }),
+        ),
+        (
+            "Ground",
+            String::from(if aircraft.on_ground {
+                "on the ground"
+            } else {
+                "airborne"
+            }),
+        ),
+        ("Ground speed", format!("{:.0} kt", aircraft.ground_speed)),
+        ("Altitude", format!("{:.0} ft", aircraft.baro_altitude_ft)),
+        ("Heading", format!("{:03.0}", aircraft.heading)),
+    ];
+    rows.iter()
+        .map(|(name, value)| format!("{name:<14}{value}\n"))
+        .collect()
+}
+
+/// Print each sample from the sim as a table, drawn over the last one, until
+/// `running` is cleared. Nothing is tracked or logged.
+pub fn run(
+    sim: &mut impl SimConnection<Error = Box<dyn Error>>,
+    running: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let mut stdout = std::io::stdout();
+    while running.load(Ordering::SeqCst) {
+        match sim.next_message()? {
+            SimMessage::SimData(aircraft) => {
+                write!(stdout, "{CLEAR_SCREEN}{}", table(&aircraft))?;
+                stdout.flush()?;
+            }
+            SimMessage::SimDataBatch(batch) => {
+                if let Some(aircraft) = batch.last() {
+                    write!(stdout, "{CLEAR_SCREEN}{}", table(aircraft))?;
+                    stdout.flush()?;
+                }
+            }
+            SimMessage::Connecting => info!("Connecting to simulator..."),
+            SimMessage::Open => info!("Simulator connection established."),
+            SimMessage::Quit => warn!("Simulator connection closed."),
+            SimMessage::Waiting | SimMessage::Unknown => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xp_sim_data::SimData;
+
+    #[test]
+    fn test_table() {
+        let record = "CL60,Challenger 650,C-FAAV,34.717778,32.485556,true,false,250.4,35012.5,450,degrees,false,5,false,35100,35000,90.4,false";
+        let aircraft = Aircraft::from(SimData::from_csv(record).unwrap());
+        assert_eq!(
+            "\
+Aircraft      Challenger 650 (CL60) C-FAAV
+Position      34°43'4.00\"N 32°29'8.00\"E
+Engines       running
+Ground        airborne
+Ground speed  250 kt
+Altitude      35000 ft
+Heading       090
+",
+            table(&aircraft)
+        );
+    }
+}
@@ -1,13 +1,23 @@
-use crate::aircraft::Aircraft;
+use crate::aircraft::{normalize_title, Aircraft};
 use crate::sim_connection::{SimConnection, SimMessage};
 use geo::LatLon;
-use simconnect::DispatchResult;
-use std::{ffi, ptr, str, thread, time};
+use log::{debug, error};
+use simconnect::{
+    DispatchResult, SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64 as FLOAT64,
+    SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_STRING128 as STRING128,
+    SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_STRING32 as STRING32,
+    SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_STRING64 as STRING64,
+    SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_STRING8 as STRING8,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::{mem, ptr, str, thread, time};
 
 #[derive(Debug)]
 enum SimStringError {
     Utf8Error(str::Utf8Error),
-    CStrError(ffi::FromBytesUntilNulError),
 }
 
 impl From<str::Utf8Error> for SimStringError {
@@ -16,12 +26,6 @@ impl From<str::Utf8Error> for SimStringError {
     }
 }
 
-impl From<ffi::FromBytesUntilNulError> for SimStringError {
-    fn from(value: ffi::FromBytesUntilNulError) -> Self {
-        Self::CStrError(value)
-    }
-}
-
 /// A representation of SimConnect's strings.
 ///
 /// It will usually be created by doing `ptr::read_unaligned(..)` in a struct
@@ -40,10 +44,12 @@ impl From<ffi::FromBytesUntilNulError> for SimStringError {
 pub struct SimString<const N: usize>([u8; N]);
 
 impl<const N: usize> SimString<N> {
+    /// The string up to the first null, or the whole buffer for a string
+    /// that fills it without leaving room for one.
     fn to_string(&self) -> Result<String, SimStringError> {
         let bytes = self.0;
-        let c_str = ffi::CStr::from_bytes_until_nul(&bytes)?;
-        Ok(String::from(c_str.to_str()?))
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(N);
+        Ok(String::from(str::from_utf8(&bytes[..len])?))
     }
 }
 
@@ -71,23 +77,154 @@ struct RawSimData {
     // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_RadioNavigation_Variables.htm
     // may or may not contain aircraft registration
     atc_id: SimString<32>,
+    // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_Misc_Variables.htm
+    ground_velocity: f64,
+    // fallbacks for when ATC ID doesn't hold a registration
+    atc_flight_number: SimString<8>,
+    atc_airline: SimString<64>,
+    // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_Misc_Variables.htm
+    plane_alt_above_ground: f64,
+    // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_Flight_Model_Variables.htm
+    airspeed_true: f64,
+    // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_Brake_Landing_Gear_Variables.htm
+    brake_parking_position: f64,
+    // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_Misc_Variables.htm
+    plane_altitude: f64,
+    indicated_altitude: f64,
+    // https://docs.flightsimulator.com/html/Programming_Tools/SimVars/Aircraft_SimVars/Aircraft_Misc_Variables.htm
+    plane_heading_degrees_true: f64,
+}
+
+/// A SimVar read into the field of `RawSimData` at `offset`.
+struct DataDefinition {
+    name: &'static str,
+    unit: &'static str,
+    datatype: simconnect::SIMCONNECT_DATATYPE,
+    offset: usize,
+}
+
+macro_rules! definition {
+    ($name:expr, $unit:expr, $datatype:expr, $field:ident) => {
+        DataDefinition {
+            name: $name,
+            unit: $unit,
+            datatype: $datatype,
+            offset: mem::offset_of!(RawSimData, $field),
+        }
+    };
+}
+
+/// The SimVars making up `RawSimData`, SimConnect writes them one after the
+/// other so they have to be in the same order as its fields.
+const DATA_DEFINITIONS: [DataDefinition; 18] = [
+    definition!("TITLE", "", STRING128, title),
+    definition!("ENG COMBUSTION:1", "Boolean", FLOAT64, eng_combustion_1),
+    definition!("ENG COMBUSTION:2", "Boolean", FLOAT64, eng_combustion_2),
+    definition!("ENG COMBUSTION:3", "Boolean", FLOAT64, eng_combustion_3),
+    definition!("ENG COMBUSTION:4", "Boolean", FLOAT64, eng_combustion_4),
+    definition!("PLANE LATITUDE", "Radians", FLOAT64, latitude),
+    definition!("PLANE LONGITUDE", "Radians", FLOAT64, longitude),
+    definition!("SIM ON GROUND", "Boolean", FLOAT64, sim_on_ground),
+    definition!("ATC ID", "", STRING32, atc_id),
+    definition!("GROUND VELOCITY", "Knots", FLOAT64, ground_velocity),
+    definition!("ATC FLIGHT NUMBER", "", STRING8, atc_flight_number),
+    definition!("ATC AIRLINE", "", STRING64, atc_airline),
+    definition!(
+        "PLANE ALT ABOVE GROUND",
+        "Feet",
+        FLOAT64,
+        plane_alt_above_ground
+    ),
+    definition!("AIRSPEED TRUE", "Knots", FLOAT64, airspeed_true),
+    definition!(
+        "BRAKE PARKING POSITION",
+        "Bool",
+        FLOAT64,
+        brake_parking_position
+    ),
+    definition!("PLANE ALTITUDE", "Feet", FLOAT64, plane_altitude),
+    definition!("INDICATED ALTITUDE", "Feet", FLOAT64, indicated_altitude),
+    definition!(
+        "PLANE HEADING DEGREES TRUE",
+        "Degrees",
+        FLOAT64,
+        plane_heading_degrees_true
+    ),
+];
+
+/// Size in bytes SimConnect writes for the datatypes we use.
+fn datatype_size(datatype: simconnect::SIMCONNECT_DATATYPE) -> Option<usize> {
+    match datatype {
+        FLOAT64 | STRING8 => Some(8),
+        STRING32 => Some(32),
+        STRING64 => Some(64),
+        STRING128 => Some(128),
+        _ => None,
+    }
+}
+
+/// Check that each definition fills the field right after the previous one,
+/// and that together they fill all `size` bytes of the struct.
+fn check_definitions(definitions: &[DataDefinition], size: usize) -> Result<(), String> {
+    let mut offset = 0;
+    for definition in definitions {
+        if definition.offset != offset {
+            return Err(format!(
+                "{} fills the field at byte {}, expected one at byte {offset}",
+                definition.name, definition.offset
+            ));
+        }
+        offset += datatype_size(definition.datatype)
+            .ok_or_else(|| format!("{} has an unsupported datatype", definition.name))?;
+    }
+    if offset != size {
+        return Err(format!(
+            "the definitions fill {offset} bytes, but the struct is {size}"
+        ));
+    }
+    Ok(())
+}
+
+/// The ATC ID the stock aircraft are shipped with.
+const PLACEHOLDER_ATC_IDS: [&str; 1] = ["ASXGS"];
+
+/// Pick the best identifier available for the aircraft, preferring the
+/// ATC ID unless it is blank or a placeholder, then the airline and flight
+/// number.
+fn registration(raw: &RawSimData) -> Result<String, SimStringError> {
+    let atc_id = raw.atc_id.to_string()?;
+    let atc_id = atc_id.trim();
+    if !atc_id.is_empty() && !PLACEHOLDER_ATC_IDS.contains(&atc_id) {
+        return Ok(String::from(atc_id));
+    }
+
+    let flight_number = raw.atc_flight_number.to_string()?;
+    let flight_number = flight_number.trim();
+    if flight_number.is_empty() {
+        return Ok(String::from(atc_id));
+    }
+    let airline = raw.atc_airline.to_string()?;
+    let airline = airline.trim();
+    if airline.is_empty() {
+        Ok(String::from(flight_number))
+    } else {
+        Ok(format!("{airline} {flight_number}"))
+    }
 }
 
 impl TryFrom<RawSimData> for Aircraft {
     type Error = SimStringError;
 
     fn try_from(raw: RawSimData) -> Result<Self, Self::Error> {
-        let engine_on = [
+        let engine_on = xp_sim_data::any_engine_running([
             raw.eng_combustion_1,
             raw.eng_combustion_2,
             raw.eng_combustion_3,
             raw.eng_combustion_4,
-        ]
-        .iter()
-        .any(|x| *x != 0.0);
+        ]);
 
         Ok(Self {
-            title: raw.title.to_string()?,
+            title: normalize_title(&raw.title.to_string()?),
             // FIXME: ICAO isn't available from simconnect yet.
             //
             // a possible option is to do a lookup for the aircraft (using the title)
@@ -96,91 +233,136 @@ impl TryFrom<RawSimData> for Aircraft {
             icao: String::from("N/A"),
             position: LatLon::from_radians(raw.latitude, raw.longitude),
             // not the most reliable source, but its the best we have
-            registration: raw.atc_id.to_string()?,
+            registration: registration(&raw)?,
             engine_on,
             on_ground: raw.sim_on_ground != 0.0,
+            gear_on_ground: None,
+            ground_speed: raw.ground_velocity,
+            agl_ft: raw.plane_alt_above_ground,
+            true_airspeed_kt: raw.airspeed_true,
+            parking_brake: raw.brake_parking_position != 0.0,
+            protocol_version: None,
+            paused: false,
+            elevation_ft: raw.plane_altitude,
+            baro_altitude_ft: raw.indicated_altitude,
+            heading: raw.plane_heading_degrees_true,
         })
     }
 }
 
-pub struct Msfs(simconnect::SimConnector);
+/// Delay after the first failed attempt to connect, doubled after each
+/// following one up to `MAX_RETRY_DELAY`.
+const INITIAL_RETRY_DELAY: time::Duration = time::Duration::from_secs(1);
+const MAX_RETRY_DELAY: time::Duration = time::Duration::from_secs(30);
+
+/// Attempts to connect before giving up, about 10 minutes with the delays above.
+const MAX_CONNECT_ATTEMPTS: u32 = 25;
+
+/// How often a sleep between attempts checks whether to stop.
+const STOP_CHECK_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// Call `connect` until it succeeds, calling `sleep` with an exponential
+/// backoff between the attempts. Gives up after `max_attempts`, or without an
+/// error when `sleep` returns false to stop, returning whether it connected.
+fn retry_with_backoff(
+    max_attempts: u32,
+    mut connect: impl FnMut() -> bool,
+    mut sleep: impl FnMut(time::Duration) -> bool,
+) -> Result<bool, String> {
+    let mut delay = INITIAL_RETRY_DELAY;
+    for attempt in 1..=max_attempts {
+        if connect() {
+            return Ok(true);
+        }
+        if attempt < max_attempts {
+            if !sleep(delay) {
+                return Ok(false);
+            }
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+    Err(format!(
+        "couldn't connect to MSFS after {max_attempts} attempts, is the sim running?"
+    ))
+}
+
+/// Sleep for `duration` while `running` is set, returning whether it still is.
+fn sleep_while(running: &AtomicBool, duration: time::Duration) -> bool {
+    let until = time::Instant::now() + duration;
+    while running.load(Ordering::SeqCst) {
+        let left = until.saturating_duration_since(time::Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        thread::sleep(left.min(STOP_CHECK_INTERVAL));
+    }
+    false
+}
+
+pub struct Msfs {
+    conn: simconnect::SimConnector,
+    connected: bool,
+    // whether Connecting was sent since the connection was lost
+    connecting: bool,
+    running: Arc<AtomicBool>,
+}
 
 impl Msfs {
+    /// Create the connection, it isn't made until the first message is requested.
     pub fn connect() -> Self {
-        let mut conn = simconnect::SimConnector::new();
-        conn.connect("Logbook");
-        conn.add_data_definition(
-            0,
-            "TITLE",
-            "",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_STRING128,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "ENG COMBUSTION:1",
-            "Boolean",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "ENG COMBUSTION:2",
-            "Boolean",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "ENG COMBUSTION:3",
-            "Boolean",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "ENG COMBUSTION:4",
-            "Boolean",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "PLANE LATITUDE",
-            "Radians",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "PLANE LONGITUDE",
-            "Radians",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "SIM ON GROUND",
-            "Boolean",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-            u32::MAX,
-            0.0,
-        );
-        conn.add_data_definition(
-            0,
-            "ATC ID",
-            "",
-            simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_STRING32,
-            u32::MAX,
-            0.0,
-        );
+        Self {
+            conn: simconnect::SimConnector::new(),
+            connected: false,
+            connecting: false,
+            running: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Stop retrying the connection once `running` is cleared, e.g. on Ctrl-C,
+    /// instead of blocking until the sim starts.
+    pub fn until_stopped(mut self, running: Arc<AtomicBool>) -> Self {
+        self.running = running;
+        self
+    }
+
+    /// Send `Connecting`, then block until the sim is running and the
+    /// connection is open, failing if it doesn't start in time. Sends
+    /// `Connecting` again if stopped while waiting.
+    fn try_connect(&mut self) -> Result<SimMessage, String> {
+        if !self.connecting {
+            self.connecting = true;
+            return Ok(SimMessage::Connecting);
+        }
+        let conn = &mut self.conn;
+        let running = &self.running;
+        let connected = retry_with_backoff(
+            MAX_CONNECT_ATTEMPTS,
+            || conn.connect("Logbook"),
+            |delay| sleep_while(running, delay),
+        )?;
+        if !connected {
+            return Ok(SimMessage::Connecting);
+        }
+        self.register();
+        self.connected = true;
+        self.connecting = false;
+        Ok(SimMessage::Open)
+    }
+
+    fn register(&self) {
+        let conn = &self.conn;
+        check_definitions(&DATA_DEFINITIONS, mem::size_of::<RawSimData>())
+            .expect("data definitions don't match RawSimData");
+        for definition in DATA_DEFINITIONS {
+            conn.add_data_definition(
+                0,
+                definition.name,
+                definition.unit,
+                definition.datatype,
+                u32::MAX,
+                0.0,
+            );
+        }
 
         // receive data related to the user aircraft
         conn.request_data_on_sim_object(
@@ -193,7 +375,6 @@ impl Msfs {
             0, // interval
             0, // limit
         );
-        Self(conn)
     }
 }
 
@@ -201,9 +382,16 @@ impl SimConnection for Msfs {
     type Error = Box<dyn std::error::Error>;
 
     fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
-        let msg = match self.0.get_next_message() {
-            Ok(DispatchResult::Open(_)) => SimMessage::Open,
-            Ok(DispatchResult::Quit(_)) => SimMessage::Quit,
+        if !self.connected {
+            return Ok(self.try_connect()?);
+        }
+        let msg = match self.conn.get_next_message() {
+            // already sent when connecting
+            Ok(DispatchResult::Open(_)) => SimMessage::Waiting,
+            Ok(DispatchResult::Quit(_)) => {
+                self.connected = false;
+                SimMessage::Quit
+            }
             Ok(DispatchResult::SimObjectData(data)) => unsafe {
                 if data.dwDefineID == 0 {
                     let sim_data_ptr = ptr::addr_of!(data.dwData) as *const RawSimData;
@@ -217,11 +405,11 @@ impl SimConnection for Msfs {
                 }
             },
             Ok(msg) => {
-                println!("Unhandled message: {msg:?}");
+                debug!("Unhandled message: {msg:?}");
                 SimMessage::Unknown
             }
             Err(e) => {
-                println!("SimConnect error: {e}");
+                error!("SimConnect error: {e}");
                 SimMessage::Waiting
             }
         };
@@ -229,4 +417,199 @@ impl SimConnection for Msfs {
         thread::sleep(time::Duration::from_secs(1));
         Ok(msg)
     }
+
+    fn close(&mut self) {
+        self.conn.close();
+        self.connected = false;
+        self.connecting = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sim_string<const N: usize>(s: &str) -> SimString<N> {
+        let mut bytes = [0; N];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        SimString(bytes)
+    }
+
+    fn raw_sim_data(atc_id: &str, atc_flight_number: &str, atc_airline: &str) -> RawSimData {
+        RawSimData {
+            title: sim_string("Airbus A320 Neo"),
+            eng_combustion_1: 1.0,
+            eng_combustion_2: 1.0,
+            eng_combustion_3: 0.0,
+            eng_combustion_4: 0.0,
+            latitude: 0.6059,
+            longitude: 0.5670,
+            sim_on_ground: 1.0,
+            atc_id: sim_string(atc_id),
+            ground_velocity: 0.0,
+            atc_flight_number: sim_string(atc_flight_number),
+            atc_airline: sim_string(atc_airline),
+            plane_alt_above_ground: 0.0,
+            airspeed_true: 0.0,
+            brake_parking_position: 0.0,
+            plane_altitude: 0.0,
+            indicated_altitude: 0.0,
+            plane_heading_degrees_true: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_sim_string() {
+        assert_eq!("5B-DCW", sim_string::<8>("5B-DCW").to_string().unwrap());
+        assert_eq!("", sim_string::<8>("").to_string().unwrap());
+        // garbage left after the terminator by a longer string
+        let mut garbage = sim_string::<8>("5B-DCW");
+        garbage.0[7] = b'X';
+        assert_eq!("5B-DCW", garbage.to_string().unwrap());
+    }
+
+    #[test]
+    fn test_sim_string_without_null() {
+        let full = sim_string::<8>("Cyprus A");
+        assert_eq!("Cyprus A", full.to_string().unwrap());
+        assert!(SimString([0xC3; 4]).to_string().is_err());
+    }
+
+    #[test]
+    fn test_engine_on() {
+        let engines = |combustion: [f64; 4]| RawSimData {
+            eng_combustion_1: combustion[0],
+            eng_combustion_2: combustion[1],
+            eng_combustion_3: combustion[2],
+            eng_combustion_4: combustion[3],
+            ..raw_sim_data("5B-DCW", "123", "Cyprus")
+        };
+        let engine_on = |combustion| Aircraft::try_from(engines(combustion)).unwrap().engine_on;
+        assert!(!engine_on([0.0; 4]));
+        assert!(engine_on([0.0, 0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn test_title_trimmed() {
+        let raw = RawSimData {
+            title: sim_string(" Airbus A320 Neo  "),
+            ..raw_sim_data("5B-DCW", "123", "Cyprus")
+        };
+        let aircraft = Aircraft::try_from(raw).unwrap();
+        assert_eq!("Airbus A320 Neo", aircraft.title);
+    }
+
+    #[test]
+    fn test_registration_from_atc_id() {
+        let aircraft = Aircraft::try_from(raw_sim_data("5B-DCW", "123", "Cyprus")).unwrap();
+        assert_eq!("5B-DCW", aircraft.registration);
+    }
+
+    #[test]
+    fn test_registration_falls_back_to_flight_number() {
+        let aircraft = Aircraft::try_from(raw_sim_data("", "123", "")).unwrap();
+        assert_eq!("123", aircraft.registration);
+
+        let aircraft = Aircraft::try_from(raw_sim_data("ASXGS", "123", "Cyprus")).unwrap();
+        assert_eq!("Cyprus 123", aircraft.registration);
+    }
+
+    #[test]
+    fn test_registration_keeps_placeholder_without_flight_number() {
+        let aircraft = Aircraft::try_from(raw_sim_data("ASXGS", "", "")).unwrap();
+        assert_eq!("ASXGS", aircraft.registration);
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        let mut attempts = 0;
+        let mut sleeps = vec![];
+        let result = retry_with_backoff(
+            5,
+            || {
+                attempts += 1;
+                attempts == 3
+            },
+            |delay| {
+                sleeps.push(delay.as_secs());
+                true
+            },
+        );
+        assert_eq!(Ok(true), result);
+        assert_eq!(3, attempts);
+        assert_eq!(vec![1, 2], sleeps);
+    }
+
+    #[test]
+    fn test_retry_with_backoff_gives_up() {
+        let mut attempts = 0;
+        let mut sleeps = vec![];
+        let result = retry_with_backoff(
+            8,
+            || {
+                attempts += 1;
+                false
+            },
+            |delay| {
+                sleeps.push(delay.as_secs());
+                true
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(8, attempts);
+        // capped at the maximum delay, without sleeping after the last attempt
+        assert_eq!(vec![1, 2, 4, 8, 16, 30, 30], sleeps);
+    }
+
+    #[test]
+    fn test_retry_with_backoff_stops() {
+        let mut attempts = 0;
+        let mut sleeps = 0;
+        let result = retry_with_backoff(
+            8,
+            || {
+                attempts += 1;
+                false
+            },
+            |_| {
+                sleeps += 1;
+                sleeps < 2
+            },
+        );
+        assert_eq!(Ok(false), result);
+        // stopped while sleeping after the second attempt
+        assert_eq!(2, attempts);
+    }
+
+    #[test]
+    fn test_sleep_while_stopped() {
+        let running = AtomicBool::new(false);
+        let start = time::Instant::now();
+        assert!(!sleep_while(&running, time::Duration::from_secs(30)));
+        assert!(start.elapsed() < time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_data_definitions_match_raw_sim_data() {
+        assert_eq!(
+            Ok(()),
+            check_definitions(&DATA_DEFINITIONS, mem::size_of::<RawSimData>())
+        );
+    }
+
+    #[test]
+    fn test_check_definitions_out_of_order() {
+        let definitions = [
+            definition!("PLANE LATITUDE", "Radians", FLOAT64, latitude),
+            definition!("TITLE", "", STRING128, title),
+        ];
+        assert!(check_definitions(&definitions, mem::size_of::<RawSimData>()).is_err());
+    }
+
+    #[test]
+    fn test_check_definitions_missing() {
+        let definitions = &DATA_DEFINITIONS[..DATA_DEFINITIONS.len() - 1];
+        let error = check_definitions(definitions, mem::size_of::<RawSimData>()).unwrap_err();
+        assert!(error.starts_with("the definitions fill"));
+    }
 }
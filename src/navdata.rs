@@ -0,0 +1,393 @@
+use geo::LatLon;
+use std::{error::Error, path::Path};
+
+// some fields aren't used, but are useful for debugging
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Airport {
+    pub id: i64,
+    pub ident: String,
+    pub position: LatLon,
+}
+
+/// How far (in degrees) the bounds taken for an airport reach from its
+/// position when the navdata doesn't have them, about 2km north and south and
+/// a little less east and west away from the equator, enough to cover the
+/// runways of most airports.
+const DEFAULT_BOUNDS_DEG: f64 = 0.02;
+
+/// The names of the `airport` columns with the airport's position and bounds,
+/// which differ between the Little Navmap databases generated for each sim.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Schema {
+    lat: &'static str,
+    lon: &'static str,
+    left: &'static str,
+    right: &'static str,
+    bottom: &'static str,
+    top: &'static str,
+    /// Whether the bounds columns are there, otherwise the airports are
+    /// indexed with bounds of `DEFAULT_BOUNDS_DEG` around their position.
+    bounds: bool,
+}
+
+impl Schema {
+    const LATY_LONX: Schema = Schema {
+        lat: "laty",
+        lon: "lonx",
+        left: "left_lonx",
+        right: "right_lonx",
+        bottom: "bottom_laty",
+        top: "top_laty",
+        bounds: true,
+    };
+
+    const LAT_LON: Schema = Schema {
+        lat: "lat",
+        lon: "lon",
+        left: "left_lon",
+        right: "right_lon",
+        bottom: "bottom_lat",
+        top: "top_lat",
+        bounds: true,
+    };
+
+    /// The `airport` columns needed to index and search the airports.
+    fn columns(&self) -> [&'static str; 4] {
+        ["airport_id", "ident", self.lat, self.lon]
+    }
+
+    /// The `airport` columns with the airport's bounds.
+    fn bounds_columns(&self) -> [&'static str; 4] {
+        [self.left, self.right, self.bottom, self.top]
+    }
+}
+
+/// An open navdata database, along with how its airports are laid out.
+pub struct Navdata {
+    connection: rusqlite::Connection,
+    schema: Schema,
+}
+
+/// Open the navdata database, indexing the airport bounding boxes for
+/// searching if they haven't been already.
+pub fn open(path: &Path) -> Result<Navdata, Box<dyn Error>> {
+    from_connection(rusqlite::Connection::open(path)?)
+}
+
+/// Same as `open`, with an already open database, e.g. one in memory.
+pub fn from_connection(connection: rusqlite::Connection) -> Result<Navdata, Box<dyn Error>> {
+    let schema = detect_schema(&connection)?;
+    let navdata = Navdata { connection, schema };
+    index_airports(&navdata)?;
+    Ok(navdata)
+}
+
+fn index_airports(navdata: &Navdata) -> Result<(), Box<dyn Error>> {
+    navdata.connection.execute(
+        "
+        create virtual table if not exists airport_coords using rtree(
+            airport_id, left_lonx, right_lonx, bottom_laty, top_laty
+        )
+    ",
+        (),
+    )?;
+    let Schema {
+        lat,
+        lon,
+        left,
+        right,
+        bottom,
+        top,
+        bounds,
+    } = navdata.schema;
+    let select = if bounds {
+        format!("select airport_id, {left}, {right}, {bottom}, {top} from airport")
+    } else {
+        let d = DEFAULT_BOUNDS_DEG;
+        format!(
+            "select airport_id, {lon} - {d}, {lon} + {d}, {lat} - {d}, {lat} + {d} from airport"
+        )
+    };
+    navdata.connection.execute(
+        &format!("insert or ignore into airport_coords {select}"),
+        (),
+    )?;
+    Ok(())
+}
+
+/// Work out the schema from the `airport` table's columns, failing if any of
+/// the columns we need are missing. The bounds are optional.
+fn detect_schema(navdata: &rusqlite::Connection) -> Result<Schema, Box<dyn Error>> {
+    let mut stmt = navdata.prepare("select name from pragma_table_info('airport')")?;
+    let columns = stmt
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.is_empty() {
+        return Err("missing airport table".into());
+    }
+    let has_column = |column: &str| columns.iter().any(|c| c == column);
+    let schema = [Schema::LATY_LONX, Schema::LAT_LON]
+        .into_iter()
+        .find(|schema| has_column(schema.lat) && has_column(schema.lon))
+        .ok_or("airport table has neither laty/lonx nor lat/lon columns")?;
+    let missing: Vec<_> = schema
+        .columns()
+        .into_iter()
+        .filter(|column| !has_column(column))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("airport table is missing columns: {}", missing.join(", ")).into());
+    }
+    let bounds = schema.bounds_columns().into_iter().all(has_column);
+    Ok(Schema { bounds, ..schema })
+}
+
+/// Check that the navdata database at `path` has an `airport` table with the
+/// columns we need, returning the number of airports in it.
+pub fn check(path: &Path) -> Result<usize, Box<dyn Error>> {
+    // read only, so that a missing file isn't created as an empty database
+    let navdata =
+        rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    check_airports(&navdata)
+}
+
+fn check_airports(navdata: &rusqlite::Connection) -> Result<usize, Box<dyn Error>> {
+    detect_schema(navdata)?;
+    let count: i64 = navdata.query_row("select count(*) from airport", (), |row| row.get(0))?;
+    Ok(count as usize)
+}
+
+/// Return the airport with the `ident`, e.g. `LCPH`.
+pub fn find_airport(navdata: &Navdata, ident: &str) -> Result<Option<Airport>, Box<dyn Error>> {
+    let Schema { lat, lon, .. } = navdata.schema;
+    let mut stmt = navdata.connection.prepare(&format!(
+        "select airport_id, ident, {lat}, {lon} from airport where ident = ?1"
+    ))?;
+    let mut airports = stmt.query_map([ident], |row| {
+        Ok(Airport {
+            id: row.get(0)?,
+            ident: row.get(1)?,
+            position: LatLon::new(row.get(2)?, row.get(3)?),
+        })
+    })?;
+    Ok(airports.next().transpose()?)
+}
+
+/// Return the airport whose bounds contain `origin`, the closest one if there
+/// are several.
+pub fn search_within(navdata: &Navdata, origin: LatLon) -> Result<Option<Airport>, Box<dyn Error>> {
+    Ok(search_all_within(navdata, origin, 0.0)?.into_iter().next())
+}
+
+/// Return all the airports whose bounds contain `origin` or that are within
+/// `radius_m` meters of it, closest first.
+pub fn search_all_within(
+    navdata: &Navdata,
+    origin: LatLon,
+    radius_m: f64,
+) -> Result<Vec<Airport>, Box<dyn Error>> {
+    // narrow it down to the airports in a box around the radius, before
+    // checking the actual distance
+    let north = origin.destination(0.0, radius_m).latitude();
+    let east = origin.destination(90.0, radius_m).longitude();
+    let south = origin.destination(180.0, radius_m).latitude();
+    let west = origin.destination(270.0, radius_m).longitude();
+
+    let Schema { lat, lon, .. } = navdata.schema;
+    let mut stmt = navdata.connection.prepare(&format!(
+        "
+select airport.airport_id, ident, airport.{lat}, airport.{lon},
+       airport_coords.left_lonx, airport_coords.right_lonx,
+       airport_coords.bottom_laty, airport_coords.top_laty
+  from airport
+  join airport_coords on airport.airport_id = airport_coords.airport_id
+  where airport_coords.left_lonx <= ?1 and airport_coords.right_lonx >= ?2 and
+        airport_coords.bottom_laty <= ?3 and airport_coords.top_laty >= ?4;
+    "
+    ))?;
+    let rows = stmt.query_map([east, west, north, south], |row| {
+        let airport = Airport {
+            id: row.get(0)?,
+            ident: row.get(1)?,
+            position: LatLon::new(row.get(2)?, row.get(3)?),
+        };
+        let (left, right, bottom, top): (f64, f64, f64, f64) =
+            (row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?);
+        let contains_origin = left <= origin.longitude()
+            && right >= origin.longitude()
+            && bottom <= origin.latitude()
+            && top >= origin.latitude();
+        Ok((airport, contains_origin))
+    })?;
+
+    let mut airports = vec![];
+    for row in rows {
+        let (airport, contains_origin) = row?;
+        let distance = origin.distance(&airport.position);
+        if contains_origin || distance <= radius_m {
+            airports.push((distance, airport));
+        }
+    }
+    airports.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    Ok(airports.into_iter().map(|(_, airport)| airport).collect())
+}
+
+/// Paphos, Larnaca, Ercan and Akrotiri with their bounds, for testing.
+#[cfg(test)]
+pub const TEST_AIRPORTS: &str = "
+create table airport (
+    airport_id integer primary key, ident text, laty real, lonx real,
+    left_lonx real, right_lonx real, bottom_laty real, top_laty real
+);
+insert into airport values
+    (1, 'LCPH', 34.717778, 32.485556, 32.46, 32.51, 34.70, 34.73),
+    (2, 'LCLK', 34.875, 33.624722, 33.60, 33.65, 34.86, 34.89),
+    (3, 'LCEN', 35.1525, 33.496389, 33.47, 33.52, 35.14, 35.17),
+    (4, 'LCRA', 34.590278, 32.987778, 32.96, 33.01, 34.57, 34.61);
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The same airports, without their bounds.
+    const AIRPORTS_WITHOUT_BOUNDS: &str = "
+create table airport (
+    airport_id integer primary key, ident text, laty real, lonx real
+);
+insert into airport values
+    (1, 'LCPH', 34.717778, 32.485556),
+    (2, 'LCLK', 34.875, 33.624722),
+    (3, 'LCEN', 35.1525, 33.496389),
+    (4, 'LCRA', 34.590278, 32.987778);
+";
+
+    /// The same airports, with the columns named without the axis.
+    const AIRPORTS_LAT_LON: &str = "
+create table airport (
+    airport_id integer primary key, ident text, lat real, lon real,
+    left_lon real, right_lon real, bottom_lat real, top_lat real
+);
+insert into airport values
+    (1, 'LCPH', 34.717778, 32.485556, 32.46, 32.51, 34.70, 34.73),
+    (2, 'LCLK', 34.875, 33.624722, 33.60, 33.65, 34.86, 34.89),
+    (3, 'LCEN', 35.1525, 33.496389, 33.47, 33.52, 35.14, 35.17),
+    (4, 'LCRA', 34.590278, 32.987778, 32.96, 33.01, 34.57, 34.61);
+";
+
+    fn navdata_from(sql: &str) -> Navdata {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection.execute_batch(sql).unwrap();
+        from_connection(connection).unwrap()
+    }
+
+    fn navdata() -> Navdata {
+        navdata_from(TEST_AIRPORTS)
+    }
+
+    fn idents(airports: &[Airport]) -> Vec<&str> {
+        airports.iter().map(|a| a.ident.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search_all_within_sorted_by_distance() {
+        let navdata = navdata();
+        // Limassol, between Paphos and Akrotiri
+        let origin = LatLon::new(34.68, 33.04);
+        let airports = search_all_within(&navdata, origin, 100_000.0).unwrap();
+        assert_eq!(vec!["LCRA", "LCPH", "LCLK", "LCEN"], idents(&airports));
+
+        let airports = search_all_within(&navdata, origin, 55_000.0).unwrap();
+        assert_eq!(vec!["LCRA", "LCPH"], idents(&airports));
+    }
+
+    #[test]
+    fn test_search_within_bounds() {
+        let navdata = navdata();
+        let airport = search_within(&navdata, LatLon::new(34.72, 32.49)).unwrap();
+        assert_eq!("LCPH", airport.unwrap().ident);
+        assert!(search_within(&navdata, LatLon::new(34.68, 33.04))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_find_airport() {
+        let navdata = navdata();
+        let airport = find_airport(&navdata, "LCLK").unwrap().unwrap();
+        assert_eq!(2, airport.id);
+        assert_eq!(34.875, airport.position.latitude());
+        assert!(find_airport(&navdata, "EGLL").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_detect_schema() {
+        assert_eq!(Schema::LATY_LONX, navdata_from(TEST_AIRPORTS).schema);
+        assert_eq!(Schema::LAT_LON, navdata_from(AIRPORTS_LAT_LON).schema);
+    }
+
+    #[test]
+    fn test_lat_lon_schema() {
+        let navdata = navdata_from(AIRPORTS_LAT_LON);
+        let airport = search_within(&navdata, LatLon::new(34.72, 32.49)).unwrap();
+        assert_eq!("LCPH", airport.unwrap().ident);
+        let origin = LatLon::new(34.68, 33.04);
+        let airports = search_all_within(&navdata, origin, 55_000.0).unwrap();
+        assert_eq!(vec!["LCRA", "LCPH"], idents(&airports));
+        let airport = find_airport(&navdata, "LCLK").unwrap().unwrap();
+        assert_eq!(34.875, airport.position.latitude());
+        assert_eq!(33.624722, airport.position.longitude());
+    }
+
+    #[test]
+    fn test_without_bounds() {
+        let navdata = navdata_from(AIRPORTS_WITHOUT_BOUNDS);
+        assert!(!navdata.schema.bounds);
+        // on the runway, about 1.5km from the airport's position
+        let airport = search_within(&navdata, LatLon::new(34.71, 32.47)).unwrap();
+        assert_eq!("LCPH", airport.unwrap().ident);
+        assert!(search_within(&navdata, LatLon::new(34.68, 33.04))
+            .unwrap()
+            .is_none());
+        let airports = search_all_within(&navdata, LatLon::new(34.68, 33.04), 55_000.0).unwrap();
+        assert_eq!(vec!["LCRA", "LCPH"], idents(&airports));
+    }
+
+    #[test]
+    fn test_check_airports() {
+        let navdata = rusqlite::Connection::open_in_memory().unwrap();
+        navdata.execute_batch(TEST_AIRPORTS).unwrap();
+        assert_eq!(4, check_airports(&navdata).unwrap());
+
+        let navdata = rusqlite::Connection::open_in_memory().unwrap();
+        navdata.execute_batch(AIRPORTS_LAT_LON).unwrap();
+        assert_eq!(4, check_airports(&navdata).unwrap());
+    }
+
+    #[test]
+    fn test_check_airports_malformed() {
+        let navdata = rusqlite::Connection::open_in_memory().unwrap();
+        let error = check_airports(&navdata).unwrap_err();
+        assert_eq!("missing airport table", error.to_string());
+
+        navdata
+            .execute_batch(
+                "create table airport (airport_id integer primary key, laty real, lonx real);",
+            )
+            .unwrap();
+        let error = check_airports(&navdata).unwrap_err();
+        assert_eq!("airport table is missing columns: ident", error.to_string());
+
+        let navdata = rusqlite::Connection::open_in_memory().unwrap();
+        navdata
+            .execute_batch("create table airport (airport_id integer primary key, ident text, latitude real, longitude real);")
+            .unwrap();
+        let error = check_airports(&navdata).unwrap_err();
+        assert_eq!(
+            "airport table has neither laty/lonx nor lat/lon columns",
+            error.to_string()
+        );
+        assert!(from_connection(navdata).is_err());
+    }
+}
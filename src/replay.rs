@@ -0,0 +1,210 @@
+use crate::aircraft::Aircraft;
+use crate::sim_connection::{SimConnection, SimMessage};
+use log::error;
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, Lines, Write},
+    path::Path,
+    thread,
+    time::Duration,
+};
+use xp_sim_data::SimData;
+
+/// How often the X-Plane plugin sends a sample, i.e. the real time between
+/// the recorded samples.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Plays back `SimData` records from a file, in the CSV format the X-Plane
+/// plugin streams, as if they came from a running sim.
+///
+/// The records are played back `speed` times faster than they were
+/// recorded. The flights are still timed by the clock, so they come out
+/// shorter when sped up.
+pub struct FileReplay<R> {
+    lines: Lines<R>,
+    delay: Duration,
+    // the line number of the last record read
+    line: usize,
+    opened: bool,
+    finished: bool,
+}
+
+impl FileReplay<BufReader<File>> {
+    pub fn open(path: &Path, speed: f64) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_reader(BufReader::new(File::open(path)?), speed))
+    }
+}
+
+impl<R: BufRead> FileReplay<R> {
+    pub fn from_reader(reader: R, speed: f64) -> Self {
+        Self {
+            lines: reader.lines(),
+            delay: SAMPLE_INTERVAL.div_f64(speed),
+            line: 0,
+            opened: false,
+            finished: false,
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<SimData>, Box<dyn Error>> {
+        for line in self.lines.by_ref() {
+            self.line += 1;
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            return SimData::from_csv(&line)
+                .map(Some)
+                .map_err(|e| format!("line {}: {e}", self.line).into());
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> SimConnection for FileReplay<R> {
+    type Error = Box<dyn Error>;
+
+    fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+        if !self.opened {
+            self.opened = true;
+            return Ok(SimMessage::Open);
+        }
+        if self.finished {
+            return Ok(SimMessage::Quit);
+        }
+        match self.next_record()? {
+            Some(sim_data) => {
+                thread::sleep(self.delay);
+                Ok(SimMessage::SimData(Aircraft::from(sim_data)))
+            }
+            None => {
+                self.finished = true;
+                Ok(SimMessage::Quit)
+            }
+        }
+    }
+
+    /// There's nothing to reconnect to, closing ends the replay.
+    fn close(&mut self) {
+        self.finished = true;
+    }
+}
+
+/// Writes every sample received from the sim to a file, in the format
+/// `FileReplay` reads, passing all the messages through.
+pub struct Recorder<S, W> {
+    sim: S,
+    writer: W,
+}
+
+impl<S: SimConnection> Recorder<S, File> {
+    /// Record to `path`, replacing any previous recording there.
+    pub fn create(sim: S, path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(sim, File::create(path)?))
+    }
+}
+
+impl<S: SimConnection, W: Write> Recorder<S, W> {
+    pub fn new(sim: S, writer: W) -> Self {
+        Self { sim, writer }
+    }
+
+    fn record(&mut self, aircraft: &Aircraft) -> Result<(), Box<dyn Error>> {
+        self.writer
+            .write_all(SimData::from(aircraft).to_csv()?.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<S: SimConnection, W: Write> SimConnection for Recorder<S, W> {
+    type Error = S::Error;
+
+    fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+        let msg = self.sim.next_message()?;
+        let samples = match &msg {
+            SimMessage::SimData(aircraft) => std::slice::from_ref(aircraft),
+            SimMessage::SimDataBatch(batch) => batch.as_slice(),
+            _ => &[],
+        };
+        for aircraft in samples {
+            // losing the recording shouldn't stop the flight from being logged
+            if let Err(e) = self.record(aircraft) {
+                error!("Failed to record sample: {e}");
+            }
+        }
+        Ok(msg)
+    }
+
+    fn close(&mut self) {
+        self.sim.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = "\
+C172,Cessna 172,N172SP,34.717778,32.485556,true,true,0,0,0\r
+C172,Cessna 172,N172SP,34.718,32.486,true,true,12.5,0,12.5\r
+
+C172,Cessna 172,N172SP,34.72,32.49,true,false,65,150.5,70\r
+";
+
+    #[test]
+    fn test_replay_in_order() {
+        let mut replay = FileReplay::from_reader(RECORDING.as_bytes(), f64::INFINITY);
+        assert!(matches!(replay.next_message(), Ok(SimMessage::Open)));
+
+        let mut samples = vec![];
+        loop {
+            match replay.next_message() {
+                Ok(SimMessage::SimData(aircraft)) => {
+                    samples.push((aircraft.ground_speed, aircraft.on_ground))
+                }
+                Ok(SimMessage::Quit) => break,
+                msg => panic!("unexpected message: {msg:?}"),
+            }
+        }
+        assert_eq!(vec![(0.0, true), (12.5, true), (65.0, false)], samples);
+        assert!(matches!(replay.next_message(), Ok(SimMessage::Quit)));
+    }
+
+    #[test]
+    fn test_replay_malformed_record() {
+        let recording = "C172,Cessna 172,N172SP,34.717778,32.485556,true,true,0,0,0\nnonsense\n";
+        let mut replay = FileReplay::from_reader(recording.as_bytes(), f64::INFINITY);
+        replay.next_message().unwrap();
+        replay.next_message().unwrap();
+        let error = replay.next_message().unwrap_err();
+        assert!(error.to_string().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn test_recorder_round_trip() {
+        let mut recording = vec![];
+        let replay = FileReplay::from_reader(RECORDING.as_bytes(), f64::INFINITY);
+        let mut recorder = Recorder::new(replay, &mut recording);
+        let mut messages = 0;
+        while !matches!(recorder.next_message(), Ok(SimMessage::Quit)) {
+            messages += 1;
+        }
+        // Open and the three samples
+        assert_eq!(4, messages);
+
+        let recording = String::from_utf8(recording).unwrap();
+        assert_eq!(3, recording.lines().count());
+        let expected: Vec<_> = RECORDING
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| SimData::from_csv(line).unwrap())
+            .collect();
+        let recorded: Vec<_> = recording
+            .lines()
+            .map(|line| SimData::from_csv(line).unwrap())
+            .collect();
+        assert_eq!(expected, recorded);
+    }
+}
@@ -1,16 +1,147 @@
 use crate::aircraft::Aircraft;
 
-#[derive(Debug)]
+/// Messages received from the sim.
+///
+/// A connection starts with `Connecting`, sent until the sim is reachable,
+/// followed by `Open` once connected. `SimData` is then sent for each sample,
+/// or `SimDataBatch` for several that arrived at once, with `Waiting` when the
+/// sim has nothing new. When the sim goes away `Quit`
+/// is sent, after which the connection is retried, starting with `Connecting`
+/// again.
+#[derive(Clone, Debug, PartialEq)]
 pub enum SimMessage {
+    Connecting,
     Open,
     Quit,
     SimData(Aircraft),
+    /// Samples that arrived together, oldest first.
+    SimDataBatch(Vec<Aircraft>),
     Waiting,
     Unknown,
 }
 
+/// The sims the logbook can connect to, picked on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimChoice {
+    Msfs,
+    Xp12,
+    /// Replaying samples recorded from X-Plane.
+    File,
+}
+
+impl SimChoice {
+    pub const ALL: [SimChoice; 3] = [SimChoice::Msfs, SimChoice::Xp12, SimChoice::File];
+
+    /// Pick the sim from the first command line argument, if there's one.
+    pub fn from_arg(arg: Option<&str>) -> Result<Self, String> {
+        let arg = arg.ok_or("USAGE: logbook.exe <SIM NAME>, valid options: MSFS, XP12, FILE")?;
+        arg.parse()
+    }
+}
+
+impl std::fmt::Display for SimChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SimChoice::Msfs => "MSFS",
+            SimChoice::Xp12 => "XP12",
+            SimChoice::File => "FILE",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl std::str::FromStr for SimChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SimChoice::ALL
+            .into_iter()
+            .find(|choice| choice.to_string() == s)
+            .ok_or_else(|| format!("invalid sim provided: {s}, valid options: MSFS, XP12, FILE"))
+    }
+}
+
 pub trait SimConnection {
     type Error;
 
     fn next_message(&mut self) -> Result<SimMessage, Self::Error>;
+
+    /// Drop the connection, the next message starts reconnecting.
+    fn close(&mut self);
+}
+
+impl<S: SimConnection + ?Sized> SimConnection for Box<S> {
+    type Error = S::Error;
+
+    fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+        (**self).next_message()
+    }
+
+    fn close(&mut self) {
+        (**self).close()
+    }
+}
+
+/// A connection sending a scripted sequence of messages, then `Quit` once
+/// they've run out, for testing without a sim.
+#[cfg(test)]
+pub struct StubConnection(std::collections::VecDeque<SimMessage>);
+
+#[cfg(test)]
+impl StubConnection {
+    pub fn new(messages: impl IntoIterator<Item = SimMessage>) -> Self {
+        Self(messages.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+impl SimConnection for StubConnection {
+    type Error = Box<dyn std::error::Error>;
+
+    fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+        Ok(self.0.pop_front().unwrap_or(SimMessage::Quit))
+    }
+
+    fn close(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::LatLon;
+
+    #[test]
+    fn test_sim_choice_from_arg() {
+        assert_eq!(Ok(SimChoice::Msfs), SimChoice::from_arg(Some("MSFS")));
+        assert_eq!(Ok(SimChoice::Xp12), SimChoice::from_arg(Some("XP12")));
+        assert_eq!(Ok(SimChoice::File), SimChoice::from_arg(Some("FILE")));
+        assert_eq!(
+            Err(String::from(
+                "invalid sim provided: xp11, valid options: MSFS, XP12, FILE"
+            )),
+            SimChoice::from_arg(Some("xp11"))
+        );
+        assert!(SimChoice::from_arg(None)
+            .unwrap_err()
+            .starts_with("USAGE: logbook.exe <SIM NAME>"));
+    }
+
+    #[test]
+    fn test_compare_sim_data() {
+        let aircraft = Aircraft {
+            title: String::from("Cessna 172"),
+            position: LatLon::new(34.717778, 32.485556),
+            ground_speed: 12.5,
+            ..Aircraft::default()
+        };
+        let msg = SimMessage::SimData(aircraft.clone());
+        assert_eq!(msg, msg.clone());
+
+        let moved = SimMessage::SimData(Aircraft {
+            position: LatLon::new(34.72, 32.49),
+            ..aircraft
+        });
+        assert_ne!(msg, moved);
+        assert_ne!(SimMessage::Open, moved);
+    }
 }
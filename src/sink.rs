@@ -0,0 +1,422 @@
+use crate::flight::Flight;
+use crate::kml;
+use crate::logbook::{Logbook, Rotation, DATE_FORMAT};
+use crate::sqlite_logbook::SqliteLogbook;
+use log::{error, info};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// Somewhere completed flights are sent to, e.g. the CSV logbook.
+pub trait FlightSink {
+    fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>>;
+
+    /// The flight in progress moved on to its next phase, or there's no
+    /// longer one with `None`, e.g. once it's completed.
+    fn on_current(&mut self, _flight: Option<&Flight>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Commit anything buffered, called before exiting. Flushing again
+    /// without any new flights does nothing.
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl FlightSink for Logbook {
+    fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+        self.log(flight)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Logbook::flush(self)
+    }
+}
+
+/// Logs each flight to a CSV logbook for its aircraft in a directory, e.g.
+/// `CL60.csv`, creating the logbook on its first flight.
+pub struct PerAircraftLogbook {
+    dir: PathBuf,
+    delimiter: u8,
+    time_format: String,
+    logbooks: HashMap<PathBuf, Logbook>,
+}
+
+impl PerAircraftLogbook {
+    pub fn new(dir: &Path, delimiter: u8, time_format: &str) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            delimiter,
+            time_format: time_format.to_string(),
+            logbooks: HashMap::new(),
+        }
+    }
+}
+
+impl FlightSink for PerAircraftLogbook {
+    fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+        let path = self.dir.join(format!("{}.csv", aircraft_file_name(flight)));
+        let logbook = match self.logbooks.entry(path) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let logbook = Logbook::new(entry.key(), self.delimiter, &self.time_format);
+                entry.insert(logbook)
+            }
+        };
+        logbook.log(flight)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        for logbook in self.logbooks.values_mut() {
+            logbook.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Name the aircraft's logbook after its ICAO code, or its title if the sim
+/// didn't give one, keeping only characters that are safe in a file name.
+fn aircraft_file_name(flight: &Flight) -> String {
+    let name = match flight.aircraft.icao.trim() {
+        "" => flight.aircraft.title.trim(),
+        icao => icao,
+    };
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        String::from("unknown")
+    } else {
+        name
+    }
+}
+
+impl FlightSink for SqliteLogbook {
+    fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+        self.log(flight)
+    }
+
+    fn on_current(&mut self, flight: Option<&Flight>) -> Result<(), Box<dyn Error>> {
+        self.set_current_flight(flight)
+    }
+}
+
+/// Exports each flight to its own KML file in a directory, optionally with
+/// the track simplified to within `tolerance_m` meters.
+pub struct KmlExport {
+    pub dir: PathBuf,
+    pub tolerance_m: Option<f64>,
+}
+
+impl FlightSink for KmlExport {
+    fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+        let path = kml::export(flight, &self.dir, self.tolerance_m)?;
+        info!("Flight exported to {}", path.display());
+        Ok(())
+    }
+}
+
+/// The formats completed flights can be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    Kml,
+    Sqlite,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "kml" => Ok(Format::Kml),
+            "sqlite" => Ok(Format::Sqlite),
+            _ => Err(format!(
+                "invalid format: {s}, valid options: csv, kml, sqlite"
+            )),
+        }
+    }
+}
+
+/// Parse a comma separated list of formats, ignoring duplicates.
+pub fn parse_formats(s: &str) -> Result<Vec<Format>, String> {
+    let mut formats = vec![];
+    for format in s.split(',') {
+        let format = format.trim().parse()?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    Ok(formats)
+}
+
+/// How the sinks write the flights.
+#[derive(Clone, Debug)]
+pub struct SinkOptions {
+    /// Split the CSV logbook into a file for each aircraft.
+    pub per_aircraft: bool,
+    /// What the CSV logbook's fields are separated by.
+    pub delimiter: u8,
+    /// The format of the times in the CSV logbook.
+    pub time_format: String,
+    /// Simplify the exported tracks to within this many meters.
+    pub track_tolerance_m: Option<f64>,
+    /// Start a new CSV logbook every day or month.
+    pub rotation: Option<Rotation>,
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        Self {
+            per_aircraft: false,
+            delimiter: b',',
+            time_format: DATE_FORMAT.to_string(),
+            track_tolerance_m: None,
+            rotation: None,
+        }
+    }
+}
+
+/// Create a sink for each format, writing into `dir`.
+pub fn sinks(
+    formats: &[Format],
+    dir: &Path,
+    options: &SinkOptions,
+) -> Result<Vec<Box<dyn FlightSink>>, Box<dyn Error>> {
+    let mut sinks: Vec<Box<dyn FlightSink>> = vec![];
+    for format in formats {
+        match format {
+            Format::Csv if options.per_aircraft => sinks.push(Box::new(PerAircraftLogbook::new(
+                dir,
+                options.delimiter,
+                &options.time_format,
+            ))),
+            Format::Csv => {
+                let path = dir.join("logbook.csv");
+                sinks.push(Box::new(match options.rotation {
+                    Some(rotation) => {
+                        Logbook::rotating(&path, options.delimiter, &options.time_format, rotation)
+                    }
+                    None => Logbook::new(&path, options.delimiter, &options.time_format),
+                }))
+            }
+            Format::Kml => sinks.push(Box::new(KmlExport {
+                dir: dir.to_path_buf(),
+                tolerance_m: options.track_tolerance_m,
+            })),
+            Format::Sqlite => {
+                sinks.push(Box::new(SqliteLogbook::open(&dir.join("logbook.sqlite"))?))
+            }
+        }
+    }
+    Ok(sinks)
+}
+
+/// Send the flight to every sink, a failing sink doesn't stop the flight from
+/// reaching the others.
+pub fn complete(sinks: &mut [Box<dyn FlightSink>], flight: &Flight) {
+    for sink in sinks {
+        if let Err(e) = sink.on_complete(flight) {
+            error!("Failed to store flight: {e}");
+        }
+    }
+}
+
+/// Let every sink know about the flight in progress, a failing sink doesn't
+/// stop the others from hearing about it.
+pub fn current(sinks: &mut [Box<dyn FlightSink>], flight: Option<&Flight>) {
+    for sink in sinks {
+        if let Err(e) = sink.on_current(flight) {
+            error!("Failed to store the current flight: {e}");
+        }
+    }
+}
+
+/// Flush every sink, a failing sink doesn't stop the others from being
+/// flushed.
+pub fn flush(sinks: &mut [Box<dyn FlightSink>]) {
+    for sink in sinks {
+        if let Err(e) = sink.flush() {
+            error!("Failed to flush flights: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::Aircraft;
+    use crate::logbook::header;
+    use geo::LatLon;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct MockSink(Rc<RefCell<Vec<String>>>);
+
+    impl FlightSink for MockSink {
+        fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().push(flight.aircraft.title.clone());
+            Ok(())
+        }
+    }
+
+    struct FailingSink;
+
+    impl FlightSink for FailingSink {
+        fn on_complete(&mut self, _: &Flight) -> Result<(), Box<dyn Error>> {
+            Err("disk full".into())
+        }
+
+        fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+            Err("disk full".into())
+        }
+    }
+
+    /// Only keeps the flights once flushed.
+    struct BufferingSink {
+        buffer: Vec<String>,
+        durable: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl FlightSink for BufferingSink {
+        fn on_complete(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+            self.buffer.push(flight.aircraft.title.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+            self.durable.borrow_mut().append(&mut self.buffer);
+            Ok(())
+        }
+    }
+
+    fn flight() -> Flight {
+        Flight::new(&Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position: LatLon::new(34.717778, 32.485556),
+            on_ground: true,
+            ..Aircraft::default()
+        })
+    }
+
+    #[test]
+    fn test_complete_reaches_every_sink() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut sinks: Vec<Box<dyn FlightSink>> =
+            vec![Box::new(FailingSink), Box::new(MockSink(received.clone()))];
+        complete(&mut sinks, &flight());
+        assert_eq!(vec![String::from("Challenger 650")], *received.borrow());
+    }
+
+    #[test]
+    fn test_flush() {
+        let durable = Rc::new(RefCell::new(vec![]));
+        let mut sinks: Vec<Box<dyn FlightSink>> = vec![
+            Box::new(FailingSink),
+            Box::new(BufferingSink {
+                buffer: vec![],
+                durable: durable.clone(),
+            }),
+        ];
+        complete(&mut sinks, &flight());
+        assert!(durable.borrow().is_empty());
+
+        flush(&mut sinks);
+        assert_eq!(vec![String::from("Challenger 650")], *durable.borrow());
+        flush(&mut sinks);
+        assert_eq!(1, durable.borrow().len());
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(Ok(vec![Format::Csv]), parse_formats("csv"));
+        assert_eq!(
+            Ok(vec![Format::Kml, Format::Csv]),
+            parse_formats("kml, csv,kml")
+        );
+        assert_eq!(Ok(vec![Format::Sqlite]), parse_formats("sqlite"));
+        assert!(parse_formats("csv,gpx").is_err());
+        assert!(parse_formats("").is_err());
+    }
+
+    #[test]
+    fn test_every_format_receives_the_flight() {
+        let dir = std::env::temp_dir().join(format!("logbook-sinks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut sinks = sinks(&[Format::Csv, Format::Kml], &dir, &SinkOptions::default()).unwrap();
+        complete(&mut sinks, &flight());
+
+        let csv = std::fs::read_to_string(dir.join("logbook.csv")).unwrap();
+        assert_eq!(2, csv.lines().count());
+        assert!(csv.lines().nth(1).unwrap().starts_with("Challenger 650,"));
+        let kml_files = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("kml".as_ref()))
+            .count();
+        assert_eq!(1, kml_files);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_aircraft_file_name() {
+        let mut flight = flight();
+        assert_eq!("CL60", aircraft_file_name(&flight));
+        flight.aircraft.icao = String::new();
+        flight.aircraft.title = String::from("Cessna 172 / G1000");
+        assert_eq!("Cessna_172___G1000", aircraft_file_name(&flight));
+        flight.aircraft.title = String::new();
+        assert_eq!("unknown", aircraft_file_name(&flight));
+    }
+
+    #[test]
+    fn test_per_aircraft_logbooks() {
+        let dir = std::env::temp_dir().join(format!("logbook-per-aircraft-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut other = flight();
+        other.aircraft.title = String::from("Cessna 172");
+        other.aircraft.icao = String::from("C172");
+        let mut sinks = sinks(
+            &[Format::Csv],
+            &dir,
+            &SinkOptions {
+                per_aircraft: true,
+                ..SinkOptions::default()
+            },
+        )
+        .unwrap();
+        complete(&mut sinks, &flight());
+        complete(&mut sinks, &other);
+        complete(&mut sinks, &flight());
+
+        let header = header().collect::<Vec<_>>().join(",");
+        let challenger = std::fs::read_to_string(dir.join("CL60.csv")).unwrap();
+        assert_eq!(Some(header.as_str()), challenger.lines().next());
+        assert_eq!(3, challenger.lines().count());
+        let cessna = std::fs::read_to_string(dir.join("C172.csv")).unwrap();
+        assert_eq!(Some(header.as_str()), cessna.lines().next());
+        assert_eq!(2, cessna.lines().count());
+        assert!(cessna
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("Cessna 172,C172,"));
+        assert!(!dir.join("logbook.csv").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
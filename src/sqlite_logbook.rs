@@ -0,0 +1,169 @@
+use crate::flight::Flight;
+use crate::logbook::{header, COLUMNS};
+use log::warn;
+use std::{error::Error, path::Path};
+
+/// The logbook's columns, quoted for use in SQL, e.g. `"Aircraft Name"`.
+fn quoted_columns() -> Vec<String> {
+    header().map(|column| format!("\"{column}\"")).collect()
+}
+
+/// A parameter for each of the logbook's columns, e.g. `?1, ?2, ?3`.
+fn placeholders() -> String {
+    (1..=COLUMNS.len())
+        .map(|i| format!("?{i}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The logbook in an SQLite database, with the same columns as the CSV
+/// logbook. Completed flights go in the `flights` table, and the flight in
+/// progress is kept as the only row of `current_flight` until it's completed.
+pub struct SqliteLogbook {
+    connection: rusqlite::Connection,
+}
+
+impl SqliteLogbook {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let logbook = Self::from_connection(rusqlite::Connection::open(path)?)?;
+        if let Some(flight) = logbook.current_flight()? {
+            warn!(
+                "{} has a flight in the {} that was still in progress when last exiting, it will be replaced by the next one.",
+                path.display(),
+                flight[0].as_deref().unwrap_or("unknown aircraft")
+            );
+        }
+        Ok(logbook)
+    }
+
+    fn from_connection(connection: rusqlite::Connection) -> Result<Self, Box<dyn Error>> {
+        let columns = quoted_columns()
+            .into_iter()
+            .map(|column| format!("{column} text"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        connection.execute_batch(&format!(
+            "
+            create table if not exists flights ({columns});
+            create table if not exists current_flight (
+                id integer primary key check (id = 1), {columns}
+            );
+        "
+        ))?;
+        let logbook = Self { connection };
+        logbook.add_missing_columns("flights")?;
+        logbook.add_missing_columns("current_flight")?;
+        Ok(logbook)
+    }
+
+    /// Add the columns added to the logbook since the table was created, so
+    /// that existing databases can still be written to.
+    fn add_missing_columns(&self, table: &str) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self
+            .connection
+            .prepare(&format!("select name from pragma_table_info('{table}')"))?;
+        let existing = stmt
+            .query_map((), |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for column in header().filter(|column| !existing.iter().any(|c| c == column)) {
+            self.connection.execute(
+                &format!("alter table {table} add column \"{column}\" text"),
+                (),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Keep `flight` as the flight in progress, or clear it with `None`.
+    pub fn set_current_flight(&mut self, flight: Option<&Flight>) -> Result<(), Box<dyn Error>> {
+        let Some(flight) = flight else {
+            self.connection.execute("delete from current_flight", ())?;
+            return Ok(());
+        };
+        let columns = quoted_columns().join(", ");
+        let params = placeholders();
+        self.connection.execute(
+            &format!("insert or replace into current_flight (id, {columns}) values (1, {params})"),
+            rusqlite::params_from_iter(flight.to_record()),
+        )?;
+        Ok(())
+    }
+
+    /// Read back the flight in progress, its fields in the order of the
+    /// logbook's columns, if there's one.
+    pub fn current_flight(&self) -> Result<Option<Vec<Option<String>>>, Box<dyn Error>> {
+        let columns = quoted_columns().join(", ");
+        let mut stmt = self.connection.prepare(&format!(
+            "select {columns} from current_flight where id = 1"
+        ))?;
+        let mut rows = stmt.query_map((), |row| {
+            (0..COLUMNS.len())
+                .map(|i| row.get::<_, Option<String>>(i))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Add the completed flight to the `flights` table, and clear it from
+    /// `current_flight` along with it.
+    pub fn log(&mut self, flight: &Flight) -> Result<(), Box<dyn Error>> {
+        let columns = quoted_columns().join(", ");
+        let params = placeholders();
+        let tx = self.connection.transaction()?;
+        tx.execute(
+            &format!("insert into flights ({columns}) values ({params})"),
+            rusqlite::params_from_iter(flight.to_record()),
+        )?;
+        tx.execute("delete from current_flight", ())?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::Aircraft;
+
+    fn count(logbook: &SqliteLogbook, table: &str) -> i64 {
+        logbook
+            .connection
+            .query_row(&format!("select count(*) from {table}"), (), |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_current_flight_cleared_on_completion() {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        let mut logbook = SqliteLogbook::from_connection(connection).unwrap();
+        assert_eq!(None, logbook.current_flight().unwrap());
+
+        let mut flight = Flight::new(&Aircraft {
+            title: String::from("Challenger 650"),
+            ..Aircraft::default()
+        });
+        logbook.set_current_flight(Some(&flight)).unwrap();
+        flight.aircraft.registration = String::from("C-FAAV");
+        logbook.set_current_flight(Some(&flight)).unwrap();
+        assert_eq!(1, count(&logbook, "current_flight"));
+        assert_eq!(Some(flight.to_record()), logbook.current_flight().unwrap());
+
+        logbook.log(&flight).unwrap();
+        assert_eq!(0, count(&logbook, "current_flight"));
+        assert_eq!(1, count(&logbook, "flights"));
+        assert_eq!(None, logbook.current_flight().unwrap());
+    }
+
+    #[test]
+    fn test_adds_missing_columns() {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch("create table flights (\"Aircraft Name\" text);")
+            .unwrap();
+        let mut logbook = SqliteLogbook::from_connection(connection).unwrap();
+        logbook.log(&Flight::new(&Aircraft::default())).unwrap();
+        assert_eq!(1, count(&logbook, "flights"));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::logbook::duration_to_string;
+use crate::logbook_reader::{FlightRecord, LogbookReader};
+use chrono::{Duration, NaiveDate};
+use log::warn;
+use std::{error::Error, fmt, path::Path};
+
+/// Labels for the block time buckets, each up to (and excluding) the limit in
+/// `BUCKET_LIMITS_MINUTES`, the last one is open ended.
+const BUCKETS: [&str; 4] = ["0-30m", "30-60m", "1-2h", "2h+"];
+const BUCKET_LIMITS_MINUTES: [i64; 3] = [30, 60, 120];
+
+fn bucket(block_time: &Duration) -> usize {
+    let minutes = block_time.num_minutes();
+    BUCKET_LIMITS_MINUTES
+        .iter()
+        .position(|limit| minutes < *limit)
+        .unwrap_or(BUCKET_LIMITS_MINUTES.len())
+}
+
+/// Parse the date given to `--since`, e.g. `2024-01-31`.
+pub fn parse_since(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date: {s}, expected YYYY-MM-DD"))
+}
+
+/// Whether the flight departed on or after `since` (UTC), flights that never
+/// departed aren't.
+fn departed_since(record: &FlightRecord, since: NaiveDate) -> bool {
+    record
+        .departure_time
+        .is_some_and(|departure| departure.date_naive() >= since)
+}
+
+/// Totals over the flights in a logbook.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub flights: usize,
+    pub block_time: Duration,
+    /// Number of flights in each of the `BUCKETS`, by block time.
+    pub durations: [usize; BUCKETS.len()],
+    pub longest: Option<FlightRecord>,
+    pub shortest: Option<FlightRecord>,
+}
+
+impl Stats {
+    pub fn from_records(records: impl IntoIterator<Item = FlightRecord>) -> Self {
+        let mut stats = Stats::default();
+        for record in records {
+            stats.flights += 1;
+            // incomplete flights don't have a block time
+            let Some(block_time) = record.block_time else {
+                continue;
+            };
+            stats.block_time += block_time;
+            stats.durations[bucket(&block_time)] += 1;
+            if stats.longest.as_ref().and_then(|r| r.block_time) < Some(block_time) {
+                stats.longest = Some(record.clone());
+            }
+            if stats
+                .shortest
+                .as_ref()
+                .and_then(|r| r.block_time)
+                .is_none_or(|shortest| block_time < shortest)
+            {
+                stats.shortest = Some(record);
+            }
+        }
+        stats
+    }
+
+    /// Read the stats from a logbook with its times in `time_format`,
+    /// skipping any malformed rows, and if given only over the flights that
+    /// departed `since` then.
+    pub fn read(
+        path: &Path,
+        since: Option<NaiveDate>,
+        delimiter: u8,
+        time_format: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let records = LogbookReader::open(path)?
+            .delimiter(delimiter)
+            .time_format(time_format)
+            .filter_map(|record| match record {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    warn!("Skipping flight: {e}");
+                    None
+                }
+            })
+            .filter(|record| since.is_none_or(|since| departed_since(record, since)));
+        Ok(Self::from_records(records))
+    }
+}
+
+fn describe(record: &FlightRecord) -> String {
+    format!(
+        "{} {}-{}, {}",
+        record.aircraft_name,
+        record.departure_icao.as_deref().unwrap_or("?"),
+        record.arrival_icao.as_deref().unwrap_or("?"),
+        record
+            .block_time
+            .map(|d| duration_to_string(&d))
+            .unwrap_or_default(),
+    )
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Flights: {}", self.flights)?;
+        writeln!(f, "Block time: {}", duration_to_string(&self.block_time))?;
+        writeln!(f)?;
+        writeln!(f, "{:<10}{:>8}", "Duration", "Flights")?;
+        for (label, count) in BUCKETS.iter().zip(self.durations) {
+            writeln!(f, "{:<10}{:>8}", label, count)?;
+        }
+        if let Some(longest) = &self.longest {
+            writeln!(f)?;
+            writeln!(f, "Longest: {}", describe(longest))?;
+        }
+        if let Some(shortest) = &self.shortest {
+            writeln!(f, "Shortest: {}", describe(shortest))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+Aircraft Name,Departure ICAO,Departure Time,Arrival ICAO,Block Time
+Cessna 172,LCPH,2024-01-01 09:00:00,LCPH,0:20
+Challenger 650,LCPH,2024-01-01 12:00:00,LCLK,0:45
+Cessna 172,LCLK,2024-01-02 08:00:00,LCPH,1:00
+Challenger 650,LCLK,2024-01-02 23:30:00,EGLL,5:10
+Challenger 650,EGLL,,,
+";
+
+    fn records() -> impl Iterator<Item = FlightRecord> {
+        let reader = LogbookReader::from_reader(FIXTURE.as_bytes());
+        reader.map(Result::unwrap)
+    }
+
+    fn stats() -> Stats {
+        Stats::from_records(records())
+    }
+
+    #[test]
+    fn test_bucket_counts() {
+        let stats = stats();
+        assert_eq!(5, stats.flights);
+        assert_eq!([1, 1, 1, 1], stats.durations);
+        assert_eq!(Duration::minutes(435), stats.block_time);
+    }
+
+    #[test]
+    fn test_longest_and_shortest() {
+        let stats = stats();
+        assert_eq!(
+            Some(String::from("EGLL")),
+            stats.longest.unwrap().arrival_icao
+        );
+        assert_eq!(
+            Some(Duration::minutes(20)),
+            stats.shortest.unwrap().block_time
+        );
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            Ok(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()),
+            parse_since("2024-01-02")
+        );
+        assert!(parse_since("02/01/2024").is_err());
+    }
+
+    #[test]
+    fn test_since() {
+        let since = parse_since("2024-01-02").unwrap();
+        let stats = Stats::from_records(records().filter(|r| departed_since(r, since)));
+        assert_eq!(2, stats.flights);
+        assert_eq!(Duration::minutes(370), stats.block_time);
+        assert_eq!(
+            Some(String::from("LCLK")),
+            stats.shortest.unwrap().departure_icao
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let table = stats().to_string();
+        assert!(table.contains("Block time: 7:15\n"));
+        assert!(table.contains("30-60m           1\n"));
+        assert!(table.contains("Longest: Challenger 650 LCLK-EGLL, 5:10\n"));
+        assert!(table.contains("Shortest: Cessna 172 LCPH-LCPH, 0:20\n"));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::aircraft::Aircraft;
+use crate::flight::{Flight, FlightState};
+use crate::navdata::Airport;
+use chrono::{DateTime, Utc};
+use geo::LatLon;
+use log::error;
+use std::{
+    error::Error,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+// how long a client has to send its request before the next one is served
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The parts of the flight in progress that are served, taken on every
+/// sample without copying the whole track.
+#[derive(Clone, Debug)]
+pub struct FlightStatus {
+    pub state: FlightState,
+    pub aircraft: String,
+    pub registration: String,
+    pub departure: Option<String>,
+    pub taxi_out: Option<DateTime<Utc>>,
+    pub shutdown: Option<DateTime<Utc>>,
+    /// The last position in the track.
+    pub position: Option<LatLon>,
+    /// See `Track::turn_rate`.
+    pub turn_rate: Option<f64>,
+}
+
+impl From<&Flight> for FlightStatus {
+    fn from(flight: &Flight) -> Self {
+        Self {
+            state: flight.state,
+            aircraft: flight.aircraft.title.clone(),
+            registration: flight.aircraft.registration.clone(),
+            departure: flight
+                .departure
+                .as_ref()
+                .map(|(airport, _)| airport.ident.clone()),
+            taxi_out: flight.taxi_out,
+            shutdown: flight.shutdown,
+            position: flight.track.points().last().map(|point| point.position),
+            turn_rate: flight.track.turn_rate(),
+        }
+    }
+}
+
+/// What's served as the status.
+#[derive(Default)]
+pub struct Status {
+    /// The flight in progress, if there is one.
+    pub flight: Option<FlightStatus>,
+    /// The aircraft as of the latest sample.
+    pub aircraft: Option<Aircraft>,
+    /// Where the pilot intends to fly to, to estimate the time remaining.
+    pub destination: Option<Airport>,
+}
+
+/// The status, shared with the status server.
+pub type SharedStatus = Arc<Mutex<Status>>;
+
+/// `s` as a JSON string, quoted, with the quotes, backslashes and control
+/// characters escaped.
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// The flight's state as JSON, `null` if there isn't a flight in progress.
+///
+/// The block time is the time since taxiing out until `now` (or shutting
+/// down), in seconds, and the position is the last one in the track. The ETA
+/// is the estimated time remaining to the destination in seconds, at the
+/// latest ground speed. The turn rate is in degrees per second between the
+/// last two positions in the track, positive turning right.
+pub fn to_json(status: &Status, now: DateTime<Utc>) -> String {
+    let Some(flight) = &status.flight else {
+        return String::from("null");
+    };
+    let block_time = flight
+        .taxi_out
+        .map(|taxi_out| (flight.shutdown.unwrap_or(now) - taxi_out).num_seconds());
+    let eta = status
+        .aircraft
+        .as_ref()
+        .zip(status.destination.as_ref())
+        .and_then(|(aircraft, destination)| aircraft.time_to(&destination.position));
+    format!(
+        "{{\"state\":{},\"aircraft\":{},\"registration\":{},\"departure\":{},\"block_time_secs\":{},\"position\":{},\"destination\":{},\"eta_secs\":{},\"turn_rate_dps\":{}}}",
+        json_string(&flight.state.to_string()),
+        json_string(&flight.aircraft),
+        json_string(&flight.registration),
+        flight
+            .departure
+            .as_deref()
+            .map_or(String::from("null"), json_string),
+        block_time.map_or(String::from("null"), |secs| secs.to_string()),
+        flight.position.map_or(String::from("null"), |p| format!(
+            "{{\"latitude\":{},\"longitude\":{}}}",
+            p.latitude(),
+            p.longitude()
+        )),
+        status
+            .destination
+            .as_ref()
+            .map_or(String::from("null"), |airport| json_string(&airport.ident)),
+        eta.map_or(String::from("null"), |eta| eta.as_secs().to_string()),
+        flight
+            .turn_rate
+            .map_or(String::from("null"), |rate| format!("{rate:.1}")),
+    )
+}
+
+fn respond(mut stream: TcpStream, status: &SharedStatus) -> Result<(), Box<dyn Error>> {
+    // whatever was requested, the status is all there is to serve
+    let mut request_line = String::new();
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let body = to_json(&status.lock().unwrap(), Utc::now());
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+/// Serve the flight in progress as JSON over HTTP on `addr`, from a
+/// background thread. Returns the address actually bound to.
+pub fn serve(addr: &str, status: SharedStatus) -> Result<SocketAddr, Box<dyn Error>> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(|e| e.into())
+                .and_then(|stream| respond(stream, &status));
+            if let Err(e) = result {
+                error!("Status request failed: {e}");
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::NM_TO_M;
+    use crate::flight::FlightState;
+    use crate::track::TrackPoint;
+    use chrono::{Duration, TimeZone};
+    use geo::LatLon;
+    use std::io::Read;
+
+    fn aircraft() -> Aircraft {
+        Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position: LatLon::new(34.717778, 32.485556),
+            on_ground: true,
+            ..Aircraft::default()
+        }
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!("null", to_json(&Status::default(), Utc::now()));
+
+        let taxi_out = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let mut flight = Flight::new(&aircraft());
+        flight.aircraft.title = String::from("Cessna \"172\"");
+        flight.state = FlightState::Taxi;
+        flight.taxi_out = Some(taxi_out);
+        flight.track.push(TrackPoint {
+            time: taxi_out,
+            position: LatLon::new(34.7, 32.5),
+            heading: 0.0,
+            elevation_ft: 0.0,
+        });
+        flight.track.push(TrackPoint {
+            time: taxi_out + Duration::seconds(10),
+            position: LatLon::new(34.7, 32.5),
+            heading: 30.0,
+            elevation_ft: 0.0,
+        });
+        let status = Status {
+            flight: Some(FlightStatus::from(&flight)),
+            ..Status::default()
+        };
+        assert_eq!(
+            "{\"state\":\"taxi\",\"aircraft\":\"Cessna \\\"172\\\"\",\"registration\":\"C-FAAV\",\"departure\":null,\"block_time_secs\":300,\"position\":{\"latitude\":34.7,\"longitude\":32.5},\"destination\":null,\"eta_secs\":null,\"turn_rate_dps\":3.0}",
+            to_json(&status, taxi_out + Duration::minutes(5))
+        );
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!("\"Cessna 172\"", json_string("Cessna 172"));
+        assert_eq!("\"\\\"172\\\"\"", json_string("\"172\""));
+        assert_eq!("\"C:\\\\X-Plane\"", json_string("C:\\X-Plane"));
+        assert_eq!("\"a\\nb\\tc\\r\"", json_string("a\nb\tc\r"));
+        assert_eq!("\"\\u0000\\u001b\\u007f\"", json_string("\0\x1b\x7f"));
+        assert_eq!("\"\\u0085\"", json_string("\u{85}"));
+        assert_eq!("\"Zürich\"", json_string("Zürich"));
+    }
+
+    #[test]
+    fn test_to_json_eta() {
+        let aircraft = Aircraft {
+            ground_speed: 120.0,
+            ..aircraft()
+        };
+        let destination = Airport {
+            id: 2,
+            ident: String::from("LCLK"),
+            position: aircraft.position.destination(90.0, 60.0 * NM_TO_M),
+        };
+        let status = Status {
+            flight: Some(FlightStatus::from(&Flight::new(&aircraft))),
+            aircraft: Some(aircraft),
+            destination: Some(destination),
+        };
+        let json = to_json(&status, Utc::now());
+        assert!(json.contains("\"destination\":\"LCLK\","));
+        let (_, eta) = json.split_once("\"eta_secs\":").unwrap();
+        let (eta, _) = eta.split_once(',').unwrap();
+        let eta: u64 = eta.parse().unwrap();
+        assert!((1799..=1800).contains(&eta));
+    }
+
+    #[test]
+    fn test_serve() {
+        let shared = SharedStatus::default();
+        let addr = serve("127.0.0.1:0", shared.clone()).unwrap();
+        shared.lock().unwrap().flight = Some(FlightStatus::from(&Flight::new(&aircraft())));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(
+            "{\"state\":\"preflight\",\"aircraft\":\"Challenger 650\",\"registration\":\"C-FAAV\",\"departure\":null,\"block_time_secs\":null,\"position\":null,\"destination\":null,\"eta_secs\":null,\"turn_rate_dps\":null}",
+            body
+        );
+    }
+
+    #[test]
+    fn test_serve_after_silent_client() {
+        let shared = SharedStatus::default();
+        let addr = serve("127.0.0.1:0", shared.clone()).unwrap();
+
+        // connects but never sends a request
+        let _silent = TcpStream::connect(addr).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::sim_connection::{SimConnection, SimMessage};
+use std::time::{Duration, Instant};
+
+/// Limits how often `SimData` messages reach the state machine.
+///
+/// Samples arriving less than `interval` after the last surfaced one are
+/// dropped, unless the aircraft's ground, gear or engine state changed, since
+/// those are the transitions the state machine is waiting for. Any other message is
+/// passed through as is, batches included, since all but their latest sample
+/// only go into the track anyway.
+pub struct Throttled<S> {
+    sim: S,
+    interval: Duration,
+    // when the last sample was surfaced, with its on_ground, gear_on_ground
+    // and engine_on
+    last: Option<(Instant, bool, Option<bool>, bool)>,
+}
+
+impl<S: SimConnection> Throttled<S> {
+    pub fn new(sim: S, interval: Duration) -> Self {
+        Self {
+            sim,
+            interval,
+            last: None,
+        }
+    }
+}
+
+impl<S: SimConnection> SimConnection for Throttled<S> {
+    type Error = S::Error;
+
+    fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+        loop {
+            let aircraft = match self.sim.next_message()? {
+                SimMessage::SimData(aircraft) => aircraft,
+                msg => return Ok(msg),
+            };
+            let now = Instant::now();
+            let surface = match self.last {
+                None => true,
+                Some((time, on_ground, gear_on_ground, engine_on)) => {
+                    now.duration_since(time) >= self.interval
+                        || on_ground != aircraft.on_ground
+                        || gear_on_ground != aircraft.gear_on_ground
+                        || engine_on != aircraft.engine_on
+                }
+            };
+            if surface {
+                self.last = Some((
+                    now,
+                    aircraft.on_ground,
+                    aircraft.gear_on_ground,
+                    aircraft.engine_on,
+                ));
+                return Ok(SimMessage::SimData(aircraft));
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.last = None;
+        self.sim.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::Aircraft;
+    use crate::sim_connection::StubConnection;
+    use geo::LatLon;
+
+    fn sample(on_ground: bool) -> SimMessage {
+        SimMessage::SimData(Aircraft {
+            title: String::from("Challenger 650"),
+            icao: String::from("CL60"),
+            registration: String::from("C-FAAV"),
+            position: LatLon::new(34.717778, 32.485556),
+            engine_on: true,
+            on_ground,
+            ..Aircraft::default()
+        })
+    }
+
+    fn surfaced(messages: Vec<SimMessage>) -> Vec<bool> {
+        let stub = StubConnection::new(messages);
+        let mut sim = Throttled::new(stub, Duration::from_secs(60));
+        let mut on_ground = vec![];
+        loop {
+            match sim.next_message() {
+                Ok(SimMessage::SimData(aircraft)) => on_ground.push(aircraft.on_ground),
+                Ok(SimMessage::Quit) => return on_ground,
+                msg => panic!("unexpected message: {msg:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_burst_is_coalesced() {
+        let burst = (0..10).map(|_| sample(true)).collect();
+        assert_eq!(vec![true], surfaced(burst));
+    }
+
+    #[test]
+    fn test_ground_state_change_is_never_dropped() {
+        let messages = vec![
+            sample(true),
+            sample(true),
+            sample(false),
+            sample(false),
+            sample(true),
+        ];
+        assert_eq!(vec![true, false, true], surfaced(messages));
+    }
+
+    #[test]
+    fn test_gear_state_change_is_never_dropped() {
+        let with_gear = |on_ground, gear_on_ground| match sample(on_ground) {
+            SimMessage::SimData(aircraft) => SimMessage::SimData(Aircraft {
+                gear_on_ground: Some(gear_on_ground),
+                ..aircraft
+            }),
+            msg => msg,
+        };
+        // the sim reports the touchdown before the gear does
+        let messages = vec![
+            with_gear(false, false),
+            with_gear(false, false),
+            with_gear(true, false),
+            with_gear(true, true),
+            with_gear(true, true),
+        ];
+        assert_eq!(vec![false, true, true], surfaced(messages));
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use geo::LatLon;
+use std::sync::OnceLock;
+use tzf_rs::DefaultFinder;
+
+/// Local timestamps carry their UTC offset, e.g. `2024-01-01 12:00:00 +02:00`.
+pub const LOCAL_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %:z";
+
+// loading the timezone boundaries is slow, only do it once
+fn finder() -> &'static DefaultFinder {
+    static FINDER: OnceLock<DefaultFinder> = OnceLock::new();
+    FINDER.get_or_init(DefaultFinder::new)
+}
+
+/// The timezone at the given position, if one could be found.
+pub fn timezone_at(position: LatLon) -> Option<Tz> {
+    finder()
+        .get_tz_name(position.longitude(), position.latitude())
+        .parse()
+        .ok()
+}
+
+/// Format `dt` in the local time at `position`, falling back to UTC when the
+/// timezone can't be determined.
+pub fn local_time_to_string(dt: &DateTime<Utc>, position: LatLon) -> String {
+    match timezone_at(position) {
+        Some(tz) => dt.with_timezone(&tz).format(LOCAL_DATE_FORMAT).to_string(),
+        None => dt.format(LOCAL_DATE_FORMAT).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // Paphos, Cyprus
+    fn lcph() -> LatLon {
+        LatLon::new(34.717778, 32.485556)
+    }
+
+    #[test]
+    fn test_timezone_at() {
+        assert_eq!(Some(chrono_tz::Asia::Nicosia), timezone_at(lcph()));
+    }
+
+    #[test]
+    fn test_local_time_to_string() {
+        let winter = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        assert_eq!(
+            "2024-01-01 12:00:00 +02:00",
+            local_time_to_string(&winter, lcph())
+        );
+        let summer = Utc.with_ymd_and_hms(2024, 7, 1, 10, 0, 0).unwrap();
+        assert_eq!(
+            "2024-07-01 13:00:00 +03:00",
+            local_time_to_string(&summer, lcph())
+        );
+    }
+}
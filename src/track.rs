@@ -0,0 +1,400 @@
+use chrono::{DateTime, Utc};
+use geo::{math::relative_bearing, LatLon};
+
+/// A single position sample recorded while a flight is in progress.
+#[derive(Clone, Debug)]
+pub struct TrackPoint {
+    pub time: DateTime<Utc>,
+    pub position: LatLon,
+    /// True heading in degrees.
+    pub heading: f64,
+    /// Altitude above sea level in feet.
+    pub elevation_ft: f64,
+}
+
+/// How many points the altitude is averaged over before looking for the top
+/// of climb and descent, 30s worth at a sample a second. That's enough to ride
+/// out turbulence and small corrections, but a level off for much longer still
+/// shows up as one.
+pub const ALTITUDE_SMOOTHING_WINDOW: usize = 30;
+
+/// Anything climbing or descending slower than this, in feet per minute, is
+/// level.
+const LEVEL_FPM: f64 = 300.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Vertical {
+    Climb,
+    Level,
+    Descent,
+}
+
+/// A stretch between two consecutive points, and which way it was flown.
+struct Segment {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    vertical: Vertical,
+}
+
+/// The positions an aircraft has flown through, in the order they were received.
+#[derive(Clone, Default)]
+pub struct Track(Vec<TrackPoint>);
+
+impl Track {
+    pub fn push(&mut self, point: TrackPoint) {
+        self.0.push(point);
+    }
+
+    pub fn points(&self) -> &[TrackPoint] {
+        &self.0
+    }
+
+    /// The points needed to draw the track to within `tolerance_m` meters,
+    /// dropping the rest with the Douglas-Peucker algorithm. The first and
+    /// last points are always kept.
+    ///
+    /// Only the horizontal position is taken into account, it's meant for
+    /// drawing the track on a map without the jitter of each sample.
+    pub fn simplified(&self, tolerance_m: f64) -> Vec<&TrackPoint> {
+        let points = &self.0;
+        if points.len() < 3 {
+            return points.iter().collect();
+        }
+        let mut keep = vec![false; points.len()];
+        keep[0] = true;
+        keep[points.len() - 1] = true;
+        // a stack rather than recursing, a long flight has tens of thousands
+        // of points
+        let mut ranges = vec![(0, points.len() - 1)];
+        while let Some((start, end)) = ranges.pop() {
+            let (from, to) = (&points[start].position, &points[end].position);
+            let furthest = (start + 1..end)
+                .map(|i| (i, distance_to_segment(&points[i].position, from, to)))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b));
+            if let Some((i, distance)) = furthest {
+                if distance > tolerance_m {
+                    keep[i] = true;
+                    ranges.push((start, i));
+                    ranges.push((i, end));
+                }
+            }
+        }
+        points
+            .iter()
+            .zip(keep)
+            .filter_map(|(point, keep)| keep.then_some(point))
+            .collect()
+    }
+
+    /// The bearing in degrees from the second to last point to the last, the
+    /// direction the aircraft is moving in. None without two points apart.
+    pub fn course(&self) -> Option<f64> {
+        let [.., from, to] = self.0.as_slice() else {
+            return None;
+        };
+        (from.position != to.position).then(|| from.position.bearing(&to.position))
+    }
+
+    /// The rate of turn between the last two points, see `turn_rate`.
+    pub fn turn_rate(&self) -> Option<f64> {
+        let [.., from, to] = self.0.as_slice() else {
+            return None;
+        };
+        turn_rate((from.time, from.heading), (to.time, to.heading))
+    }
+
+    /// The altitude at each point, averaged over the `window` points around it.
+    fn smoothed_altitudes(&self, window: usize) -> Vec<(DateTime<Utc>, f64)> {
+        let half = window / 2;
+        (0..self.0.len())
+            .map(|i| {
+                let around = &self.0[i.saturating_sub(half)..(i + half + 1).min(self.0.len())];
+                let sum: f64 = around.iter().map(|p| p.elevation_ft).sum();
+                (self.0[i].time, sum / around.len() as f64)
+            })
+            .collect()
+    }
+
+    /// Whether the aircraft was climbing, level or descending between each
+    /// pair of consecutive points, going by the smoothed altitude. It only
+    /// changes once the change has lasted for `window` segments, so noise
+    /// around `LEVEL_FPM` isn't taken for the start or end of a climb.
+    fn segments(&self, window: usize) -> Vec<Segment> {
+        let mut segments: Vec<Segment> = self
+            .smoothed_altitudes(window)
+            .windows(2)
+            .filter(|w| w[1].0 > w[0].0)
+            .map(|w| {
+                let minutes = (w[1].0 - w[0].0).num_milliseconds() as f64 / 60_000.0;
+                let fpm = (w[1].1 - w[0].1) / minutes;
+                let vertical = if fpm > LEVEL_FPM {
+                    Vertical::Climb
+                } else if fpm < -LEVEL_FPM {
+                    Vertical::Descent
+                } else {
+                    Vertical::Level
+                };
+                Segment {
+                    from: w[0].0,
+                    to: w[1].0,
+                    vertical,
+                }
+            })
+            .collect();
+        let raw: Vec<Vertical> = segments.iter().map(|s| s.vertical).collect();
+        let mut vertical = raw.first().copied().unwrap_or(Vertical::Level);
+        for (i, segment) in segments.iter_mut().enumerate() {
+            let sustained = &raw[i..(i + window).min(raw.len())];
+            if sustained.iter().all(|v| *v == raw[i]) {
+                vertical = raw[i];
+            }
+            segment.vertical = vertical;
+        }
+        segments
+    }
+
+    /// When the final descent started, i.e. the start of the last descent,
+    /// with the altitude smoothed over `window` points.
+    pub fn top_of_descent(&self, window: usize) -> Option<DateTime<Utc>> {
+        let segments = self.segments(window);
+        let descent = last_start(&segments, Vertical::Descent)?;
+        Some(segments[descent].from)
+    }
+
+    /// When the climb to the cruise ended, i.e. the end of the last climb
+    /// before the final descent, with the altitude smoothed over `window`
+    /// points. Levelling off for longer than the window on the way up doesn't
+    /// count as the top of climb, but a step climb in the cruise does.
+    pub fn top_of_climb(&self, window: usize) -> Option<DateTime<Utc>> {
+        let segments = self.segments(window);
+        let descent = last_start(&segments, Vertical::Descent).unwrap_or(segments.len());
+        let climbs = &segments[..descent];
+        climbs
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(i, segment)| {
+                segment.vertical == Vertical::Climb
+                    && climbs
+                        .get(i + 1)
+                        .is_none_or(|next| next.vertical != Vertical::Climb)
+            })
+            .map(|(_, segment)| segment.to)
+    }
+}
+
+/// The index of the first segment of the last run of `vertical` segments.
+fn last_start(segments: &[Segment], vertical: Vertical) -> Option<usize> {
+    (0..segments.len()).rev().find(|&i| {
+        segments[i].vertical == vertical && (i == 0 || segments[i - 1].vertical != vertical)
+    })
+}
+
+/// Distance in meters from `position` to the closest point between `from` and
+/// `to`, measured across the track flown from `from` to `to`.
+fn distance_to_segment(position: &LatLon, from: &LatLon, to: &LatLon) -> f64 {
+    let (distance, bearing) = from.distance_and_bearing(position);
+    let (length, track) = from.distance_and_bearing(to);
+    let angle = (bearing - track).to_radians();
+    let along = distance * angle.cos();
+    if along <= 0.0 {
+        distance
+    } else if along >= length {
+        position.distance(to)
+    } else {
+        (distance * angle.sin()).abs()
+    }
+}
+
+/// Rate of turn in degrees per second between two `(time, heading)` samples,
+/// positive turning right. Turns are taken the short way round, so samples
+/// need to be less than half a turn apart. `None` unless `to` is later than
+/// `from`.
+pub fn turn_rate(from: (DateTime<Utc>, f64), to: (DateTime<Utc>, f64)) -> Option<f64> {
+    let secs = (to.0 - from.0).num_milliseconds() as f64 / 1000.0;
+    if secs <= 0.0 {
+        return None;
+    }
+    Some(relative_bearing(from.1, to.1) / secs)
+}
+
+// a track grows by a sample every second, printing every point would bury
+// the rest of the flight when debugging
+impl std::fmt::Debug for Track {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Track({} points)", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_turn_rate() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let at = |secs| start + Duration::seconds(secs);
+        // a standard rate turn to the right, through north
+        assert_eq!(Some(3.0), turn_rate((at(0), 345.0), (at(10), 15.0)));
+        // and to the left
+        assert_eq!(Some(-3.0), turn_rate((at(0), 15.0), (at(10), 345.0)));
+        assert_eq!(Some(0.0), turn_rate((at(0), 90.0), (at(5), 90.0)));
+        assert_eq!(None, turn_rate((at(5), 90.0), (at(5), 95.0)));
+        assert_eq!(None, turn_rate((at(5), 90.0), (at(0), 95.0)));
+    }
+
+    fn track(positions: impl IntoIterator<Item = LatLon>) -> Track {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let mut track = Track::default();
+        for (i, position) in positions.into_iter().enumerate() {
+            track.push(TrackPoint {
+                time: start + Duration::seconds(i as i64),
+                position,
+                heading: 0.0,
+                elevation_ft: 0.0,
+            });
+        }
+        track
+    }
+
+    #[test]
+    fn test_simplified_straight_line() {
+        let origin = LatLon::new(34.7, 32.5);
+        // flying east, wandering 5m either side of the line
+        let track = track((0..100).map(|i| {
+            let jitter = if i % 2 == 0 { 5.0 } else { -5.0 };
+            origin
+                .destination(90.0, i as f64 * 100.0)
+                .destination(0.0, jitter)
+        }));
+        let simplified = track.simplified(20.0);
+        assert_eq!(2, simplified.len());
+        assert_eq!(track.points()[0].time, simplified[0].time);
+        assert_eq!(track.points()[99].time, simplified[1].time);
+        // nothing is dropped with a tolerance below the jitter
+        assert_eq!(100, track.simplified(1.0).len());
+    }
+
+    #[test]
+    fn test_simplified_keeps_turns() {
+        let origin = LatLon::new(34.7, 32.5);
+        let corner = origin.destination(90.0, 5000.0);
+        let track = track(
+            (0..=50)
+                .map(|i| origin.destination(90.0, i as f64 * 100.0))
+                .chain((1..=50).map(|i| corner.destination(0.0, i as f64 * 100.0))),
+        );
+        let simplified = track.simplified(20.0);
+        assert_eq!(3, simplified.len());
+        assert!(simplified[1].position.distance(&corner) < 1.0);
+    }
+
+    #[test]
+    fn test_course() {
+        let origin = LatLon::new(34.7, 32.5);
+        assert_eq!(None, track([origin]).course());
+        assert_eq!(None, track([origin, origin]).course());
+        let course = track([origin, origin.destination(275.0, 70.0)])
+            .course()
+            .unwrap();
+        assert!((course - 275.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_track_turn_rate() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let point = |secs, heading| TrackPoint {
+            time: start + Duration::seconds(secs),
+            position: LatLon::new(34.7, 32.5),
+            heading,
+            elevation_ft: 0.0,
+        };
+        let mut track = Track::default();
+        track.push(point(0, 100.0));
+        assert_eq!(None, track.turn_rate());
+        // holding the heading, give or take
+        track.push(point(1, 100.0));
+        track.push(point(2, 100.5));
+        assert!(track.turn_rate().unwrap().abs() < 1.0);
+        track.push(point(4, 94.5));
+        assert_eq!(Some(-3.0), track.turn_rate());
+    }
+
+    /// Climbing at 1500 fpm for 20 minutes to 30,000 ft, an hour in the
+    /// cruise and descending for 20 minutes, with a sample a second and some
+    /// noise on top.
+    fn trapezoid() -> (Track, DateTime<Utc>) {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let mut track = Track::default();
+        for i in 0..(120 * 60) {
+            let minutes = i as f64 / 60.0;
+            let altitude = match minutes {
+                m if m < 10.0 => 0.0,
+                m if m < 30.0 => (m - 10.0) * 1500.0,
+                m if m < 90.0 => 30000.0,
+                m if m < 110.0 => 30000.0 - (m - 90.0) * 1500.0,
+                _ => 0.0,
+            };
+            let noise = if i % 2 == 0 { 40.0 } else { -40.0 };
+            track.push(TrackPoint {
+                time: start + Duration::seconds(i),
+                position: LatLon::new(34.7, 32.5),
+                heading: 0.0,
+                elevation_ft: altitude + noise,
+            });
+        }
+        (track, start)
+    }
+
+    #[test]
+    fn test_top_of_climb_and_descent() {
+        let (track, start) = trapezoid();
+        let near = |expected: DateTime<Utc>, actual: Option<DateTime<Utc>>| {
+            (actual.unwrap() - expected).num_seconds().abs() <= 60
+        };
+        assert!(near(
+            start + Duration::minutes(30),
+            track.top_of_climb(ALTITUDE_SMOOTHING_WINDOW)
+        ));
+        assert!(near(
+            start + Duration::minutes(90),
+            track.top_of_descent(ALTITUDE_SMOOTHING_WINDOW)
+        ));
+    }
+
+    #[test]
+    fn test_level_off_in_the_climb() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let mut track = Track::default();
+        // climbing to 10,000 ft, holding it for 5 minutes, climbing to 20,000 ft
+        // and descending
+        let profile = [
+            0.0, 5000.0, 10000.0, 10000.0, 15000.0, 20000.0, 20000.0, 10000.0,
+        ];
+        for (i, elevation_ft) in profile.into_iter().enumerate() {
+            track.push(TrackPoint {
+                time: start + Duration::minutes(i as i64 * 5),
+                position: LatLon::new(34.7, 32.5),
+                heading: 0.0,
+                elevation_ft,
+            });
+        }
+        assert_eq!(Some(start + Duration::minutes(25)), track.top_of_climb(1));
+        assert_eq!(Some(start + Duration::minutes(30)), track.top_of_descent(1));
+    }
+
+    #[test]
+    fn test_no_climb() {
+        let mut track = Track::default();
+        assert_eq!(None, track.top_of_climb(ALTITUDE_SMOOTHING_WINDOW));
+        track.push(TrackPoint {
+            time: Utc::now(),
+            position: LatLon::new(34.7, 32.5),
+            heading: 0.0,
+            elevation_ft: 500.0,
+        });
+        assert_eq!(None, track.top_of_climb(ALTITUDE_SMOOTHING_WINDOW));
+        assert_eq!(None, track.top_of_descent(ALTITUDE_SMOOTHING_WINDOW));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::sim_connection::{SimConnection, SimMessage};
+use log::{info, warn};
+use std::time::{Duration, Instant};
+
+/// Warns when the sim stops sending `SimData`, e.g. because it hung, and
+/// optionally closes the connection so it's reconnected.
+///
+/// The watchdog fires once per stall, it's rearmed by the next `SimData`.
+pub struct Watchdog<S> {
+    sim: S,
+    timeout: Option<Duration>,
+    reconnect: bool,
+    last_data: Instant,
+    fired: bool,
+}
+
+impl<S: SimConnection> Watchdog<S> {
+    /// Watch `sim`, firing after `timeout` without data, or never if `None`.
+    pub fn new(sim: S, timeout: Option<Duration>, reconnect: bool) -> Self {
+        Self {
+            sim,
+            timeout,
+            reconnect,
+            last_data: Instant::now(),
+            fired: false,
+        }
+    }
+}
+
+impl<S: SimConnection> SimConnection for Watchdog<S> {
+    type Error = S::Error;
+
+    fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+        let msg = self.sim.next_message()?;
+        if let SimMessage::SimData(_) | SimMessage::SimDataBatch(_) = msg {
+            self.last_data = Instant::now();
+            self.fired = false;
+        } else if let Some(timeout) = self.timeout {
+            let elapsed = self.last_data.elapsed();
+            if !self.fired && elapsed >= timeout {
+                self.fired = true;
+                warn!(
+                    "no data received from the simulator for {}s.",
+                    elapsed.as_secs()
+                );
+                if self.reconnect {
+                    info!("Reconnecting to simulator...");
+                    self.sim.close();
+                }
+            }
+        }
+        Ok(msg)
+    }
+
+    fn close(&mut self) {
+        self.sim.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft::Aircraft;
+    use geo::LatLon;
+
+    /// Sends `data` samples, then stops producing data.
+    struct Stalling {
+        data: usize,
+        closed: usize,
+    }
+
+    impl SimConnection for Stalling {
+        type Error = ();
+
+        fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
+            if self.data == 0 {
+                return Ok(SimMessage::Waiting);
+            }
+            self.data -= 1;
+            Ok(SimMessage::SimData(Aircraft {
+                title: String::from("Challenger 650"),
+                icao: String::from("CL60"),
+                registration: String::from("C-FAAV"),
+                position: LatLon::new(34.717778, 32.485556),
+                engine_on: true,
+                on_ground: true,
+                ..Aircraft::default()
+            }))
+        }
+
+        fn close(&mut self) {
+            self.closed += 1;
+        }
+    }
+
+    fn run(watchdog: &mut Watchdog<Stalling>, messages: usize) {
+        for _ in 0..messages {
+            watchdog.next_message().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fires_once_when_data_stops() {
+        let sim = Stalling { data: 3, closed: 0 };
+        let mut watchdog = Watchdog::new(sim, Some(Duration::ZERO), true);
+        run(&mut watchdog, 3);
+        assert!(!watchdog.fired);
+
+        run(&mut watchdog, 5);
+        assert!(watchdog.fired);
+        assert_eq!(1, watchdog.sim.closed);
+    }
+
+    #[test]
+    fn test_rearmed_by_data() {
+        let sim = Stalling { data: 0, closed: 0 };
+        let mut watchdog = Watchdog::new(sim, Some(Duration::ZERO), true);
+        run(&mut watchdog, 2);
+        watchdog.sim.data = 1;
+        run(&mut watchdog, 3);
+        assert_eq!(2, watchdog.sim.closed);
+    }
+
+    #[test]
+    fn test_warn_only() {
+        let sim = Stalling { data: 0, closed: 0 };
+        let mut watchdog = Watchdog::new(sim, Some(Duration::ZERO), false);
+        run(&mut watchdog, 2);
+        assert!(watchdog.fired);
+        assert_eq!(0, watchdog.sim.closed);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let sim = Stalling { data: 0, closed: 0 };
+        let mut watchdog = Watchdog::new(sim, None, true);
+        run(&mut watchdog, 2);
+        assert!(!watchdog.fired);
+    }
+}
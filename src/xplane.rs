@@ -1,27 +1,89 @@
 use crate::{
-    aircraft::Aircraft,
+    aircraft::{normalize_icao, normalize_title, Aircraft},
     sim_connection::{SimConnection, SimMessage},
 };
 use geo::LatLon;
-use xp_sim_data::SimData;
+use xp_sim_data::{CoordinateUnits, SimData};
 use std::{
     io::{self, Read},
-    net::TcpStream,
+    net::{SocketAddr, TcpStream},
+    thread,
     time::Duration,
 };
 
 pub const SERVER_ADDR: &str = "127.0.0.1:52000";
 
+/// How long to wait between attempts to connect to the plugin.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A connection to the X-Plane plugin, reconnecting whenever it's lost.
 pub struct Xplane {
-    conn: TcpStream,
+    addr: SocketAddr,
+    conn: Option<TcpStream>,
+    // whether Connecting was sent since the connection was lost
+    connecting: bool,
+    retry_interval: Duration,
+    decoder: PacketDecoder,
+    // the last aircraft received, to fill in what the plugin couldn't read
+    last_aircraft: Option<Aircraft>,
+    // whether the records already received are sent together as a batch
+    batching: bool,
+    // a record that failed to decode, held back to return after the batch
+    // decoded before it
+    failed: Option<Box<dyn std::error::Error>>,
 }
 
 impl Xplane {
-    pub fn connect() -> Result<Self, io::Error> {
-        // todo: attempt reconnect if closed
-        let conn = TcpStream::connect(SERVER_ADDR)?;
-        conn.set_read_timeout(Some(Duration::from_secs(1)))?;
-        Ok(Xplane { conn, })
+    pub fn connect() -> Self {
+        Self::connect_to(SERVER_ADDR.parse().expect("invalid server address"))
+    }
+
+    /// Connect to a plugin listening on `addr`, the connection isn't made
+    /// until the first message is requested.
+    pub fn connect_to(addr: SocketAddr) -> Self {
+        Xplane {
+            addr,
+            conn: None,
+            connecting: false,
+            retry_interval: RETRY_INTERVAL,
+            decoder: PacketDecoder::default(),
+            last_aircraft: None,
+            batching: false,
+            failed: None,
+        }
+    }
+
+    /// Send the records that were received together in a single
+    /// `SimDataBatch`, rather than one `SimData` for each, so that they can be
+    /// handled at once.
+    pub fn batched(self) -> Self {
+        Self {
+            batching: true,
+            ..self
+        }
+    }
+
+    fn try_connect(&mut self) -> Result<SimMessage, io::Error> {
+        // let the UI know before blocking on the connection
+        if !self.connecting {
+            self.connecting = true;
+            return Ok(SimMessage::Connecting);
+        }
+        match TcpStream::connect(self.addr) {
+            Ok(conn) => {
+                conn.set_read_timeout(Some(Duration::from_secs(1)))?;
+                self.conn = Some(conn);
+                self.connecting = false;
+                // anything left over was cut off by the previous connection
+                self.decoder = PacketDecoder::default();
+                Ok(SimMessage::Open)
+            }
+            Err(_) => {
+                // the sim isn't running (yet), try again later
+                thread::sleep(self.retry_interval);
+                Ok(SimMessage::Connecting)
+            }
+        }
     }
 }
 
@@ -29,41 +91,413 @@ impl SimConnection for Xplane {
     type Error = Box<dyn std::error::Error>;
 
     fn next_message(&mut self) -> Result<SimMessage, Self::Error> {
-        match read_packet(&mut self.conn) {
-            Ok(buf) => {
-                let msg = std::str::from_utf8(&buf)?;
-                let sim_data = SimData::from_csv(msg)?;
-                Ok(SimMessage::SimData(Aircraft::from(sim_data)))
+        let Some(conn) = self.conn.as_mut() else {
+            return Ok(self.try_connect()?);
+        };
+        if let Some(e) = self.failed.take() {
+            return Err(e);
+        }
+        loop {
+            if let Some(packet) = self.decoder.next_packet() {
+                let aircraft = decode(&packet, &mut self.last_aircraft)?;
+                if !self.batching {
+                    return Ok(SimMessage::SimData(aircraft));
+                }
+                let mut batch = vec![aircraft];
+                while let Some(packet) = self.decoder.next_packet() {
+                    match decode(&packet, &mut self.last_aircraft) {
+                        Ok(aircraft) => batch.push(aircraft),
+                        Err(e) => {
+                            self.failed = Some(e);
+                            break;
+                        }
+                    }
+                }
+                return Ok(match batch.len() {
+                    1 => SimMessage::SimData(batch.remove(0)),
+                    _ => SimMessage::SimDataBatch(batch),
+                });
+            }
+            let mut chunk = [0; 256];
+            match conn.read(&mut chunk) {
+                Ok(0) => {
+                    self.conn = None;
+                    return Ok(SimMessage::Quit);
+                }
+                Ok(n) => self.decoder.push(&chunk[..n]),
+                // windows reports timeouts as TimedOut, unix as WouldBlock
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::WouldBlock =>
+                {
+                    return Ok(SimMessage::Waiting)
+                }
+                Err(ref e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    self.conn = None;
+                    return Ok(SimMessage::Quit);
+                }
+                Err(e) => return Err(Box::new(e)),
             }
-            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => Ok(SimMessage::Waiting),
-            Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => Ok(SimMessage::Quit),
-            Err(e) => Err(Box::new(e)),
         }
     }
+
+    fn close(&mut self) {
+        self.conn = None;
+        self.connecting = false;
+    }
 }
 
-fn read_packet(stream: &mut TcpStream) -> Result<Vec<u8>, std::io::Error> {
-    let packet_size = next_packet_size(stream)?;
-    let mut buf = vec![0; packet_size];
-    stream.read_exact(&mut buf)?;
-    Ok(buf)
+/// Reassembles the packets sent by the plugin, each a little endian `u16`
+/// length followed by that many bytes, from however the bytes arrive.
+///
+/// Only whole packets are handed out, so a record split between reads, even
+/// in the middle of a character, is decoded once all of it has arrived.
+#[derive(Default)]
+struct PacketDecoder {
+    buf: Vec<u8>,
 }
 
-fn next_packet_size(stream: &mut TcpStream) -> Result<usize, std::io::Error> {
-    let mut buf = [0; 2];
-    stream.read_exact(&mut buf)?;
-    Ok(u16::from_le_bytes(buf) as usize)
+impl PacketDecoder {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn next_packet(&mut self) -> Option<Vec<u8>> {
+        let header = self.buf.get(..2)?;
+        let packet_size = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let packet = self.buf.get(2..2 + packet_size)?.to_vec();
+        self.buf.drain(..2 + packet_size);
+        Some(packet)
+    }
 }
 
 impl From<SimData> for Aircraft {
     fn from(sim_data: SimData) -> Self {
         Self {
-            title: sim_data.name,
-            icao: sim_data.icao,
-            registration: sim_data.registration,
-            position: LatLon::new(sim_data.latitude, sim_data.longitude),
+            title: normalize_title(&sim_data.name),
+            icao: normalize_icao(sim_data.icao.as_deref().unwrap_or_default()),
+            registration: sim_data.registration.unwrap_or_default(),
+            position: match sim_data.coordinate_units {
+                CoordinateUnits::Degrees => LatLon::new(sim_data.latitude, sim_data.longitude),
+                CoordinateUnits::Radians => {
+                    LatLon::from_radians(sim_data.latitude, sim_data.longitude)
+                }
+            },
             engine_on: sim_data.engine_on,
             on_ground: sim_data.on_ground,
+            gear_on_ground: sim_data.gear_on_ground,
+            ground_speed: sim_data.ground_speed,
+            agl_ft: sim_data.agl_ft,
+            true_airspeed_kt: sim_data.true_airspeed_kt,
+            parking_brake: sim_data.parking_brake,
+            // plugins sent 0 before versioning the records
+            protocol_version: Some(sim_data.protocol_version).filter(|v| *v > 0),
+            paused: sim_data.paused,
+            elevation_ft: sim_data.elevation_ft,
+            baro_altitude_ft: sim_data.baro_altitude_ft,
+            heading: sim_data.heading,
         }
     }
 }
+
+/// Decode a packet's record, keeping the aircraft as the `last_aircraft`.
+fn decode(
+    packet: &[u8],
+    last_aircraft: &mut Option<Aircraft>,
+) -> Result<Aircraft, Box<dyn std::error::Error>> {
+    let sim_data = SimData::from_csv(std::str::from_utf8(packet)?)?;
+    let aircraft = to_aircraft(sim_data, last_aircraft.as_ref());
+    *last_aircraft = Some(aircraft.clone());
+    Ok(aircraft)
+}
+
+/// Convert the record, taking the ICAO code and registration the plugin
+/// couldn't read (e.g. while the aircraft is still loading) from the
+/// `previous` aircraft, if it's the same one.
+fn to_aircraft(sim_data: SimData, previous: Option<&Aircraft>) -> Aircraft {
+    let missing_icao = sim_data.icao.is_none();
+    let missing_registration = sim_data.registration.is_none();
+    let mut aircraft = Aircraft::from(sim_data);
+    if let Some(previous) = previous.filter(|previous| previous.title == aircraft.title) {
+        if missing_icao {
+            aircraft.icao = previous.icao.clone();
+        }
+        if missing_registration {
+            aircraft.registration = previous.registration.clone();
+        }
+    }
+    aircraft
+}
+
+impl From<&Aircraft> for SimData {
+    fn from(aircraft: &Aircraft) -> Self {
+        let mut sim_data = SimData::builder()
+            .name(aircraft.title.clone())
+            .icao(Some(aircraft.icao.clone()).filter(|icao| !icao.is_empty()))
+            .registration(Some(aircraft.registration.clone()).filter(|r| !r.is_empty()))
+            .position(aircraft.position.latitude(), aircraft.position.longitude())
+            .engine_on(aircraft.engine_on)
+            .on_ground(aircraft.on_ground)
+            .ground_speed(aircraft.ground_speed)
+            .agl_ft(aircraft.agl_ft)
+            .true_airspeed_kt(aircraft.true_airspeed_kt)
+            .parking_brake(aircraft.parking_brake)
+            .protocol_version(aircraft.protocol_version.unwrap_or(0))
+            .paused(aircraft.paused)
+            .elevation_ft(aircraft.elevation_ft)
+            .baro_altitude_ft(aircraft.baro_altitude_ft)
+            .heading(aircraft.heading)
+            .build();
+        sim_data.gear_on_ground = aircraft.gear_on_ground;
+        sim_data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, net::TcpListener};
+
+    fn send_packet(stream: &mut TcpStream, msg: &str) {
+        stream.write_all(&(msg.len() as u16).to_le_bytes()).unwrap();
+        stream.write_all(msg.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_reconnect_sequence() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut xplane = Xplane::connect_to(listener.local_addr().unwrap());
+
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Connecting)));
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Open)));
+
+        let (mut plugin, _) = listener.accept().unwrap();
+        send_packet(
+            &mut plugin,
+            "CL60,Challenger 650,C-FAAV,32.000123,42.000123,false,true,0,0,0\r\n",
+        );
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::SimData(_))));
+
+        // the sim was closed
+        drop(plugin);
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Quit)));
+
+        // and started again
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Connecting)));
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Open)));
+    }
+
+    #[test]
+    fn test_several_records_in_one_read() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut xplane = Xplane::connect_to(listener.local_addr().unwrap());
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Connecting)));
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Open)));
+
+        // queued up by the plugin before we got to reading any of them
+        let (mut plugin, _) = listener.accept().unwrap();
+        let registrations = ["C-FAAV", "C-FAAW", "C-FAAX"];
+        let mut bytes = vec![];
+        for registration in registrations {
+            bytes.extend(packet(&format!(
+                "CL60,Challenger 650,{registration},32.000123,42.000123,false,true,0,0,0\r\n"
+            )));
+        }
+        plugin.write_all(&bytes).unwrap();
+
+        for registration in registrations {
+            match xplane.next_message() {
+                Ok(SimMessage::SimData(aircraft)) => {
+                    assert_eq!(registration, aircraft.registration)
+                }
+                msg => panic!("expected sim data, got {msg:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_batched_records() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut xplane = Xplane::connect_to(listener.local_addr().unwrap()).batched();
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Connecting)));
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Open)));
+
+        let (mut plugin, _) = listener.accept().unwrap();
+        let registrations = ["C-FAAV", "C-FAAW", "C-FAAX"];
+        let mut bytes = vec![];
+        for registration in registrations {
+            bytes.extend(packet(&format!(
+                "CL60,Challenger 650,{registration},32.000123,42.000123,false,true,0,0,0\r\n"
+            )));
+        }
+        plugin.write_all(&bytes).unwrap();
+
+        match xplane.next_message() {
+            Ok(SimMessage::SimDataBatch(batch)) => assert_eq!(
+                registrations.to_vec(),
+                batch
+                    .iter()
+                    .map(|aircraft| aircraft.registration.as_str())
+                    .collect::<Vec<_>>()
+            ),
+            msg => panic!("expected a batch, got {msg:?}"),
+        }
+    }
+
+    #[test]
+    fn test_batch_before_malformed_record() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut xplane = Xplane::connect_to(listener.local_addr().unwrap()).batched();
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Connecting)));
+        assert!(matches!(xplane.next_message(), Ok(SimMessage::Open)));
+
+        let (mut plugin, _) = listener.accept().unwrap();
+        let record = |registration| {
+            packet(&format!(
+                "CL60,Challenger 650,{registration},32.000123,42.000123,false,true,0,0,0\r\n"
+            ))
+        };
+        let mut bytes = vec![];
+        bytes.extend(record("C-FAAV"));
+        bytes.extend(record("C-FAAW"));
+        bytes.extend(packet("not a record\r\n"));
+        bytes.extend(record("C-FAAX"));
+        plugin.write_all(&bytes).unwrap();
+
+        // the records before the malformed one are still handed out first
+        match xplane.next_message() {
+            Ok(SimMessage::SimDataBatch(batch)) => assert_eq!(2, batch.len()),
+            msg => panic!("expected a batch, got {msg:?}"),
+        }
+        assert!(xplane.next_message().is_err());
+        match xplane.next_message() {
+            Ok(SimMessage::SimData(aircraft)) => assert_eq!("C-FAAX", aircraft.registration),
+            msg => panic!("expected a record, got {msg:?}"),
+        }
+    }
+
+    #[test]
+    fn test_connecting_while_sim_is_down() {
+        // grab a free port, then close it so nothing is listening
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut xplane = Xplane::connect_to(addr);
+        xplane.retry_interval = Duration::ZERO;
+
+        for _ in 0..3 {
+            assert!(matches!(xplane.next_message(), Ok(SimMessage::Connecting)));
+        }
+    }
+
+    #[test]
+    fn test_sim_data_aircraft_round_trip() {
+        let sim_data = SimData::builder()
+            .icao(Some("CL60"))
+            .name("Challenger 650")
+            .registration(Some("C-FAAV"))
+            .position(34.717778, 32.485556)
+            .engine_on(true)
+            .ground_speed(140.0)
+            .agl_ft(1200.0)
+            .true_airspeed_kt(150.0)
+            .parking_brake(true)
+            .protocol_version(1)
+            .paused(true)
+            .elevation_ft(1350.0)
+            .baro_altitude_ft(1325.0)
+            .heading(95.0)
+            .build();
+        let aircraft = Aircraft::from(sim_data.clone());
+        assert_eq!(sim_data, SimData::from(&aircraft));
+    }
+
+    #[test]
+    fn test_sim_data_normalized() {
+        let sim_data = SimData::builder()
+            .icao(Some(" cl60\0"))
+            .name("Challenger 650 ")
+            .build();
+        let aircraft = Aircraft::from(sim_data);
+        assert_eq!("CL60", aircraft.icao);
+        assert_eq!("Challenger 650", aircraft.title);
+    }
+
+    #[test]
+    fn test_sim_data_fills_gaps() {
+        let known = Aircraft::from(
+            SimData::builder()
+                .icao(Some("CL60"))
+                .name("Challenger 650")
+                .registration(Some("C-FAAV"))
+                .build(),
+        );
+        let loading = SimData::builder()
+            .icao(Some("CL60"))
+            .name("Challenger 650")
+            .build();
+        assert_eq!("", Aircraft::from(loading.clone()).registration);
+        assert_eq!("C-FAAV", to_aircraft(loading, Some(&known)).registration);
+
+        // a blank ICAO code isn't taken from another aircraft
+        let other = SimData::builder()
+            .name("Cessna 172")
+            .registration(Some("N172SP"))
+            .build();
+        let aircraft = to_aircraft(other, Some(&known));
+        assert_eq!("", aircraft.icao);
+        assert_eq!("N172SP", aircraft.registration);
+    }
+
+    #[test]
+    fn test_sim_data_in_radians() {
+        let sim_data = SimData::builder()
+            .position(34.717778_f64.to_radians(), 32.485556_f64.to_radians())
+            .coordinate_units(CoordinateUnits::Radians)
+            .build();
+        let aircraft = Aircraft::from(sim_data);
+        assert!((aircraft.position.latitude() - 34.717778).abs() < 1e-9);
+        assert!((aircraft.position.longitude() - 32.485556).abs() < 1e-9);
+    }
+
+    fn packet(msg: &str) -> Vec<u8> {
+        let mut packet = (msg.len() as u16).to_le_bytes().to_vec();
+        packet.extend_from_slice(msg.as_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_decoder_character_split_between_reads() {
+        let bytes = packet("DR40,Robin DR400 Régent,F-GLTM,45.0,5.0,true,true,0,0,0\r\n");
+        // in the middle of the é
+        let split = bytes.iter().position(|b| *b == 0xC3).unwrap() + 1;
+        let mut decoder = PacketDecoder::default();
+
+        decoder.push(&bytes[..split]);
+        assert_eq!(None, decoder.next_packet());
+        decoder.push(&bytes[split..]);
+        let packet = decoder.next_packet().unwrap();
+        let sim_data = SimData::from_csv(std::str::from_utf8(&packet).unwrap()).unwrap();
+        assert_eq!("Robin DR400 Régent", sim_data.name);
+        assert_eq!(None, decoder.next_packet());
+    }
+
+    #[test]
+    fn test_decoder_several_packets_in_one_read() {
+        let mut bytes = packet("first");
+        bytes.extend(packet("second"));
+        bytes.extend(&packet("third")[..3]);
+        let mut decoder = PacketDecoder::default();
+
+        decoder.push(&bytes);
+        assert_eq!(Some(b"first".to_vec()), decoder.next_packet());
+        assert_eq!(Some(b"second".to_vec()), decoder.next_packet());
+        assert_eq!(None, decoder.next_packet());
+    }
+}